@@ -3,12 +3,15 @@ use std::io::Write;
 use clap::{Parser, Subcommand};
 
 use crate::subcmd::check::check;
+use crate::subcmd::compile_dict::compile_dict;
+use crate::subcmd::convert_dict::convert_dict;
 use crate::subcmd::dump_bigram_dict::dump_bigram_dict;
 use crate::subcmd::dump_unigram_dict::dump_unigram_dict;
 use crate::subcmd::evaluate::evaluate;
-use crate::subcmd::learn_corpus::learn_corpus;
+use crate::subcmd::learn_corpus::{learn_corpus, LearningOptions};
 use crate::subcmd::make_dict::make_system_dict;
 use crate::subcmd::make_stats_system_bigram_lm::make_stats_system_bigram_lm;
+use crate::subcmd::make_stats_system_trigram_lm::make_stats_system_trigram_lm;
 use crate::subcmd::make_stats_system_unigram_lm::make_stats_system_unigram_lm;
 use crate::subcmd::tokenize::{
     tokenize_aozora_bunko_vibrato_ipadic, tokenize_lindera_ipadic, tokenize_vibrato_ipadic,
@@ -49,7 +52,13 @@ enum Commands {
     MakeSystemDict(MakeSystemDictArgs),
     #[clap(arg_required_else_help = true)]
     MakeStatsSystemBigramLM(MakeStatsSystemBigramLMArgs),
+    #[clap(arg_required_else_help = true)]
+    MakeStatsSystemTrigramLM(MakeStatsSystemTrigramLMArgs),
     MakeStatsSystemUnigramLM(MakeStatsSystemUnigramLMArgs),
+    #[clap(arg_required_else_help = true)]
+    CompileDict(CompileDictArgs),
+    #[clap(arg_required_else_help = true)]
+    ConvertDict(ConvertDictArgs),
 
     #[clap(arg_required_else_help = true)]
     Evaluate(EvaluateArgs),
@@ -72,11 +81,40 @@ struct MakeSystemDictArgs {
     txt_file: String,
 }
 
+/// SKK 辞書を、起動時に mmap で読み込めるバイナリ形式にコンパイルする。
+#[derive(Debug, clap::Args)]
+struct CompileDictArgs {
+    /// 辞書ファイルの文字コード。省略時は UTF-8
+    #[arg(short, long)]
+    encoding: Option<String>,
+    src_file: String,
+    dst_file: String,
+}
+
+/// 辞書の形式を変換する。Mozc や MS-IME のユーザー辞書を SKK 辞書にする、など。
+#[derive(Debug, clap::Args)]
+struct ConvertDictArgs {
+    /// 変換元の辞書の形式。skk, mozc, msime, atok
+    #[arg(long)]
+    from: String,
+    /// 変換先の辞書の形式。skk, mozc, msime, atok
+    #[arg(long)]
+    to: String,
+    /// 変換元の辞書ファイルの文字コード。省略時は UTF-8
+    #[arg(short, long)]
+    encoding: Option<String>,
+    src_file: String,
+    dst_file: String,
+}
+
 /// 変換精度を評価する
 #[derive(Debug, clap::Args)]
 struct EvaluateArgs {
     #[arg(long)]
     load_user_config: bool,
+    /// 上位 N 件の変換結果に正解が含まれているかも集計する
+    #[arg(long, default_value_t = 1)]
+    nbest: usize,
     /// コーパスが格納されているディレクトリ
     corpus_dir: String,
 }
@@ -96,6 +134,27 @@ struct CheckArgs {
 struct LearnCorpusArgs {
     #[arg(short, long)]
     delta: f32,
+    /// AdaGrad で素性ごとに更新量を調整する
+    #[arg(long)]
+    adagrad: bool,
+    /// コーパスをシャッフルするときの乱数のシード
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// エポックごとに変換精度を評価するためのコーパス
+    #[arg(long)]
+    dev_corpus: Option<String>,
+    /// dev コーパスでの精度が改善しないまま、このエポック数が過ぎたら学習を打ち切る
+    #[arg(long)]
+    patience: Option<usize>,
+    /// エポックごとに平均化したモデルを保存するディレクトリ
+    #[arg(long)]
+    checkpoint_dir: Option<String>,
+    /// 素性の重みも学習して、このファイルに保存する
+    #[arg(long)]
+    feature_model: Option<String>,
+    /// 変換時に使う single term 辞書。single_term 素性の重みを学習するために指定する
+    #[arg(long)]
+    single_term_dict: Vec<String>,
     #[arg(long, default_value_t = 10)]
     may_epochs: i32,
     #[arg(long, default_value_t = 100)]
@@ -164,9 +223,23 @@ struct MakeStatsSystemBigramLMArgs {
     #[arg(long)]
     corpus_dirs: Vec<String>,
     unigram_trie_file: String,
+    /// バックオフ重みをつけて書き出すユニグラム言語モデル
+    dst_unigram_trie_file: String,
     bigram_trie_file: String,
 }
 
+/// トライグラム言語モデルを生成する。
+#[derive(Debug, clap::Args)]
+struct MakeStatsSystemTrigramLMArgs {
+    #[arg(short, long)]
+    threshold: u32,
+    #[arg(long)]
+    corpus_dirs: Vec<String>,
+    /// 実行時に読み込まれるユニグラム言語モデル。単語IDをそろえるために使う。
+    unigram_trie_file: String,
+    trigram_trie_file: String,
+}
+
 /// ユニグラム辞書ファイルをダンプする
 #[derive(Debug, clap::Args)]
 struct DumpUnigramDictArgs {
@@ -204,10 +277,27 @@ fn main() -> anyhow::Result<()> {
         Commands::MakeSystemDict(opt) => {
             make_system_dict(&opt.txt_file, Some(opt.vocab_file.as_str()), opt.corpus)
         }
-        Commands::Evaluate(opt) => evaluate(&opt.corpus_dir, opt.load_user_config),
+        Commands::CompileDict(opt) => compile_dict(&opt.src_file, &opt.dst_file, opt.encoding),
+        Commands::ConvertDict(opt) => convert_dict(
+            &opt.src_file,
+            &opt.from,
+            opt.encoding,
+            &opt.dst_file,
+            &opt.to,
+        ),
+        Commands::Evaluate(opt) => evaluate(&opt.corpus_dir, opt.load_user_config, opt.nbest),
         Commands::Check(opt) => check(&opt.yomi, opt.expected, opt.user_data),
         Commands::LearnCorpus(opts) => learn_corpus(
-            opts.delta,
+            &LearningOptions {
+                delta: opts.delta,
+                adagrad: opts.adagrad,
+                seed: opts.seed,
+                dev_corpus: opts.dev_corpus,
+                patience: opts.patience,
+                checkpoint_dir: opts.checkpoint_dir,
+                feature_model: opts.feature_model,
+                single_term_dicts: opts.single_term_dict,
+            },
             opts.may_epochs,
             opts.should_epochs,
             opts.must_epochs,
@@ -247,8 +337,15 @@ fn main() -> anyhow::Result<()> {
             opt.threshold,
             &opt.corpus_dirs,
             &opt.unigram_trie_file,
+            &opt.dst_unigram_trie_file,
             &opt.bigram_trie_file,
         ),
+        Commands::MakeStatsSystemTrigramLM(opt) => make_stats_system_trigram_lm(
+            opt.threshold,
+            &opt.corpus_dirs,
+            &opt.unigram_trie_file,
+            &opt.trigram_trie_file,
+        ),
         Commands::MakeStatsSystemUnigramLM(opt) => {
             make_stats_system_unigram_lm(opt.src_file.as_str(), opt.dst_file.as_str())
         }
@@ -15,14 +15,17 @@ pub fn check(yomi: &str, expected: Option<String>, user_data: bool) -> anyhow::R
                 dict_type: "skk".to_string(),
                 encoding: Some("euc-jp".to_string()),
                 path: "skk-dev-dict/SKK-JISYO.L".to_string(),
+                ..Default::default()
             },
             DictConfig {
                 dict_type: "skk".to_string(),
                 encoding: Some("utf-8".to_string()),
                 path: "data/SKK-JISYO.akaza".to_string(),
+                ..Default::default()
             },
         ],
         single_term: None,
+        ..Default::default()
     });
     if user_data {
         info!("Enabled user data");
@@ -0,0 +1,16 @@
+use anyhow::Result;
+
+use libakaza::config::DictConfig;
+use libakaza::dict::compiled_dict::CompiledDict;
+use libakaza::dict::loader::load_dict;
+
+/// SKK 辞書を、起動時に mmap で読み込めるバイナリ形式にコンパイルする。
+pub fn compile_dict(src_file: &str, dst_file: &str, encoding: Option<String>) -> Result<()> {
+    let dict = load_dict(&DictConfig {
+        path: src_file.to_string(),
+        encoding,
+        dict_type: "skk".to_string(),
+        ..Default::default()
+    })?;
+    CompiledDict::save(&dict, dst_file)
+}
@@ -0,0 +1,28 @@
+use anyhow::{bail, Result};
+
+use libakaza::config::DictConfig;
+use libakaza::dict::ime_dict::{write_mozc_dict, write_msime_dict};
+use libakaza::dict::loader::load_dict;
+use libakaza::dict::skk::write::write_skk_dict;
+
+/// 辞書の形式を変換する。形式は skk, mozc, msime(atok) のいずれか。
+pub fn convert_dict(
+    src_file: &str,
+    src_type: &str,
+    encoding: Option<String>,
+    dst_file: &str,
+    dst_type: &str,
+) -> Result<()> {
+    let dict = load_dict(&DictConfig {
+        path: src_file.to_string(),
+        encoding,
+        dict_type: src_type.to_string(),
+        ..Default::default()
+    })?;
+    match dst_type {
+        "skk" => write_skk_dict(dst_file, vec![dict]),
+        "mozc" => write_mozc_dict(dst_file, &dict),
+        "msime" | "atok" => write_msime_dict(dst_file, &dict),
+        _ => bail!("Unknown dictionary type: {}", dst_type),
+    }
+}
@@ -45,7 +45,10 @@ impl SaigenRitsu {
 /// にのっている評価方法を採用。
 ///
 /// なぜこうしているかというと、mozc の論文にのっている BLEU を使用する方式より実装が楽だからです!
-pub fn evaluate(corpus_dir: &String, load_user_config: bool) -> anyhow::Result<()> {
+///
+/// nbest に 2 以上を指定すると、上位 N 件の中に正解が含まれている割合もあわせて集計する。
+/// リランキングの実験をする際の上限の目安になる。
+pub fn evaluate(corpus_dir: &String, load_user_config: bool, nbest: usize) -> anyhow::Result<()> {
     /*
     # corpus.0.txt デバッグ用のファイル
     # corpus.1.txt メイン(候補割り当ても含む)
@@ -69,20 +72,24 @@ pub fn evaluate(corpus_dir: &String, load_user_config: bool) -> anyhow::Result<(
                 dict_type: "skk".to_string(),
                 encoding: Some("euc-jp".to_string()),
                 path: "skk-dev-dict/SKK-JISYO.L".to_string(),
+                ..Default::default()
             },
             DictConfig {
                 dict_type: "skk".to_string(),
                 encoding: Some("utf-8".to_string()),
                 path: "data/SKK-JISYO.akaza".to_string(),
+                ..Default::default()
             },
         ],
         single_term: None,
+        ..Default::default()
     })
     .load_user_config(load_user_config)
     .build()?;
 
     let mut good_cnt = 0;
     let mut bad_cnt = 0;
+    let mut nbest_good_cnt = 0;
 
     let force_ranges = Vec::new();
     let total_t1 = SystemTime::now();
@@ -117,6 +124,19 @@ pub fn evaluate(corpus_dir: &String, load_user_config: bool) -> anyhow::Result<(
             // 最長共通部分列を算出。
             saigen_ritsu.add(&surface, &got);
 
+            let nbest_result = if nbest > 1 {
+                akaza.convert_nbest(yomi.as_str(), Some(&force_ranges), nbest)?
+            } else {
+                Vec::new()
+            };
+            if surface == got
+                || nbest_result
+                    .iter()
+                    .any(|sentence| sentence.surface() == surface)
+            {
+                nbest_good_cnt += 1;
+            }
+
             if surface == got {
                 info!("{} => (teacher={}, akaza={})", yomi, surface, got);
                 good_cnt += 1;
@@ -135,6 +155,12 @@ pub fn evaluate(corpus_dir: &String, load_user_config: bool) -> anyhow::Result<(
                     saigen_ritsu.rate()
                 );
 
+                for (i, sentence) in nbest_result.iter().enumerate() {
+                    let clauses: Vec<String> =
+                        sentence.clauses.iter().map(|c| c.surface.clone()).collect();
+                    println!("   {}: {} cost={}", i + 1, clauses.join("|"), sentence.cost);
+                }
+
                 // 遅いなと思ったら cargo run --release になってるか確認すべし
                 // https://codom.hatenablog.com/entry/2017/06/03/221318
 
@@ -153,6 +179,14 @@ pub fn evaluate(corpus_dir: &String, load_user_config: bool) -> anyhow::Result<(
         total_elapsed.as_millis(),
         saigen_ritsu.rate(),
     );
+    if nbest > 1 {
+        info!(
+            "{}-best good count={} ({}%)",
+            nbest,
+            nbest_good_cnt,
+            100.0 * (nbest_good_cnt as f32) / ((good_cnt + bad_cnt) as f32)
+        );
+    }
 
     Ok(())
 }
@@ -1,17 +1,22 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
+use std::hash::Hash;
 use std::path::Path;
-use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
 use encoding_rs::UTF_8;
-use log::{debug, info};
+use log::{debug, info, warn};
 
 use libakaza::corpus::{read_corpus_file, FullAnnotationCorpus};
-use libakaza::dict::skk::read::read_skkdict;
+use libakaza::cost_model::default_cost_model::DefaultCostModel;
+use libakaza::cost_model::feature_cost_model::FeatureCostModel;
+use libakaza::cost_model::features::{path_features, FeatureWeights};
+use libakaza::dict::merge_dict::merge_dict;
+use libakaza::dict::skk::read::{read_skkdict, split_annotation};
+use libakaza::graph::candidate::Candidate;
 use libakaza::graph::graph_builder::GraphBuilder;
 use libakaza::graph::graph_resolver::GraphResolver;
 use libakaza::graph::segmenter::Segmenter;
+use libakaza::graph::word_node::WordNode;
 use libakaza::kana_trie::cedarwood_kana_trie::CedarwoodKanaTrie;
 use libakaza::lm::base::{SystemBigramLM, SystemUnigramLM};
 use libakaza::lm::on_memory::on_memory_system_bigram_lm::OnMemorySystemBigramLM;
@@ -20,17 +25,153 @@ use libakaza::lm::system_bigram::{MarisaSystemBigramLM, MarisaSystemBigramLMBuil
 use libakaza::lm::system_unigram_lm::{MarisaSystemUnigramLM, MarisaSystemUnigramLMBuilder};
 use libakaza::user_side_data::user_data::UserData;
 
+/// 学習の設定
+pub struct LearningOptions {
+    /// 1回の更新で動かすコストの量
+    pub delta: f32,
+    /// AdaGrad で素性ごとに更新量を調整する
+    pub adagrad: bool,
+    /// コーパスをシャッフルするときの乱数のシード
+    pub seed: u64,
+    /// エポックごとに変換精度を評価するためのコーパス
+    pub dev_corpus: Option<String>,
+    /// dev コーパスでの精度が、このエポック数のあいだ改善しなければ学習を打ち切る
+    pub patience: Option<usize>,
+    /// エポックごとに平均化したモデルを保存するディレクトリ
+    pub checkpoint_dir: Option<String>,
+    /// 指定されていれば、素性の重みも学習してこのファイルに保存する
+    pub feature_model: Option<String>,
+    /// 変換時に使う single term 辞書(UTF-8 の SKK 辞書)。single_term 素性を学習するために使う。
+    pub single_term_dicts: Vec<String>,
+}
+
+/**
+ * 平均化パーセプトロンのための、更新量の履歴。
+ *
+ * 毎回すべての重みを足し込むのは重いので、更新のたびに「それまでに見た事例数 × 更新量」を
+ * 足し込んでおき、平均は 現在の重み - 足し込んだ値 / 事例数 として求める。
+ */
+#[derive(Clone)]
+struct AveragedWeights<K> {
+    accumulated: HashMap<K, f32>,
+    // AdaGrad 用の勾配の二乗和
+    squared_gradients: HashMap<K, f32>,
+}
+
+impl<K: Hash + Eq + Clone> AveragedWeights<K> {
+    fn new() -> Self {
+        AveragedWeights {
+            accumulated: HashMap::new(),
+            squared_gradients: HashMap::new(),
+        }
+    }
+
+    /// 勾配から更新量を求めて記録し、更新量を返す。
+    /// steps は、この事例より前に見た事例数。
+    fn update(&mut self, key: &K, gradient: f32, delta: f32, adagrad: bool, steps: u32) -> f32 {
+        let amount = if adagrad {
+            let squared = self.squared_gradients.entry(key.clone()).or_insert(0_f32);
+            *squared += gradient * gradient;
+            delta * gradient / squared.sqrt()
+        } else {
+            delta * gradient
+        };
+        *self.accumulated.entry(key.clone()).or_insert(0_f32) += steps as f32 * amount;
+        amount
+    }
+
+    /// steps 個の事例を見たあとの平均の重み。
+    fn average(&self, key: &K, current: f32, steps: u32) -> f32 {
+        match self.accumulated.get(key) {
+            Some(accumulated) if steps > 0 => current - accumulated / steps as f32,
+            _ => current,
+        }
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &K> {
+        self.accumulated.keys()
+    }
+}
+
+/**
+ * コーパスをシャッフルするための、シードつきの疑似乱数(SplitMix64)。
+ *
+ * 同じシードからは、依存ライブラリのバージョンによらず同じ順番が得られるように、自前で実装している。
+ */
+struct SeededShuffler {
+    state: u64,
+}
+
+impl SeededShuffler {
+    fn new(seed: u64) -> Self {
+        SeededShuffler { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Fisher-Yates でシャッフルする。
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// dev コーパスで最も精度の良かったエポックの、学習の状態。
+struct LearningSnapshot {
+    unigram_map: HashMap<String, (i32, f32)>,
+    bigram_map: HashMap<(i32, i32), f32>,
+    features: HashMap<String, f32>,
+    unigram_weights: AveragedWeights<String>,
+    bigram_weights: AveragedWeights<(i32, i32)>,
+    feature_averaged_weights: AveragedWeights<String>,
+    steps: u32,
+}
+
 struct LearningService {
-    graph_builder: GraphBuilder<OnMemorySystemUnigramLM, OnMemorySystemBigramLM>,
+    graph_builder: GraphBuilder<OnMemorySystemUnigramLM>,
     segmenter: Segmenter,
-    system_unigram_lm: Rc<OnMemorySystemUnigramLM>,
-    system_bigram_lm: Rc<OnMemorySystemBigramLM>,
+    system_unigram_lm: Arc<OnMemorySystemUnigramLM>,
+    system_bigram_lm: Arc<OnMemorySystemBigramLM>,
+    // 重みを平均化したものと差し替えるために、言語モデルの中身への参照を持っておく。
+    unigram_map: Arc<RwLock<HashMap<String, (i32, f32)>>>,
+    bigram_map: Arc<RwLock<HashMap<(i32, i32), f32>>>,
+    feature_weights: Arc<FeatureWeights>,
+    // 正解のノードが single term 辞書にあるかを調べるために持っておく。
+    single_term_dict: HashMap<String, Vec<String>>,
+    unigram_weights: AveragedWeights<String>,
+    bigram_weights: AveragedWeights<(i32, i32)>,
+    feature_averaged_weights: AveragedWeights<String>,
+    // これまでに見た事例数
+    steps: u32,
 }
 
 impl LearningService {
-    pub fn new(src_unigram: &str, src_bigram: &str, corpuses: &[&str]) -> anyhow::Result<Self> {
+    pub fn new(
+        src_unigram: &str,
+        src_bigram: &str,
+        corpuses: &[&str],
+        single_term_dicts: &[String],
+    ) -> anyhow::Result<Self> {
         let system_kana_kanji_dict = read_skkdict(Path::new("data/SKK-JISYO.akaza"), UTF_8)?;
-        let all_yomis = system_kana_kanji_dict.keys().cloned().collect::<Vec<_>>();
+        let single_term_dict = merge_dict(
+            single_term_dicts
+                .iter()
+                .map(|path| read_skkdict(Path::new(path), UTF_8))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        );
+        let all_yomis = system_kana_kanji_dict
+            .keys()
+            .chain(single_term_dict.keys())
+            .cloned()
+            .collect::<Vec<_>>();
         let system_kana_trie = CedarwoodKanaTrie::build(all_yomis);
         let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(system_kana_trie))]);
 
@@ -64,25 +205,37 @@ impl LearningService {
                 }
             }
         }
-        let system_unigram_lm = Rc::new(OnMemorySystemUnigramLM::new(
-            Rc::new(RefCell::new(unigram_map)),
+        let unigram_map = Arc::new(RwLock::new(unigram_map));
+        let system_unigram_lm = Arc::new(OnMemorySystemUnigramLM::new(
+            unigram_map.clone(),
+            src_system_unigram_lm.backoff_hash_map(),
             src_system_unigram_lm.get_default_cost(),
             src_system_unigram_lm.get_default_cost_for_short(),
         ));
 
         info!("bigram source file: {}", src_bigram);
         let src_system_bigram_lm = MarisaSystemBigramLM::load(src_bigram)?;
-        let system_bigram_lm = Rc::new(OnMemorySystemBigramLM::new(
-            Rc::new(RefCell::new(src_system_bigram_lm.as_hash_map())),
+        let bigram_map = Arc::new(RwLock::new(src_system_bigram_lm.as_hash_map()));
+        let system_bigram_lm = Arc::new(OnMemorySystemBigramLM::new(
+            bigram_map.clone(),
             src_system_bigram_lm.get_default_edge_cost(),
         ));
 
-        let graph_builder = GraphBuilder::new(
+        // 素性の重みは空の状態から学習する。学習しない場合はコストに影響しない。
+        let feature_weights = Arc::new(FeatureWeights::default());
+        let cost_model = FeatureCostModel::new(
+            Arc::new(DefaultCostModel::new(
+                Arc::new(Mutex::new(UserData::default())),
+                system_unigram_lm.clone(),
+                system_bigram_lm.clone(),
+            )),
+            feature_weights.clone(),
+        );
+        let graph_builder = GraphBuilder::new_with_cost_model(
             system_kana_kanji_dict,
-            HashMap::default(),
-            Arc::new(Mutex::new(UserData::default())),
+            single_term_dict.clone(),
             system_unigram_lm.clone(),
-            system_bigram_lm.clone(),
+            Arc::new(cost_model),
         );
 
         Ok(LearningService {
@@ -90,21 +243,94 @@ impl LearningService {
             segmenter,
             system_unigram_lm,
             system_bigram_lm,
+            unigram_map,
+            bigram_map,
+            feature_weights,
+            single_term_dict,
+            unigram_weights: AveragedWeights::new(),
+            bigram_weights: AveragedWeights::new(),
+            feature_averaged_weights: AveragedWeights::new(),
+            steps: 0,
         })
     }
 
-    pub fn try_learn(&self, epochs: i32, delta: f32, corpus: &str) -> anyhow::Result<()> {
+    pub fn try_learn(
+        &mut self,
+        label: &str,
+        epochs: i32,
+        corpus: &str,
+        options: &LearningOptions,
+        shuffler: &mut SeededShuffler,
+    ) -> anyhow::Result<()> {
         let corpuses = read_corpus_file(Path::new(corpus))?;
-        for _ in 1..epochs {
+        let dev_corpuses = match &options.dev_corpus {
+            Some(dev_corpus) => Some(read_corpus_file(Path::new(dev_corpus))?),
+            None => None,
+        };
+        let mut order = (0..corpuses.len()).collect::<Vec<_>>();
+        let mut best_accuracy = -1_f32;
+        let mut best_snapshot = None;
+        let mut epochs_without_improvement = 0;
+        for epoch in 0..epochs {
+            // 事例の順番に引きずられないように、エポックごとに並べかえる。
+            shuffler.shuffle(&mut order);
             let mut ok_cnt = 0;
-            for teacher in corpuses.iter() {
-                let succeeded = self.learn(delta, teacher)?;
+            for i in order.iter() {
+                let succeeded = self.learn(&corpuses[*i], options)?;
 
                 if succeeded {
                     ok_cnt += 1;
                 }
             }
-            info!("ok_cnt={} corpuses.len()={}", ok_cnt, corpuses.len());
+            info!(
+                "{} epoch={} ok_cnt={} corpuses.len()={}",
+                label,
+                epoch,
+                ok_cnt,
+                corpuses.len()
+            );
+
+            if let Some(dir) = &options.checkpoint_dir {
+                let dst_unigram = format!("{}/unigram-{}-{}.model", dir, label, epoch);
+                let dst_bigram = format!("{}/bigram-{}-{}.model", dir, label, epoch);
+                let dst_feature = options
+                    .feature_model
+                    .as_ref()
+                    .map(|_| format!("{}/feature-{}-{}.model", dir, label, epoch));
+                self.with_averaged_weights(|service| {
+                    service.save_unigram(&dst_unigram)?;
+                    service.save_bigram(&dst_unigram, &dst_bigram)?;
+                    service.save_feature(&dst_feature)
+                })?;
+            }
+
+            if let Some(dev_corpuses) = &dev_corpuses {
+                let accuracy =
+                    self.with_averaged_weights(|service| service.evaluate(dev_corpuses))?;
+                info!("{} epoch={} dev accuracy={}", label, epoch, accuracy);
+                if accuracy > best_accuracy {
+                    best_accuracy = accuracy;
+                    best_snapshot = Some(self.snapshot());
+                    epochs_without_improvement = 0;
+                } else {
+                    epochs_without_improvement += 1;
+                }
+                if let Some(patience) = options.patience {
+                    if epochs_without_improvement >= patience {
+                        info!("Dev accuracy has not improved for {} epochs.", patience);
+                        // 最後のエポックではなく、精度が最も良かったエポックの状態に戻す。
+                        if let Some(snapshot) = best_snapshot.take() {
+                            info!(
+                                "{} restore the weights of best dev accuracy={}",
+                                label, best_accuracy
+                            );
+                            self.restore(snapshot);
+                        }
+                        break;
+                    }
+                }
+            }
+
             if ok_cnt == corpuses.len() {
                 info!("Learning process finished.");
                 break;
@@ -114,74 +340,240 @@ impl LearningService {
         Ok(())
     }
 
-    pub fn learn(&self, delta: f32, teacher: &FullAnnotationCorpus) -> anyhow::Result<bool> {
-        let yomi = teacher.yomi();
-        let surface = teacher.surface();
-        let segmentation_result = self.segmenter.build(&yomi, None);
+    fn convert(&self, yomi: &str) -> anyhow::Result<Vec<Candidate>> {
+        let segmentation_result = self.segmenter.build(yomi, None);
         let graph_resolver = GraphResolver::default();
 
-        let lattice = self
-            .graph_builder
-            .construct(yomi.as_str(), segmentation_result);
+        let lattice = self.graph_builder.construct(yomi, segmentation_result);
         let got = graph_resolver.resolve(&lattice)?;
-        let terms: Vec<String> = got.iter().map(|f| f[0].surface.clone()).collect();
-        let result = terms.join("");
+        Ok(got.iter().map(|f| f[0].clone()).collect())
+    }
+
+    /// 正解した文の割合を返す。
+    fn evaluate(&self, corpuses: &[FullAnnotationCorpus]) -> anyhow::Result<f32> {
+        if corpuses.is_empty() {
+            return Ok(0_f32);
+        }
+        let mut ok_cnt = 0;
+        for corpus in corpuses {
+            let got = self.convert(corpus.yomi().as_str())?;
+            if surface_of(&got) == corpus.surface() {
+                ok_cnt += 1;
+            }
+        }
+        Ok(ok_cnt as f32 / corpuses.len() as f32)
+    }
+
+    /**
+     * 構造化パーセプトロンの1ステップ。
+     *
+     * 変換結果が正解と異なる場合は、正解の経路にある素性のコストを下げて、
+     * 誤った経路にある素性のコストを上げる。
+     */
+    pub fn learn(
+        &mut self,
+        teacher: &FullAnnotationCorpus,
+        options: &LearningOptions,
+    ) -> anyhow::Result<bool> {
+        let candidates = self.convert(teacher.yomi().as_str())?;
+        let result = surface_of(&candidates);
+        let got = candidates.iter().map(|f| f.key()).collect::<Vec<_>>();
+        let surface = teacher.surface();
 
         println!("{}", result);
 
         // 正解じゃないときには出現頻度の確率が正しくないということだと思いますんで
-        // 頻度を増やす。
-        if result != surface {
-            // learn unigram
-            if !teacher.nodes.is_empty() {
-                for i in 0..teacher.nodes.len() {
-                    let key = teacher.nodes[i].key();
-                    let (_, cost) = self
-                        .system_unigram_lm
-                        .find(&key.to_string())
-                        .unwrap_or((-1, 0_f32));
-                    self.system_unigram_lm.update(key.as_str(), cost - delta);
-                }
-            }
+        // 正解の経路を通りやすくする。
+        let succeeded = if result != surface {
+            let gold = teacher.nodes.iter().map(|f| f.key()).collect::<Vec<_>>();
 
-            // learn bigram
-            if teacher.nodes.len() > 1 {
-                for i in 1..teacher.nodes.len() {
-                    let key1 = teacher.nodes[i - 1].key();
-                    let key2 = teacher.nodes[i].key();
-                    let Some((word_id1, _)) = self.system_unigram_lm.find(key1.as_str()) else {
+            // 正解の経路にあれば -1、誤った経路にあれば +1。両方にあれば打ち消しあう。
+            let mut unigram_gradients: HashMap<String, f32> = HashMap::new();
+            // (word_id1, word_id2) -> (勾配, 採録されていないときのバックオフしたコスト)
+            let mut bigram_gradients: HashMap<(i32, i32), (f32, Option<f32>)> = HashMap::new();
+            for (keys, sign) in [(&gold, -1_f32), (&got, 1_f32)] {
+                for key in keys {
+                    *unigram_gradients.entry(key.clone()).or_insert(0_f32) += sign;
+                }
+                for i in 1..keys.len() {
+                    let Some((word_id1, _)) = self.system_unigram_lm.find(keys[i - 1].as_str())
+                    else {
                         // info!("{} is not registered in the real system unigram LM.",word1);
                         continue;
                     };
-                    let Some((word_id2, _)) = self.system_unigram_lm.find(key2.as_str()) else {
+                    let Some((word_id2, cost2)) = self.system_unigram_lm.find(keys[i].as_str())
+                    else {
                         // info!("{} is not registered in the real system unigram LM.",word1);
                         continue;
                     };
-                    let v = self
-                        .system_bigram_lm
-                        .get_edge_cost(word_id1, word_id2)
-                        .unwrap_or(0_f32);
-                    info!(
-                        "Update bigram cost: {}={},{}={}, v={}",
-                        key1, word_id1, key2, word_id2, v
+                    let backoff_cost = self
+                        .system_unigram_lm
+                        .get_backoff_cost(keys[i - 1].as_str())
+                        .map(|backoff| backoff + cost2);
+                    bigram_gradients
+                        .entry((word_id1, word_id2))
+                        .or_insert((0_f32, backoff_cost))
+                        .0 += sign;
+                }
+            }
+
+            // learn unigram
+            for (key, gradient) in unigram_gradients {
+                if gradient == 0_f32 {
+                    continue;
+                }
+                let Some((_, cost)) = self.system_unigram_lm.find(key.as_str()) else {
+                    // 正解の単語は LearningService::new で言語モデルに登録しているので、ここに来るのは
+                    // 誤った経路にだけある未知語。以前と同じく、言語モデルにない単語のコストは動かさない。
+                    if gradient < 0_f32 {
+                        warn!("{} is not registered in the system unigram LM.", key);
+                    }
+                    continue;
+                };
+                let amount = self.unigram_weights.update(
+                    &key,
+                    gradient,
+                    options.delta,
+                    options.adagrad,
+                    self.steps,
+                );
+                self.system_unigram_lm.update(key.as_str(), cost + amount);
+            }
+
+            // learn bigram
+            for ((word_id1, word_id2), (gradient, backoff_cost)) in bigram_gradients {
+                if gradient == 0_f32 {
+                    continue;
+                }
+                // 採録されていないバイグラムは、変換時と同じくバックオフしたコストから調整する。
+                let v = self
+                    .system_bigram_lm
+                    .get_edge_cost(word_id1, word_id2)
+                    .or(backoff_cost)
+                    .unwrap_or_else(|| self.system_bigram_lm.get_default_edge_cost());
+                let amount = self.bigram_weights.update(
+                    &(word_id1, word_id2),
+                    gradient,
+                    options.delta,
+                    options.adagrad,
+                    self.steps,
+                );
+                debug!(
+                    "Update bigram cost: {},{} v={} amount={}",
+                    word_id1, word_id2, v, amount
+                );
+                self.system_bigram_lm.update(word_id1, word_id2, v + amount);
+            }
+
+            // learn features
+            if options.feature_model.is_some() {
+                let gold_nodes = mark_single_term(&teacher.nodes, &self.single_term_dict);
+                let got_nodes = candidate_nodes(&candidates);
+                for (feature, gradient) in feature_gradients(&gold_nodes, &got_nodes) {
+                    if gradient == 0_f32 {
+                        continue;
+                    }
+                    let amount = self.feature_averaged_weights.update(
+                        &feature,
+                        gradient,
+                        options.delta,
+                        options.adagrad,
+                        self.steps,
                     );
-                    self.system_bigram_lm.update(word_id1, word_id2, v - delta);
+                    self.feature_weights
+                        .update(&feature, self.feature_weights.get(&feature) + amount);
                 }
             }
 
             debug!("BAD! result={}, surface={}", result, surface);
-            Ok(false)
+            false
         } else {
             debug!("学習完了! result={}", result);
-            Ok(true)
+            true
+        };
+        self.steps += 1;
+        Ok(succeeded)
+    }
+
+    fn snapshot(&self) -> LearningSnapshot {
+        LearningSnapshot {
+            unigram_map: self.unigram_map.read().unwrap().clone(),
+            bigram_map: self.bigram_map.read().unwrap().clone(),
+            features: self.feature_weights.as_hash_map(),
+            unigram_weights: self.unigram_weights.clone(),
+            bigram_weights: self.bigram_weights.clone(),
+            feature_averaged_weights: self.feature_averaged_weights.clone(),
+            steps: self.steps,
         }
     }
 
+    fn restore(&mut self, snapshot: LearningSnapshot) {
+        *self.unigram_map.write().unwrap() = snapshot.unigram_map;
+        *self.bigram_map.write().unwrap() = snapshot.bigram_map;
+        self.feature_weights.set_weights(snapshot.features);
+        self.unigram_weights = snapshot.unigram_weights;
+        self.bigram_weights = snapshot.bigram_weights;
+        self.feature_averaged_weights = snapshot.feature_averaged_weights;
+        self.steps = snapshot.steps;
+    }
+
+    /// 重みを平均化したものに一時的に差し替えて f を実行する。
+    fn with_averaged_weights<T>(
+        &self,
+        f: impl FnOnce(&Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let current_unigram = self.unigram_map.read().unwrap().clone();
+        let current_bigram = self.bigram_map.read().unwrap().clone();
+        let current_features = self.feature_weights.as_hash_map();
+        {
+            let mut unigram_map = self.unigram_map.write().unwrap();
+            for key in self.unigram_weights.keys() {
+                if let Some((_, cost)) = unigram_map.get_mut(key) {
+                    *cost = self.unigram_weights.average(key, *cost, self.steps);
+                }
+            }
+            let mut bigram_map = self.bigram_map.write().unwrap();
+            for key in self.bigram_weights.keys() {
+                if let Some(cost) = bigram_map.get_mut(key) {
+                    *cost = self.bigram_weights.average(key, *cost, self.steps);
+                }
+            }
+            let mut features = current_features.clone();
+            for key in self.feature_averaged_weights.keys() {
+                if let Some(weight) = features.get_mut(key) {
+                    *weight = self
+                        .feature_averaged_weights
+                        .average(key, *weight, self.steps);
+                }
+            }
+            self.feature_weights.set_weights(features);
+        }
+
+        let result = f(self);
+
+        *self.unigram_map.write().unwrap() = current_unigram;
+        *self.bigram_map.write().unwrap() = current_bigram;
+        self.feature_weights.set_weights(current_features);
+        result
+    }
+
+    pub fn save_feature(&self, dst_feature: &Option<String>) -> anyhow::Result<()> {
+        if let Some(dst_feature) = dst_feature {
+            info!("Save feature weights to {}", dst_feature);
+            self.feature_weights.save(dst_feature)?;
+        }
+        Ok(())
+    }
+
     pub fn save_unigram(&self, dst_unigram: &str) -> anyhow::Result<()> {
         // unigram
         let mut unigram_builder = MarisaSystemUnigramLMBuilder::default();
+        let backoff = self.system_unigram_lm.backoff_hash_map();
         for (key, (_, cost)) in self.system_unigram_lm.as_hash_map() {
-            unigram_builder.add(key.as_str(), cost);
+            match backoff.get(&key) {
+                Some(backoff) => unigram_builder.add_with_backoff(key.as_str(), cost, *backoff),
+                None => unigram_builder.add(key.as_str(), cost),
+            }
         }
         // ↓本来なら現在のデータで再調整すべきだが、一旦元のものを使う。
         // TODO あとで整理する
@@ -244,10 +636,61 @@ impl LearningService {
     }
 }
 
+/// 変換結果の候補を、素性を求めるためのノードに戻す。
+fn candidate_nodes(candidates: &[Candidate]) -> Vec<WordNode> {
+    candidates
+        .iter()
+        .map(|f| {
+            let mut node = WordNode::new(0, &f.surface, &f.yomi, None);
+            node.single_term = f.single_term;
+            node
+        })
+        .collect()
+}
+
+/**
+ * 正解のノードのうち、変換時に single term 辞書から引かれるものに印をつける。
+ * single term 辞書は読み全体を変換するときにしか使われないので、1単語だけの文のみが対象になる。
+ */
+fn mark_single_term(
+    nodes: &[WordNode],
+    single_term_dict: &HashMap<String, Vec<String>>,
+) -> Vec<WordNode> {
+    let mut nodes = nodes.to_vec();
+    if let [node] = nodes.as_mut_slice() {
+        node.single_term = single_term_dict.get(&node.yomi).is_some_and(|surfaces| {
+            surfaces
+                .iter()
+                .any(|surface| split_annotation(surface).0 == node.surface)
+        });
+    }
+    nodes
+}
+
+/// 正解の経路にある素性は -1、誤った経路にある素性は +1。
+fn feature_gradients(gold: &[WordNode], got: &[WordNode]) -> HashMap<String, f32> {
+    let mut gradients: HashMap<String, f32> = HashMap::new();
+    for (nodes, sign) in [(gold, -1_f32), (got, 1_f32)] {
+        for feature in path_features(nodes) {
+            *gradients.entry(feature).or_insert(0_f32) += sign;
+        }
+    }
+    gradients
+}
+
+fn surface_of(candidates: &[Candidate]) -> String {
+    candidates
+        .iter()
+        .map(|f| f.surface.as_str())
+        .collect::<Vec<_>>()
+        .join("")
+}
+
 /// コーパスを元にした学習を行います。
+/// 平均化した構造化パーセプトロンで、システム言語モデルのコストを調整します。
 #[allow(clippy::too_many_arguments)]
 pub fn learn_corpus(
-    delta: f32,
+    options: &LearningOptions,
     may_epochs: i32,
     should_epochs: i32,
     must_epochs: i32,
@@ -259,24 +702,109 @@ pub fn learn_corpus(
     dst_unigram: &str,
     dst_bigram: &str,
 ) -> anyhow::Result<()> {
-    let service = LearningService::new(
+    let mut service = LearningService::new(
         src_unigram,
         src_bigram,
         &[may_corpus, should_corpus, must_corpus],
+        &options.single_term_dicts,
     )?;
+    let mut shuffler = SeededShuffler::new(options.seed);
 
     // 実際の学習をさせる
-    for (epoch, corpus) in [
-        (may_epochs, may_corpus),
-        (should_epochs, should_corpus),
-        (must_epochs, must_corpus),
+    for (label, epoch, corpus) in [
+        ("may", may_epochs, may_corpus),
+        ("should", should_epochs, should_corpus),
+        ("must", must_epochs, must_corpus),
     ] {
-        service.try_learn(epoch, delta, corpus)?;
+        service.try_learn(label, epoch, corpus, options, &mut shuffler)?;
     }
 
     // 保存していく
-    service.save_unigram(dst_unigram)?;
-    service.save_bigram(dst_unigram, dst_bigram)?;
+    service.with_averaged_weights(|service| {
+        service.save_unigram(dst_unigram)?;
+        service.save_bigram(dst_unigram, dst_bigram)?;
+        service.save_feature(&options.feature_model)
+    })?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_averaged_weights() {
+        let mut weights: AveragedWeights<&str> = AveragedWeights::new();
+        // 3事例のうち、2事例目のあとで 1 だけ更新された場合。
+        // 各事例のあとの重みは 10, 11, 11 なので平均は 32/3。
+        let mut w = 10_f32;
+        w += weights.update(&"a", 1_f32, 1_f32, false, 1);
+        let got = weights.average(&"a", w, 3);
+        assert!((got - 32_f32 / 3_f32).abs() < 0.0001);
+
+        // 更新されていないものはそのまま
+        assert_eq!(weights.average(&"b", 5_f32, 3), 5_f32);
+    }
+
+    #[test]
+    fn test_adagrad() {
+        let mut weights: AveragedWeights<&str> = AveragedWeights::new();
+        assert_eq!(weights.update(&"a", 2_f32, 0.5, true, 0), 0.5);
+        // 勾配の二乗和が大きくなるほど、更新量は小さくなる。
+        let second = weights.update(&"a", 2_f32, 0.5, true, 1);
+        assert!(0_f32 < second && second < 0.5);
+    }
+
+    #[test]
+    fn test_seeded_shuffler() {
+        let shuffled = |seed| {
+            let mut items = (0..10).collect::<Vec<_>>();
+            SeededShuffler::new(seed).shuffle(&mut items);
+            items
+        };
+        // 同じシードなら同じ順番になる。
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), shuffled(43));
+        // 要素は失われない。
+        let mut sorted = shuffled(42);
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_single_term_feature() {
+        let single_term_dict = HashMap::from([("すし".to_string(), vec!["🍣;emoji".to_string()])]);
+        let teacher = [WordNode::new(0, "🍣", "すし", None)];
+        let gold = mark_single_term(&teacher, &single_term_dict);
+        assert!(gold[0].single_term);
+        // 1単語だけの文でなければ、single term 辞書からは引かれない。
+        let sentence = [
+            WordNode::new(0, "🍣", "すし", None),
+            WordNode::new(6, "だ", "だ", None),
+        ];
+        assert!(!mark_single_term(&sentence, &single_term_dict)[0].single_term);
+
+        // single term 辞書の候補を誤って選んだ場合は、single_term 素性のコストが上がる。
+        let mut got = Candidate::new("すし", "寿司", 0_f32);
+        got.single_term = true;
+        let gradients = feature_gradients(
+            &[WordNode::new(0, "寿司", "すし", None)],
+            &candidate_nodes(&[got]),
+        );
+        assert_eq!(gradients.get("single_term"), Some(&1_f32));
+
+        let feature_weights = FeatureWeights::default();
+        let mut averaged: AveragedWeights<String> = AveragedWeights::new();
+        let amount = averaged.update(&"single_term".to_string(), 1_f32, 0.5, false, 0);
+        feature_weights.update("single_term", feature_weights.get("single_term") + amount);
+        assert!(feature_weights.get("single_term") > 0_f32);
+
+        // 正解が single term 辞書の候補なら、コストは下がる。
+        let gradients = feature_gradients(
+            &gold,
+            &candidate_nodes(&[Candidate::new("すし", "寿司", 0_f32)]),
+        );
+        assert_eq!(gradients.get("single_term"), Some(&-1_f32));
+    }
+}
@@ -7,6 +7,8 @@ use anyhow::{bail, Result};
 use encoding_rs::UTF_8;
 use log::info;
 
+use libakaza::dict::skk::read::split_annotation;
+
 use crate::utils::copy_snapshot;
 
 /// テキスト形式での辞書を作成する。
@@ -142,6 +144,8 @@ fn validate_dict(dict: HashMap<String, Vec<String>>) -> Result<HashMap<String, V
         }
         let kana_cnt = kana.chars().count();
         for surface in surfaces {
+            // アノテーションは長さの確認に含めない。
+            let (surface, _) = split_annotation(surface);
             if surface.is_empty() {
                 bail!("Empty surface: {:?}", kana);
             }
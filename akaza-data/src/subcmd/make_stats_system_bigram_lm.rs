@@ -12,13 +12,17 @@ use rayon::prelude::*;
 
 use crate::utils::get_file_list;
 use libakaza::lm::base::{SystemBigramLM, SystemUnigramLM};
+use libakaza::lm::smoothing::estimate_kneser_ney;
 use libakaza::lm::system_bigram::{MarisaSystemBigramLM, MarisaSystemBigramLMBuilder};
-use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
+use libakaza::lm::system_unigram_lm::{MarisaSystemUnigramLM, MarisaSystemUnigramLMBuilder};
 
+/// バイグラム言語モデルを作る。
+/// バックオフ重みはユニグラム言語モデルに格納するので、dst_unigram_trie_file にユニグラム言語モデルも書き出す。
 pub fn make_stats_system_bigram_lm(
     threshold: u32,
     corpus_dirs: &Vec<String>,
     unigram_trie_file: &str,
+    dst_unigram_trie_file: &str,
     bigram_trie_file: &str,
 ) -> Result<()> {
     // まずは unigram の language model を読み込む
@@ -29,11 +33,15 @@ pub fn make_stats_system_bigram_lm(
         threshold
     );
 
-    let unigram_map = unigram_lm
-        .as_hash_map()
+    let unigram_hash_map = unigram_lm.as_hash_map();
+    let unigram_map = unigram_hash_map
         .iter()
         .map(|(key, (word_id, _cost))| (key.clone(), *word_id))
         .collect::<HashMap<_, _>>();
+    let unigram_costs = unigram_hash_map
+        .values()
+        .cloned()
+        .collect::<HashMap<_, _>>();
     let reverse_unigram_map = unigram_map
         .iter()
         .map(|(key, word_id)| (*word_id, key.to_string()))
@@ -63,7 +71,12 @@ pub fn make_stats_system_bigram_lm(
     }
 
     // スコアを計算する
-    let scoremap = make_score_map(threshold, &merged);
+    let estimated = estimate_kneser_ney(threshold, &merged, &unigram_costs);
+    info!(
+        "Estimated bigram={} backoff={}",
+        estimated.bigram_costs.len(),
+        estimated.backoff_costs.len()
+    );
 
     // dump bigram text file.
     let dumpfname = format!(
@@ -84,15 +97,42 @@ pub fn make_stats_system_bigram_lm(
         }
     }
 
+    // バックオフ重みをつけたユニグラム言語モデルを書き込む。
+    // キーが変わると単語 ID がふりなおされるので、バイグラムの単語 ID は新しいものに引き直す。
+    info!("Generating unigram trie file");
+    let mut unigram_builder = MarisaSystemUnigramLMBuilder::default();
+    for (key, (word_id, cost)) in &unigram_hash_map {
+        match estimated.backoff_costs.get(word_id) {
+            Some(backoff) => unigram_builder.add_with_backoff(key, *cost, *backoff),
+            None => unigram_builder.add(key, *cost),
+        }
+    }
+    info!("Writing {}", dst_unigram_trie_file);
+    unigram_builder.save(dst_unigram_trie_file)?;
+    let dst_unigram_lm = MarisaSystemUnigramLM::load(dst_unigram_trie_file)?;
+    let new_word_ids = reverse_unigram_map
+        .iter()
+        .filter_map(|(word_id, key)| {
+            dst_unigram_lm
+                .find(key)
+                .map(|(new_word_id, _)| (*word_id, new_word_id))
+        })
+        .collect::<HashMap<_, _>>();
+
     // 結果を書き込む
-    info!("Generating trie file");
+    info!("Generating bigram trie file");
     let mut builder = MarisaSystemBigramLMBuilder::default();
-    for ((word_id1, word_id2), score) in scoremap {
-        builder.add(word_id1, word_id2, score);
+    for ((word_id1, word_id2), score) in estimated.bigram_costs {
+        let (Some(new_word_id1), Some(new_word_id2)) =
+            (new_word_ids.get(&word_id1), new_word_ids.get(&word_id2))
+        else {
+            continue;
+        };
+        builder.add(*new_word_id1, *new_word_id2, score);
     }
     {
         // default edge cost の計算。
-        // 頻度0の単語として計算する
+        // バックオフ重みがない単語(コーパスに出てこなかった単語)のために、頻度0の単語として計算する
 
         // 総出現単語数
         let c = merged.values().sum();
@@ -105,28 +145,12 @@ pub fn make_stats_system_bigram_lm(
     info!("Writing {}", bigram_trie_file);
     builder.save(bigram_trie_file)?;
 
-    validation(unigram_trie_file, bigram_trie_file)?;
+    validation(dst_unigram_trie_file, bigram_trie_file)?;
 
     println!("DONE");
     Ok(())
 }
 
-// unigram のロジックと一緒なのでまとめたい。
-fn make_score_map(threshold: u32, wordcnt: &HashMap<(i32, i32), u32>) -> HashMap<(i32, i32), f32> {
-    // 総出現単語数
-    let c = wordcnt.values().sum();
-    // 単語の種類数
-    let v = wordcnt.keys().count();
-    wordcnt
-        .iter()
-        .filter(|(_word, cnt)| *cnt > &threshold)
-        .map(|(word, cnt)| {
-            let n_words = *cnt;
-            (*word, calc_cost(n_words, c, v as u32))
-        })
-        .collect::<HashMap<_, _>>()
-}
-
 fn count_bigram(
     src: &PathBuf,
     unigram_lm: &HashMap<String, i32>,
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{prelude::*, BufReader};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::info;
+use rayon::prelude::*;
+
+use crate::utils::get_file_list;
+use libakaza::lm::base::SystemUnigramLM;
+use libakaza::lm::system_trigram::{MarisaSystemTrigramLM, MarisaSystemTrigramLMBuilder};
+use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
+
+#[derive(Default)]
+struct NGramCount {
+    trigram: HashMap<(i32, i32, i32), u32>,
+    bigram: HashMap<(i32, i32), u32>,
+}
+
+/**
+ * trigram 言語モデルを生成する。
+ *
+ * 単語IDは unigram_trie_file のものを使うので、実行時に読み込む unigram.model を指定すること。
+ */
+pub fn make_stats_system_trigram_lm(
+    threshold: u32,
+    corpus_dirs: &Vec<String>,
+    unigram_trie_file: &str,
+    trigram_trie_file: &str,
+) -> Result<()> {
+    // まずは unigram の language model を読み込む
+    let unigram_lm = MarisaSystemUnigramLM::load(unigram_trie_file)?;
+    info!(
+        "Unigram system lm: {} threshold={}",
+        unigram_lm.num_keys(),
+        threshold
+    );
+
+    let unigram_map = unigram_lm
+        .as_hash_map()
+        .iter()
+        .map(|(key, (word_id, _cost))| (key.clone(), *word_id))
+        .collect::<HashMap<_, _>>();
+
+    // 次に、コーパスをスキャンして trigram と bigram を数える。
+    let mut file_list: Vec<PathBuf> = Vec::new();
+    for corpus_dir in corpus_dirs {
+        let list = get_file_list(Path::new(corpus_dir))?;
+        for x in list {
+            file_list.push(x)
+        }
+    }
+    let results = file_list
+        .par_iter()
+        .map(|src| count_trigram(src, &unigram_map))
+        .collect::<Vec<_>>();
+
+    // 集計した結果をマージする
+    info!("Merging");
+    let mut merged = NGramCount::default();
+    for result in results {
+        let result = result?;
+        for (word_ids, cnt) in result.trigram {
+            *merged.trigram.entry(word_ids).or_insert(0) += cnt;
+        }
+        for (word_ids, cnt) in result.bigram {
+            *merged.bigram.entry(word_ids).or_insert(0) += cnt;
+        }
+    }
+
+    // スコアを計算する
+    let scoremap = make_score_map(threshold, &merged);
+
+    // 結果を書き込む
+    info!("Generating trie file");
+    let mut builder = MarisaSystemTrigramLMBuilder::default();
+    for ((word_id1, word_id2, word_id3), score) in scoremap {
+        builder.add(word_id1, word_id2, word_id3, score);
+    }
+    info!("Writing {}", trigram_trie_file);
+    builder.save(trigram_trie_file)?;
+
+    let trigram = MarisaSystemTrigramLM::load(trigram_trie_file)?;
+    println!("trigram entries: {}", trigram.num_keys());
+
+    println!("DONE");
+    Ok(())
+}
+
+/**
+ * bigram のエッジコストに足し込む補正値を計算する。
+ *
+ *   -log10(P(w3|w1,w2) / P(w3|w2))
+ *
+ * 文脈を見ることで w3 が出やすくなるなら負の値になる。
+ */
+fn make_score_map(threshold: u32, count: &NGramCount) -> HashMap<(i32, i32, i32), f32> {
+    // (w1, w2) で始まる trigram の数
+    let mut trigram_context: HashMap<(i32, i32), u32> = HashMap::new();
+    for ((word_id1, word_id2, _), cnt) in &count.trigram {
+        *trigram_context.entry((*word_id1, *word_id2)).or_insert(0) += cnt;
+    }
+    // w2 で始まる bigram の数
+    let mut bigram_context: HashMap<i32, u32> = HashMap::new();
+    for ((word_id1, _), cnt) in &count.bigram {
+        *bigram_context.entry(*word_id1).or_insert(0) += cnt;
+    }
+
+    count
+        .trigram
+        .iter()
+        .filter(|(_word_ids, cnt)| *cnt > &threshold)
+        .filter_map(|((word_id1, word_id2, word_id3), cnt)| {
+            let trigram_prob = *cnt as f32 / *trigram_context.get(&(*word_id1, *word_id2))? as f32;
+            let bigram_prob = *count.bigram.get(&(*word_id2, *word_id3))? as f32
+                / *bigram_context.get(word_id2)? as f32;
+            Some((
+                (*word_id1, *word_id2, *word_id3),
+                -f32::log10(trigram_prob / bigram_prob),
+            ))
+        })
+        .collect::<HashMap<_, _>>()
+}
+
+fn count_trigram(src: &PathBuf, unigram_lm: &HashMap<String, i32>) -> Result<NGramCount> {
+    info!("Counting {}", src.to_string_lossy());
+    let file = File::open(src)?;
+    let mut count = NGramCount::default();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.trim();
+        let words = line.split(' ').collect::<Vec<_>>();
+        if words.len() < 2 {
+            continue;
+        }
+        let word_ids = words
+            .iter()
+            .map(|word| unigram_lm.get(&word.to_string()))
+            .collect::<Vec<_>>();
+
+        for window in word_ids.windows(2) {
+            let [Some(word_id1), Some(word_id2)] = window else {
+                continue;
+            };
+            *count.bigram.entry((**word_id1, **word_id2)).or_insert(0) += 1;
+        }
+        for window in word_ids.windows(3) {
+            let [Some(word_id1), Some(word_id2), Some(word_id3)] = window else {
+                continue;
+            };
+            *count
+                .trigram
+                .entry((**word_id1, **word_id2, **word_id3))
+                .or_insert(0) += 1;
+        }
+    }
+    Ok(count)
+}
@@ -5,6 +5,7 @@ use std::io::{prelude::*, BufReader};
 
 use chrono::Local;
 use libakaza::cost::calc_cost;
+use libakaza::lm::char_bigram::make_char_bigram_costs;
 use log::info;
 
 use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
@@ -34,6 +35,10 @@ pub fn make_stats_system_unigram_lm(srcpath: &str, dstpath: &str) -> anyhow::Res
     for (word, score) in &scoremap {
         builder.add(word.as_str(), *score);
     }
+    // 未知語のコストを見積もるための、読みの文字バイグラム。
+    for (key, score) in make_char_bigram_costs(&wordcnt) {
+        builder.add(key.as_str(), score);
+    }
 
     // 総出現単語数
     let c = wordcnt.values().sum();
@@ -1,10 +1,13 @@
 pub mod check;
+pub mod compile_dict;
+pub mod convert_dict;
 pub mod dump_bigram_dict;
 pub mod dump_unigram_dict;
 pub mod evaluate;
 pub mod learn_corpus;
 pub mod make_dict;
 pub mod make_stats_system_bigram_lm;
+pub mod make_stats_system_trigram_lm;
 pub mod make_stats_system_unigram_lm;
 pub mod tokenize;
 pub mod vocab;
@@ -54,6 +54,9 @@ pub(crate) fn ibus_akaza_commands_map() -> HashMap<&'static str, IbusAkazaComman
     register("update_candidates", |context, engine| {
         context.update_candidates(engine)
     });
+    register("predict_candidates", |context, engine| {
+        context.predict_candidates(engine).unwrap();
+    });
     register("erase_character_before_cursor", |context, engine| {
         context.erase_character_before_cursor(engine)
     });
@@ -90,6 +93,14 @@ pub(crate) fn ibus_akaza_commands_map() -> HashMap<&'static str, IbusAkazaComman
     register("convert_to_half_romaji", |context, engine| {
         context.convert_to_half_romaji(engine).unwrap();
     });
+    // 変換中の読みと、選んでいる候補をユーザー辞書に登録します
+    register("register_word", |context, engine| {
+        context.register_word(engine);
+    });
+    // 選んでいる候補を学習結果から消して、以後は候補に出さないようにします
+    register("forget_candidate", |context, engine| {
+        context.forget_candidate(engine).unwrap();
+    });
 
     {
         // TODO コピペがすごい。マクロかうまいなにかでまとめて登録できるようにしたい。
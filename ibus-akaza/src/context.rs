@@ -37,11 +37,11 @@ use ibus_sys::property::{
 use ibus_sys::text::{ibus_text_new_from_string, ibus_text_set_attributes, IBusText, StringExt};
 use libakaza::engine::base::HenkanEngine;
 use libakaza::engine::bigram_word_viterbi_engine::BigramWordViterbiEngine;
-use libakaza::extend_clause::{extend_left, extend_right};
+use libakaza::extend_clause::{extend_left, extend_right, keep_current};
 use libakaza::graph::candidate::Candidate;
-use libakaza::lm::system_bigram::MarisaSystemBigramLM;
 use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
 use libakaza::romkan::RomKanConverter;
+use libakaza::user_side_data::user_dict::UserDictEntry;
 
 use crate::commands::{ibus_akaza_commands_map, IbusAkazaCommand};
 use crate::input_mode::{
@@ -75,7 +75,7 @@ pub struct AkazaContext {
     pub(crate) lookup_table: IBusLookupTable,
     pub(crate) romkan: RomKanConverter,
     command_map: HashMap<&'static str, IbusAkazaCommand>,
-    engine: BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM>,
+    engine: BigramWordViterbiEngine<MarisaSystemUnigramLM>,
     clauses: Vec<VecDeque<Candidate>>,
     // げんざいせんたくされているぶんせつ。
     current_clause: usize,
@@ -163,9 +163,7 @@ impl AkazaContext {
 }
 
 impl AkazaContext {
-    pub(crate) fn new(
-        akaza: BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM>,
-    ) -> Self {
+    pub(crate) fn new(akaza: BigramWordViterbiEngine<MarisaSystemUnigramLM>) -> Self {
         let (input_mode_prop, prop_list, prop_dict) = Self::init_props();
         AkazaContext {
             input_mode: INPUT_MODE_HIRAGANA,
@@ -515,6 +513,60 @@ impl AkazaContext {
              */
     }
 
+    /**
+     * 変換中の読みを、いま選んでいる候補をつなげた表記でユーザー辞書に登録して、確定する。
+     * 「あかざ」を「赤」「座」と1文字ずつ選んでから登録すれば、次からは「赤座」と変換される。
+     * 表記を直接入力する UI はないので、候補の組み合わせで作れない表記は、
+     * user_dict.v1.txt を編集して登録する。
+     */
+    pub(crate) fn register_word(&mut self, engine: *mut IBusEngine) {
+        if !self.in_henkan_mode() || self.clauses.is_empty() {
+            return;
+        }
+        let yomi: String = self.clauses.iter().map(|c| c[0].yomi.as_str()).collect();
+        let surface = self.build_string();
+        if let Ok(mut user_data) = self.engine.user_data.lock() {
+            user_data.add_word(UserDictEntry::new(&yomi, &surface));
+            if let Err(err) = user_data.write_user_stats_file() {
+                warn!("Cannot save user dictionary: {}", err);
+            }
+        } else {
+            warn!("Cannot get mutex for registering a word");
+            return;
+        }
+
+        // 1単語として学習させてから確定する。
+        self.clauses = vec![VecDeque::from([Candidate::new(&yomi, &surface, 0_f32)])];
+        self.node_selected.clear();
+        self.commit_string(engine, surface.as_str());
+    }
+
+    /**
+     * 選んでいる候補を忘れる。学習した結果を消して、以後はその候補を出さない。
+     * 候補を出しなおすときは、文節の区切りはそのままにする。
+     */
+    pub(crate) fn forget_candidate(&mut self, engine: *mut IBusEngine) -> Result<()> {
+        if !self.in_henkan_mode() || self.clauses.is_empty() {
+            return Ok(());
+        }
+        let idx = *self.node_selected.get(&self.current_clause).unwrap_or(&0);
+        let candidate = &self.clauses[self.current_clause][idx];
+        if let Ok(mut user_data) = self.engine.user_data.lock() {
+            user_data.forget_word(&candidate.yomi, &candidate.surface);
+            if let Err(err) = user_data.write_user_stats_file() {
+                warn!("Cannot save user data: {}", err);
+            }
+        } else {
+            warn!("Cannot get mutex for forgetting a candidate");
+            return Ok(());
+        }
+
+        self.force_selected_clause = keep_current(&self.clauses);
+        self._update_candidates(engine)?;
+        self.node_selected.clear();
+        Ok(())
+    }
+
     pub fn commit_candidate(&mut self, engine: *mut IBusEngine) {
         let s = self.build_string();
         self.commit_string(engine, s.as_str());
@@ -561,6 +613,30 @@ impl AkazaContext {
         Ok(())
     }
 
+    /**
+     * 入力中の読みの続きを予測した候補を、1文節の候補として表示する。
+     * 予測できる候補がなければ、通常の変換をする。
+     */
+    pub(crate) fn predict_candidates(&mut self, engine: *mut IBusEngine) -> Result<()> {
+        let predictions = self.engine.predict(
+            self.preedit.as_str(),
+            self.lookup_table.get_page_size() as usize,
+        )?;
+        if predictions.is_empty() {
+            self.update_candidates(engine);
+            return Ok(());
+        }
+
+        self.clauses = vec![VecDeque::from(predictions)];
+        self.current_clause = 0;
+        self.node_selected.clear();
+        self.force_selected_clause.clear();
+
+        self.create_lookup_table();
+        self.refresh(engine);
+        Ok(())
+    }
+
     /**
      * 現在の候補選択状態から、 lookup table を構築する。
      */
@@ -572,7 +648,17 @@ impl AkazaContext {
         if !self.clauses.is_empty() {
             // lookup table に候補を詰め込んでいく。
             for node in &self.clauses[self.current_clause] {
-                let candidate = &node.surface_with_dynamic();
+                // アノテーションがあれば、候補のうしろに注釈として表示する。
+                let mut candidate = match &node.annotation {
+                    Some(annotation) => {
+                        format!("{} ; {}", node.surface_with_dynamic(), annotation)
+                    }
+                    None => node.surface_with_dynamic(),
+                };
+                // show_name を指定した辞書から引いた候補には、辞書の名前も表示する。
+                if let Some(dict_label) = &node.dict_label {
+                    candidate += format!(" [{}]", dict_label).as_str();
+                }
                 self.lookup_table.append_candidate(candidate.to_ibus_text());
             }
         }
@@ -4,15 +4,15 @@ use log::trace;
 
 use ibus_sys::core::{IBusModifierType_IBUS_CONTROL_MASK, IBusModifierType_IBUS_SHIFT_MASK};
 use ibus_sys::ibus_key::{
-    IBUS_KEY_BackSpace, IBUS_KEY_Down, IBUS_KEY_Escape, IBUS_KEY_Hangul, IBUS_KEY_Hangul_Hanja,
-    IBUS_KEY_Henkan, IBUS_KEY_KP_Down, IBUS_KEY_KP_Enter, IBUS_KEY_KP_Left, IBUS_KEY_KP_Page_Down,
-    IBUS_KEY_KP_Page_Up, IBUS_KEY_KP_Right, IBUS_KEY_KP_Up, IBUS_KEY_Left, IBUS_KEY_Muhenkan,
-    IBUS_KEY_Page_Down, IBUS_KEY_Page_Up, IBUS_KEY_Return, IBUS_KEY_Right, IBUS_KEY_Up,
-    IBUS_KEY_colon, IBUS_KEY_h, IBUS_KEY_j, IBUS_KEY_k, IBUS_KEY_l, IBUS_KEY_space, IBUS_KEY_0,
-    IBUS_KEY_1, IBUS_KEY_2, IBUS_KEY_3, IBUS_KEY_4, IBUS_KEY_5, IBUS_KEY_6, IBUS_KEY_7, IBUS_KEY_8,
-    IBUS_KEY_9, IBUS_KEY_F10, IBUS_KEY_F6, IBUS_KEY_F7, IBUS_KEY_F8, IBUS_KEY_F9, IBUS_KEY_KP_0,
-    IBUS_KEY_KP_1, IBUS_KEY_KP_2, IBUS_KEY_KP_3, IBUS_KEY_KP_4, IBUS_KEY_KP_5, IBUS_KEY_KP_6,
-    IBUS_KEY_KP_7, IBUS_KEY_KP_8, IBUS_KEY_KP_9,
+    IBUS_KEY_BackSpace, IBUS_KEY_Delete, IBUS_KEY_Down, IBUS_KEY_Escape, IBUS_KEY_Hangul,
+    IBUS_KEY_Hangul_Hanja, IBUS_KEY_Henkan, IBUS_KEY_KP_Down, IBUS_KEY_KP_Enter, IBUS_KEY_KP_Left,
+    IBUS_KEY_KP_Page_Down, IBUS_KEY_KP_Page_Up, IBUS_KEY_KP_Right, IBUS_KEY_KP_Up, IBUS_KEY_Left,
+    IBUS_KEY_Muhenkan, IBUS_KEY_Page_Down, IBUS_KEY_Page_Up, IBUS_KEY_Return, IBUS_KEY_Right,
+    IBUS_KEY_Tab, IBUS_KEY_Up, IBUS_KEY_colon, IBUS_KEY_h, IBUS_KEY_j, IBUS_KEY_k, IBUS_KEY_l,
+    IBUS_KEY_space, IBUS_KEY_0, IBUS_KEY_1, IBUS_KEY_2, IBUS_KEY_3, IBUS_KEY_4, IBUS_KEY_5,
+    IBUS_KEY_6, IBUS_KEY_7, IBUS_KEY_8, IBUS_KEY_9, IBUS_KEY_F10, IBUS_KEY_F6, IBUS_KEY_F7,
+    IBUS_KEY_F8, IBUS_KEY_F9, IBUS_KEY_KP_0, IBUS_KEY_KP_1, IBUS_KEY_KP_2, IBUS_KEY_KP_3,
+    IBUS_KEY_KP_4, IBUS_KEY_KP_5, IBUS_KEY_KP_6, IBUS_KEY_KP_7, IBUS_KEY_KP_8, IBUS_KEY_KP_9,
 };
 
 use crate::context::KeyState;
@@ -146,6 +146,12 @@ impl KeyMap {
             "update_candidates",
         );
         builder.insert(&[KeyState::Conversion], &[IBUS_KEY_space], 0, "cursor_down");
+        builder.insert(
+            &[KeyState::Composition],
+            &[IBUS_KEY_Tab],
+            0,
+            "predict_candidates",
+        );
 
         builder.insert(
             &[KeyState::Conversion, KeyState::Composition],
@@ -249,6 +255,22 @@ impl KeyMap {
             "convert_to_half_romaji",
         );
 
+        // 単語登録
+        builder.insert(
+            &[KeyState::Conversion],
+            &[IBUS_KEY_F7],
+            IBusModifierType_IBUS_CONTROL_MASK,
+            "register_word",
+        );
+
+        // 候補の削除
+        builder.insert(
+            &[KeyState::Conversion],
+            &[IBUS_KEY_Delete],
+            IBusModifierType_IBUS_CONTROL_MASK,
+            "forget_candidate",
+        );
+
         builder.insert(
             &[KeyState::Conversion],
             &[IBUS_KEY_KP_Page_Up, IBUS_KEY_Page_Up],
@@ -0,0 +1,74 @@
+use std::fmt::{Display, Formatter};
+
+/// 文字種。変換候補の素性や、未知語の区切りに使う。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharType {
+    Hiragana,
+    Katakana,
+    Kanji,
+    Alphabet,
+    Number,
+    Symbol,
+}
+
+impl CharType {
+    pub fn of(c: char) -> CharType {
+        match c {
+            'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => CharType::Hiragana,
+            // 長音記号はカタカナとして扱う。「サーバー」のような語を一続きにしたいため。
+            'ァ'..='ヺ' | 'ー' | 'ヽ' | 'ヾ' | 'ｦ'..='ﾟ' => CharType::Katakana,
+            '一'..='龯' | '々' | '〆' => CharType::Kanji,
+            'a'..='z' | 'A'..='Z' | 'ａ'..='ｚ' | 'Ａ'..='Ｚ' => CharType::Alphabet,
+            '0'..='9' | '０'..='９' => CharType::Number,
+            _ => CharType::Symbol,
+        }
+    }
+}
+
+impl Display for CharType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// 文字種が連続しているところをまとめて、文字種の並びを返す。
+/// 「良くない」であれば [Kanji, Hiragana] になる。
+pub fn char_type_runs(s: &str) -> Vec<CharType> {
+    let mut runs: Vec<CharType> = Vec::new();
+    for c in s.chars() {
+        let char_type = CharType::of(c);
+        if runs.last() != Some(&char_type) {
+            runs.push(char_type);
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_type() {
+        assert_eq!(CharType::of('あ'), CharType::Hiragana);
+        assert_eq!(CharType::of('ア'), CharType::Katakana);
+        assert_eq!(CharType::of('ー'), CharType::Katakana);
+        assert_eq!(CharType::of('漢'), CharType::Kanji);
+        assert_eq!(CharType::of('々'), CharType::Kanji);
+        assert_eq!(CharType::of('a'), CharType::Alphabet);
+        assert_eq!(CharType::of('Ｂ'), CharType::Alphabet);
+        assert_eq!(CharType::of('3'), CharType::Number);
+        assert_eq!(CharType::of('３'), CharType::Number);
+        assert_eq!(CharType::of('、'), CharType::Symbol);
+    }
+
+    #[test]
+    fn test_char_type_runs() {
+        assert_eq!(
+            char_type_runs("良くない"),
+            vec![CharType::Kanji, CharType::Hiragana]
+        );
+        assert_eq!(char_type_runs("サーバー"), vec![CharType::Katakana]);
+        assert_eq!(char_type_runs(""), vec![]);
+    }
+}
@@ -4,6 +4,19 @@ dicts:
   - path: /usr/share/skk/SKK-JISYO.okinawa
     encoding: euc-jp
     dict_type: skk
+interpolation:
+  unigram_lambda: 0.3
+  bigram_lambda: 0.3
+dynamic_candidates:
+  date:
+    - yomi: きょう
+      formats: ["%Y-%m-%d", "{wareki}年%-m月%-d日"]
+  unicode: true
+  calculator: true
+  wareki: true
+user_stats:
+  half_life_days: 90
+  max_entries: 100000
  */
 use anyhow::Result;
 use log::{info, warn};
@@ -15,6 +28,15 @@ use std::io::BufReader;
 pub struct Config {
     pub dicts: Vec<DictConfig>,
     pub single_term: Option<Vec<DictConfig>>,
+    /// 指定されていれば、ユーザー言語モデルとシステム言語モデルを線形補間する。
+    /// 指定されていなければ、ユーザー言語モデルにエントリーがあればそれだけを使う。
+    pub interpolation: Option<InterpolationConfig>,
+    /// 日付や計算結果のような、入力から動的に作る候補。
+    #[serde(default)]
+    pub dynamic_candidates: DynamicCandidatesConfig,
+    /// ユーザー言語モデルの学習結果をどれくらい覚えておくか。
+    #[serde(default)]
+    pub user_stats: UserStatsConfig,
 }
 
 impl Config {
@@ -54,6 +76,124 @@ pub struct DictConfig {
     /// Default: UTF-8
     pub encoding: Option<String>,
     pub dict_type: String,
+    /// 辞書の名前。Default: ファイル名
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 候補のうしろに辞書の名前を表示するか。Default: false
+    #[serde(default)]
+    pub show_name: bool,
+    /// 優先度。同じ表層の候補が複数の辞書にある場合は、優先度が高い辞書のものを使う。
+    /// システム辞書の優先度は 0。Default: 0
+    #[serde(default)]
+    pub priority: i32,
+    /// 辞書から引いた単語のコストに足す値。負の値にすると変換されやすくなる。Default: 0
+    #[serde(default)]
+    pub weight: f32,
+}
+
+/// ユーザー言語モデルの確率に掛ける重み。システム言語モデルには (1 - lambda) が掛かる。
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct InterpolationConfig {
+    #[serde(default = "default_lambda")]
+    pub unigram_lambda: f32,
+    #[serde(default = "default_lambda")]
+    pub bigram_lambda: f32,
+}
+
+fn default_lambda() -> f32 {
+    0.3
+}
+
+impl Default for InterpolationConfig {
+    fn default() -> Self {
+        InterpolationConfig {
+            unigram_lambda: default_lambda(),
+            bigram_lambda: default_lambda(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct UserStatsConfig {
+    /// 指定されていれば、学習した回数を、最後に使ってからこの日数ごとに半分として扱う。
+    /// 指定されていなければ、回数を減らさない。
+    #[serde(default)]
+    pub half_life_days: Option<f32>,
+    /// ユニグラム、バイグラムそれぞれについて、覚えておくエントリー数の上限。
+    /// 超えたら、最後に使ったのが古いものから捨てる。
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_max_entries() -> usize {
+    100_000
+}
+
+impl Default for UserStatsConfig {
+    fn default() -> Self {
+        UserStatsConfig {
+            half_life_days: None,
+            max_entries: default_max_entries(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct DynamicCandidatesConfig {
+    #[serde(default = "default_date_candidates")]
+    pub date: Vec<DateCandidateConfig>,
+    /// "u+3042" → "あ"
+    #[serde(default = "default_true")]
+    pub unicode: bool,
+    /// "=1+2" → "3"
+    #[serde(default = "default_true")]
+    pub calculator: bool,
+    /// "れいわ6ねん" → "2024年", "2024ねん" → "令和6年"
+    #[serde(default = "default_true")]
+    pub wareki: bool,
+}
+
+/// yomi を変換したときに、現在の日時を formats の書式で候補にする。
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct DateCandidateConfig {
+    pub yomi: String,
+    pub formats: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// SKK-JISYO.dynamic にある西暦の書式とは重ならないようにしておく。
+fn default_date_candidates() -> Vec<DateCandidateConfig> {
+    vec![
+        DateCandidateConfig {
+            yomi: "きょう".to_string(),
+            formats: vec![
+                "{wareki}年%-m月%-d日".to_string(),
+                "%-m月%-d日({weekday})".to_string(),
+            ],
+        },
+        DateCandidateConfig {
+            yomi: "いま".to_string(),
+            formats: vec!["%H:%M".to_string()],
+        },
+        DateCandidateConfig {
+            yomi: "ようび".to_string(),
+            formats: vec!["{weekday}曜日".to_string()],
+        },
+    ]
+}
+
+impl Default for DynamicCandidatesConfig {
+    fn default() -> Self {
+        DynamicCandidatesConfig {
+            date: default_date_candidates(),
+            unicode: true,
+            calculator: true,
+            wareki: true,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -69,9 +209,83 @@ mod tests {
             DictConfig {
                 path: "/usr/share/skk/SKK-JISYO.L".to_string(),
                 encoding: Some("euc-jp".to_string()),
-                dict_type: "skk".to_string()
+                dict_type: "skk".to_string(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.interpolation, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_dict_priority() -> anyhow::Result<()> {
+        let config: Config = serde_yaml::from_str(
+            "dicts:\n  - path: glossary.txt\n    dict_type: skk\n    name: 社内用語\n    show_name: true\n    priority: 10\n    weight: -1.5\n",
+        )?;
+        assert_eq!(
+            config.dicts[0],
+            DictConfig {
+                path: "glossary.txt".to_string(),
+                encoding: None,
+                dict_type: "skk".to_string(),
+                name: Some("社内用語".to_string()),
+                show_name: true,
+                priority: 10,
+                weight: -1.5,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_interpolation() -> anyhow::Result<()> {
+        let config: Config =
+            serde_yaml::from_str("dicts: []\ninterpolation:\n  unigram_lambda: 0.5\n")?;
+        assert_eq!(
+            config.interpolation,
+            Some(InterpolationConfig {
+                unigram_lambda: 0.5,
+                bigram_lambda: 0.3,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_dynamic_candidates() -> anyhow::Result<()> {
+        let config: Config = serde_yaml::from_str(
+            "dicts: []\ndynamic_candidates:\n  date: []\n  calculator: false\n",
+        )?;
+        assert_eq!(
+            config.dynamic_candidates,
+            DynamicCandidatesConfig {
+                date: vec![],
+                unicode: true,
+                calculator: false,
+                wareki: true,
+            }
+        );
+        let config: Config = serde_yaml::from_str("dicts: []\n")?;
+        assert_eq!(
+            config.dynamic_candidates,
+            DynamicCandidatesConfig::default()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_user_stats() -> anyhow::Result<()> {
+        let config: Config =
+            serde_yaml::from_str("dicts: []\nuser_stats:\n  half_life_days: 90\n")?;
+        assert_eq!(
+            config.user_stats,
+            UserStatsConfig {
+                half_life_days: Some(90_f32),
+                max_entries: 100_000,
             }
         );
+        let config: Config = serde_yaml::from_str("dicts: []\n")?;
+        assert_eq!(config.user_stats, UserStatsConfig::default());
         Ok(())
     }
 }
@@ -0,0 +1,375 @@
+/*
+   動詞・形容詞の活用。
+
+   見出し語(終止形)の読みと表記、活用の種類から、活用した形の読みと表記を作る。
+   ユーザー辞書に「ググる」を登録すると、「ググら」「ググった」「ググらない」なども変換できるようにする。
+
+   活用語尾はかななので、読みと表記のどちらにも同じ語尾をつける。
+   表記の末尾が読みの活用語尾と一致しない場合("ぐぐる" → "Google" など)は活用させない。
+*/
+
+/// 活用の種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConjugationClass {
+    /// 五段活用。行は見出し語の末尾のかなで決まる。"書く", "ググる"
+    Godan,
+    /// カ行五段活用のうち、音便が促音便になるもの。"行く"
+    GodanIku,
+    /// 一段活用。"食べる"
+    Ichidan,
+    /// サ行変格活用。"勉強する"
+    Sahen,
+    /// 形容詞。"高い"
+    Keiyoushi,
+}
+
+/// 五段活用の行。子音、ア段からオ段までのかな、「た」「て」に続く音便の形。
+struct GodanRow {
+    consonant: char,
+    kana: [char; 5],
+    ta: &'static str,
+    te: &'static str,
+}
+
+const GODAN_ROWS: [GodanRow; 9] = [
+    GodanRow {
+        consonant: 'k',
+        kana: ['か', 'き', 'く', 'け', 'こ'],
+        ta: "いた",
+        te: "いて",
+    },
+    GodanRow {
+        consonant: 'g',
+        kana: ['が', 'ぎ', 'ぐ', 'げ', 'ご'],
+        ta: "いだ",
+        te: "いで",
+    },
+    GodanRow {
+        consonant: 's',
+        kana: ['さ', 'し', 'す', 'せ', 'そ'],
+        ta: "した",
+        te: "して",
+    },
+    GodanRow {
+        consonant: 't',
+        kana: ['た', 'ち', 'つ', 'て', 'と'],
+        ta: "った",
+        te: "って",
+    },
+    GodanRow {
+        consonant: 'n',
+        kana: ['な', 'に', 'ぬ', 'ね', 'の'],
+        ta: "んだ",
+        te: "んで",
+    },
+    GodanRow {
+        consonant: 'b',
+        kana: ['ば', 'び', 'ぶ', 'べ', 'ぼ'],
+        ta: "んだ",
+        te: "んで",
+    },
+    GodanRow {
+        consonant: 'm',
+        kana: ['ま', 'み', 'む', 'め', 'も'],
+        ta: "んだ",
+        te: "んで",
+    },
+    GodanRow {
+        consonant: 'r',
+        kana: ['ら', 'り', 'る', 'れ', 'ろ'],
+        ta: "った",
+        te: "って",
+    },
+    GodanRow {
+        consonant: 'w',
+        kana: ['わ', 'い', 'う', 'え', 'お'],
+        ta: "った",
+        te: "って",
+    },
+];
+
+const ICHIDAN_ENDINGS: &[&str] = &[
+    "",
+    "る",
+    "れ",
+    "ろ",
+    "よ",
+    "ない",
+    "なかった",
+    "ます",
+    "ました",
+    "た",
+    "て",
+    "れば",
+    "よう",
+];
+
+const SAHEN_ENDINGS: &[&str] = &[
+    "し",
+    "さ",
+    "す",
+    "する",
+    "すれ",
+    "せ",
+    "しろ",
+    "せよ",
+    "しない",
+    "しなかった",
+    "します",
+    "しました",
+    "した",
+    "して",
+    "すれば",
+    "しよう",
+];
+
+const KEIYOUSHI_ENDINGS: &[&str] = &[
+    "い",
+    "く",
+    "かっ",
+    "かろ",
+    "けれ",
+    "かった",
+    "くて",
+    "くない",
+    "くなかった",
+    "ければ",
+    "かろう",
+    "さ",
+];
+
+impl ConjugationClass {
+    /**
+     * Mozc や MS-IME の品詞名から、活用の種類を判定する。活用しない品詞なら None。
+     *
+     * 例: "動詞ラ行五段" → Godan, "一段動詞" → Ichidan
+     */
+    pub fn from_pos(pos: &str) -> Option<ConjugationClass> {
+        if pos.contains("促音便") {
+            Some(ConjugationClass::GodanIku)
+        } else if pos.contains("五段") {
+            Some(ConjugationClass::Godan)
+        } else if pos.contains("一段") {
+            Some(ConjugationClass::Ichidan)
+        } else if pos.contains("サ行変格")
+            || (pos.contains("動詞") && (pos.contains("サ変") || pos.contains("さ変")))
+        {
+            Some(ConjugationClass::Sahen)
+        } else if pos.contains("形容詞") {
+            Some(ConjugationClass::Keiyoushi)
+        } else {
+            None
+        }
+    }
+
+    /// 見出し語の活用語尾と、活用した形の語尾のリスト。見出し語がこの活用の形でなければ None。
+    fn endings(&self, yomi: &str) -> Option<(String, Vec<String>)> {
+        let (ending, endings) = match self {
+            ConjugationClass::Godan | ConjugationClass::GodanIku => {
+                let last = yomi.chars().last()?;
+                let row = GODAN_ROWS.iter().find(|row| row.kana[2] == last)?;
+                let (ta, te) = match self {
+                    ConjugationClass::GodanIku if last == 'く' => ("った", "って"),
+                    ConjugationClass::GodanIku => return None,
+                    _ => (row.ta, row.te),
+                };
+                let [a, i, u, e, o] = row.kana.map(|c| c.to_string());
+                // 「書い」「読ん」のような、「た」「て」が続く形。
+                let onbin = ta.trim_end_matches(['た', 'だ']).to_string();
+                let endings = vec![
+                    a.clone(),
+                    i.clone(),
+                    u.clone(),
+                    e.clone(),
+                    o.clone(),
+                    onbin,
+                    a.clone() + "ない",
+                    a + "なかった",
+                    i.clone() + "ます",
+                    i + "ました",
+                    ta.to_string(),
+                    te.to_string(),
+                    e + "ば",
+                    o + "う",
+                ];
+                (u, endings)
+            }
+            ConjugationClass::Ichidan => ("る".to_string(), to_strings(ICHIDAN_ENDINGS)),
+            ConjugationClass::Sahen => ("する".to_string(), to_strings(SAHEN_ENDINGS)),
+            ConjugationClass::Keiyoushi => ("い".to_string(), to_strings(KEIYOUSHI_ENDINGS)),
+        };
+        if yomi.ends_with(&ending) && yomi.len() > ending.len() {
+            Some((ending, endings))
+        } else {
+            None
+        }
+    }
+}
+
+fn to_strings(endings: &[&str]) -> Vec<String> {
+    endings.iter().map(|it| it.to_string()).collect()
+}
+
+/**
+ * 見出し語の読みと表記から、活用した形の読みと表記のリストを作る。見出し語そのものを先頭にする。
+ * 活用語尾が見出し語と一致しない場合は、見出し語だけを返す。
+ *
+ * 例: ("ぐぐる", "ググる", Godan) → [("ぐぐる", "ググる"), ("ぐぐら", "ググら"), ..., ("ぐぐった", "ググった"), ...]
+ */
+pub fn conjugate(yomi: &str, surface: &str, class: ConjugationClass) -> Vec<(String, String)> {
+    let mut result = vec![(yomi.to_string(), surface.to_string())];
+    let Some((ending, endings)) = class.endings(yomi) else {
+        return result;
+    };
+    let (Some(yomi_stem), Some(surface_stem)) =
+        (yomi.strip_suffix(&ending), surface.strip_suffix(&ending))
+    else {
+        return result;
+    };
+    if surface_stem.is_empty() {
+        return result;
+    }
+    for ending in endings {
+        let pair = (
+            yomi_stem.to_string() + &ending,
+            surface_stem.to_string() + &ending,
+        );
+        if !result.contains(&pair) {
+            result.push(pair);
+        }
+    }
+    result
+}
+
+/**
+ * 五段活用の音便の形が c で終わる行の子音。
+ * SKK の送りありエントリーの "かk" から "書い" を、"よm" から "読ん" を引くのに使う。
+ *
+ * 例: 'っ' → ['t', 'r', 'w']
+ */
+pub fn onbin_consonants(c: char) -> Vec<char> {
+    GODAN_ROWS
+        .iter()
+        .filter(|row| row.ta.starts_with(c))
+        .map(|row| row.consonant)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surfaces(yomi: &str, surface: &str, class: ConjugationClass) -> Vec<String> {
+        conjugate(yomi, surface, class)
+            .into_iter()
+            .map(|(_, surface)| surface)
+            .collect()
+    }
+
+    #[test]
+    fn test_from_pos() {
+        assert_eq!(
+            ConjugationClass::from_pos("動詞ラ行五段"),
+            Some(ConjugationClass::Godan)
+        );
+        assert_eq!(
+            ConjugationClass::from_pos("動詞カ行促音便"),
+            Some(ConjugationClass::GodanIku)
+        );
+        assert_eq!(
+            ConjugationClass::from_pos("一段動詞"),
+            Some(ConjugationClass::Ichidan)
+        );
+        assert_eq!(
+            ConjugationClass::from_pos("動詞サ変"),
+            Some(ConjugationClass::Sahen)
+        );
+        assert_eq!(
+            ConjugationClass::from_pos("形容詞"),
+            Some(ConjugationClass::Keiyoushi)
+        );
+        assert_eq!(ConjugationClass::from_pos("名詞サ変"), None);
+        assert_eq!(ConjugationClass::from_pos("形容動詞"), None);
+        assert_eq!(ConjugationClass::from_pos("名詞"), None);
+    }
+
+    #[test]
+    fn test_godan() {
+        let got = conjugate("ぐぐる", "ググる", ConjugationClass::Godan);
+        assert_eq!(got[0], ("ぐぐる".to_string(), "ググる".to_string()));
+        assert!(got.contains(&("ぐぐった".to_string(), "ググった".to_string())));
+        assert!(got.contains(&("ぐぐらない".to_string(), "ググらない".to_string())));
+        assert_eq!(
+            surfaces("かく", "書く", ConjugationClass::Godan),
+            vec![
+                "書く",
+                "書か",
+                "書き",
+                "書け",
+                "書こ",
+                "書い",
+                "書かない",
+                "書かなかった",
+                "書きます",
+                "書きました",
+                "書いた",
+                "書いて",
+                "書けば",
+                "書こう"
+            ]
+        );
+        let got = surfaces("よむ", "読む", ConjugationClass::Godan);
+        assert!(got.contains(&"読んだ".to_string()));
+        let got = surfaces("かう", "買う", ConjugationClass::Godan);
+        assert!(got.contains(&"買わない".to_string()));
+        assert!(got.contains(&"買って".to_string()));
+    }
+
+    #[test]
+    fn test_godan_iku() {
+        let got = surfaces("いく", "行く", ConjugationClass::GodanIku);
+        assert!(got.contains(&"行った".to_string()));
+        assert!(!got.contains(&"行いた".to_string()));
+    }
+
+    #[test]
+    fn test_ichidan_sahen_keiyoushi() {
+        let got = surfaces("たべる", "食べる", ConjugationClass::Ichidan);
+        assert!(got.contains(&"食べ".to_string()));
+        assert!(got.contains(&"食べない".to_string()));
+        assert!(got.contains(&"食べよう".to_string()));
+
+        let got = surfaces("べんきょうする", "勉強する", ConjugationClass::Sahen);
+        assert!(got.contains(&"勉強した".to_string()));
+        assert!(got.contains(&"勉強しない".to_string()));
+
+        let got = surfaces("たかい", "高い", ConjugationClass::Keiyoushi);
+        assert!(got.contains(&"高かった".to_string()));
+        assert!(got.contains(&"高くない".to_string()));
+    }
+
+    /// 活用語尾が一致しないものは、見出し語だけにする。
+    #[test]
+    fn test_unconjugatable() {
+        assert_eq!(
+            conjugate("ぐぐる", "Google", ConjugationClass::Godan),
+            vec![("ぐぐる".to_string(), "Google".to_string())]
+        );
+        assert_eq!(
+            conjugate("たかい", "高い", ConjugationClass::Ichidan),
+            vec![("たかい".to_string(), "高い".to_string())]
+        );
+        assert_eq!(
+            conjugate("る", "る", ConjugationClass::Godan),
+            vec![("る".to_string(), "る".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_onbin_consonants() {
+        assert_eq!(onbin_consonants('い'), vec!['k', 'g']);
+        assert_eq!(onbin_consonants('っ'), vec!['t', 'r', 'w']);
+        assert_eq!(onbin_consonants('ん'), vec!['n', 'b', 'm']);
+        assert_eq!(onbin_consonants('か'), Vec::<char>::new());
+    }
+}
@@ -4,5 +4,10 @@ const ALPHA: f32 = 0.00001;
 // 確率の計算。
 // 加算スムージングをかけている。
 pub fn calc_cost(count: u32, unique_words: u32, total_words: u32) -> f32 {
-    -f32::log10(((count as f32) + ALPHA) / ((unique_words as f32) + ALPHA + (total_words as f32)))
+    calc_cost_f32(count as f32, unique_words, total_words)
+}
+
+// 回数が実数の場合の確率の計算。ユーザー言語モデルで、回数を時間とともに減らすときに使う。
+pub fn calc_cost_f32(count: f32, unique_words: u32, total_words: u32) -> f32 {
+    -f32::log10((count + ALPHA) / ((unique_words as f32) + ALPHA + (total_words as f32)))
 }
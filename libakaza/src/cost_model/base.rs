@@ -0,0 +1,22 @@
+use crate::graph::word_node::WordNode;
+
+/// ラティス上のノードとエッジのコストを計算する。
+/// 変換エンジンを複数のスレッドから共有できるように、Send + Sync であること。
+pub trait CostModel: Send + Sync {
+    /// 単語そのもののコスト。
+    fn get_node_cost(&self, node: &WordNode) -> f32;
+    /// prev の直後に node が続くときのコスト。
+    fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32;
+
+    /// prev2, prev の直後に node が続くときのエッジのコスト。
+    /// 2つ前の単語を見ないモデルでは get_edge_cost と同じ。
+    fn get_trigram_edge_cost(&self, _prev2: &WordNode, prev: &WordNode, node: &WordNode) -> f32 {
+        self.get_edge_cost(prev, node)
+    }
+
+    /// get_trigram_edge_cost が prev2 によって変わるか。
+    /// false であれば、経路探索は直前のノードだけを見ればよい。
+    fn uses_trigram(&self) -> bool {
+        false
+    }
+}
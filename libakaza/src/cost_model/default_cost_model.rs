@@ -0,0 +1,256 @@
+use std::sync::{Arc, Mutex};
+
+use log::trace;
+
+use crate::char_type::CharType;
+use crate::cost_model::base::CostModel;
+use crate::graph::segmenter::unknown_word_run;
+use crate::graph::word_node::WordNode;
+use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
+use crate::lm::char_bigram::char_ngram_cost;
+use crate::user_side_data::user_data::UserData;
+
+/// 候補から削除されたバイグラムのエッジのコスト。
+pub(crate) const BLACKLISTED_EDGE_COST: f32 = 1000_f32;
+
+/// ユーザーの入力履歴があればそれを優先し、なければシステム言語モデルのコストを使う。
+pub struct DefaultCostModel<U: SystemUnigramLM, B: SystemBigramLM> {
+    user_data: Arc<Mutex<UserData>>,
+    system_unigram_lm: Arc<U>,
+    system_bigram_lm: Arc<B>,
+}
+
+impl<U: SystemUnigramLM, B: SystemBigramLM> DefaultCostModel<U, B> {
+    pub fn new(
+        user_data: Arc<Mutex<UserData>>,
+        system_unigram_lm: Arc<U>,
+        system_bigram_lm: Arc<B>,
+    ) -> DefaultCostModel<U, B> {
+        DefaultCostModel {
+            user_data,
+            system_unigram_lm,
+            system_bigram_lm,
+        }
+    }
+}
+
+impl<U: SystemUnigramLM, B: SystemBigramLM> CostModel for DefaultCostModel<U, B> {
+    fn get_node_cost(&self, node: &WordNode) -> f32 {
+        if let Some(user_cost) = self.user_data.lock().unwrap().get_unigram_cost(node) {
+            // use user's score. if it's exists.
+            return user_cost;
+        }
+
+        system_node_cost(self.system_unigram_lm.as_ref(), node)
+    }
+
+    fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32 {
+        {
+            let user_data = self.user_data.lock().unwrap();
+            // 候補から削除したつながりでも、ほかに経路がなければ使えるように、大きなコストにしておく。
+            if user_data.is_blacklisted_bigram(prev, node) {
+                return BLACKLISTED_EDGE_COST;
+            }
+            if let Some(cost) = user_data.get_bigram_cost(prev, node) {
+                return cost;
+            }
+        }
+
+        system_edge_cost(
+            self.system_unigram_lm.as_ref(),
+            self.system_bigram_lm.as_ref(),
+            prev,
+            node,
+        )
+    }
+}
+
+/// システム言語モデルから求めたノードのコスト。
+pub(crate) fn system_node_cost<U: SystemUnigramLM>(system_unigram_lm: &U, node: &WordNode) -> f32 {
+    if let Some((_, system_unigram_cost)) = node.word_id_and_score {
+        trace!("HIT!: {}, {}", node.key(), system_unigram_cost);
+        system_unigram_cost
+    } else if node.surface.len() < node.yomi.len() {
+        // 労働者災害補償保険法 のように、システム辞書には wikipedia から採録されているが,
+        // 言語モデルには採録されていない場合,漢字候補を先頭に持ってくる。
+        // つまり、変換後のほうが短くなるもののほうをコストを安くしておく。
+        system_unigram_lm.get_default_cost_for_short()
+    } else {
+        unknown_word_cost(system_unigram_lm, node)
+    }
+}
+
+/// 未知語の読み1文字あたりのコスト。文字バイグラムが採録されていないときに使う。
+const UNKNOWN_CHAR_COST: f32 = 0.5_f32;
+
+/// 言語モデルに採録されていない単語のコスト。
+fn unknown_word_cost<U: SystemUnigramLM>(system_unigram_lm: &U, node: &WordNode) -> f32 {
+    let default_cost = system_unigram_lm.get_default_cost();
+    let yomi = node.yomi.as_str();
+    if unknown_word_run(yomi) != Some(yomi) {
+        return default_cost;
+    }
+    // カタカナ・英字・数字・外来語の並びは、ひとかたまりの未知語として長さに応じたコストにする。
+    // 読みの文字バイグラムがあればそれで見積もり、なければ1文字ごとに一定のコストを足す。
+    let length_cost = char_ngram_cost(system_unigram_lm, yomi)
+        .unwrap_or(UNKNOWN_CHAR_COST * yomi.chars().count() as f32);
+    let cost = default_cost + length_cost;
+    // ひらがなで入力された外来語は、カタカナにしたものを優先する。
+    if node
+        .surface
+        .starts_with(|c| CharType::of(c) == CharType::Hiragana)
+    {
+        cost + UNKNOWN_CHAR_COST
+    } else {
+        cost
+    }
+}
+
+/// システム言語モデルから求めたエッジのコスト。
+/// バイグラムが採録されていなければ、前の単語のバックオフ重みと後ろの単語のユニグラムのコストから求める。
+pub(crate) fn system_edge_cost<U: SystemUnigramLM, B: SystemBigramLM>(
+    system_unigram_lm: &U,
+    system_bigram_lm: &B,
+    prev: &WordNode,
+    node: &WordNode,
+) -> f32 {
+    let Some((prev_id, _)) = prev.word_id_and_score else {
+        return system_bigram_lm.get_default_edge_cost();
+    };
+    let Some((node_id, node_score)) = node.word_id_and_score else {
+        return system_bigram_lm.get_default_edge_cost();
+    };
+    if let Some(cost) = system_bigram_lm.get_edge_cost(prev_id, node_id) {
+        cost
+    } else if let Some(backoff) = system_unigram_lm.get_backoff_cost(&prev.key()) {
+        backoff + node_score
+    } else {
+        system_bigram_lm.get_default_edge_cost()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lm::char_bigram::char_bigram_key;
+    use crate::lm::system_bigram::MarisaSystemBigramLMBuilder;
+    use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_default_cost() -> anyhow::Result<()> {
+        let cost_model = DefaultCostModel::new(
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(18_f32)
+                    .build()?,
+            ),
+        );
+        let known = WordNode::new(0, "私", "わたし", Some((1, 5_f32)));
+        let unknown = WordNode::new(0, "渡し", "わたし", None);
+        assert_eq!(cost_model.get_node_cost(&known), 5_f32);
+        assert_eq!(cost_model.get_node_cost(&unknown), 19_f32);
+        assert_eq!(cost_model.get_edge_cost(&known, &unknown), 18_f32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blacklisted_edge_cost() -> anyhow::Result<()> {
+        let user_data = Arc::new(Mutex::new(UserData::default()));
+        user_data
+            .lock()
+            .unwrap()
+            .forget_bigram("私/わたし", "派/は");
+        let cost_model = DefaultCostModel::new(
+            user_data,
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(18_f32)
+                    .build()?,
+            ),
+        );
+        let watashi = WordNode::new(0, "私", "わたし", None);
+        let ha = WordNode::new(9, "派", "は", None);
+        assert_eq!(
+            cost_model.get_edge_cost(&watashi, &ha),
+            BLACKLISTED_EDGE_COST
+        );
+        assert_eq!(cost_model.get_edge_cost(&ha, &watashi), 18_f32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_backoff_edge_cost() -> anyhow::Result<()> {
+        let mut bigram_builder = MarisaSystemBigramLMBuilder::default();
+        bigram_builder.set_default_edge_cost(18_f32);
+        bigram_builder.add(1, 2, 0.5_f32);
+        let mut unigram_builder = MarisaSystemUnigramLMBuilder::default();
+        unigram_builder.add_with_backoff("私/わたし", 5_f32, 1.5_f32);
+        let cost_model = DefaultCostModel::new(
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                unigram_builder
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(bigram_builder.build()?),
+        );
+        let watashi = WordNode::new(0, "私", "わたし", Some((1, 5_f32)));
+        let ha = WordNode::new(9, "は", "は", Some((2, 2_f32)));
+        let ga = WordNode::new(9, "が", "が", Some((3, 3_f32)));
+        // 採録されているバイグラム
+        assert_eq!(cost_model.get_edge_cost(&watashi, &ha), 0.5_f32);
+        // 採録されていないバイグラムは、バックオフ重み + ユニグラムのコスト
+        assert_eq!(cost_model.get_edge_cost(&watashi, &ga), 4.5_f32);
+        // バックオフ重みがなければデフォルトのコスト
+        assert_eq!(cost_model.get_edge_cost(&ha, &ga), 18_f32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_word_cost() -> anyhow::Result<()> {
+        let mut unigram_builder = MarisaSystemUnigramLMBuilder::default();
+        unigram_builder
+            .set_default_cost(20_f32)
+            .set_default_cost_for_short(20_f32);
+        for (prev, c) in [
+            ('\u{2}', 'こ'),
+            ('こ', 'ー'),
+            ('ー', 'ひ'),
+            ('ひ', 'ー'),
+            ('ー', '\u{3}'),
+        ] {
+            unigram_builder.add(&char_bigram_key(prev, c), 1_f32);
+        }
+        let system_unigram_lm = unigram_builder.build();
+        let cost = |surface: &str, yomi: &str| {
+            system_node_cost(&system_unigram_lm, &WordNode::new(0, surface, yomi, None))
+        };
+
+        // ひとかたまりの未知語でなければデフォルトのコスト
+        assert_eq!(cost("はば", "はば"), 20_f32);
+        assert_eq!(cost("ハバ", "はば"), 20_f32);
+        // 数字・カタカナの並びは長さに応じたコスト
+        assert_eq!(cost("2023", "2023"), 22_f32);
+        assert_eq!(cost("コンピュータ", "コンピュータ"), 23_f32);
+        assert!(cost("コンピュータ", "コンピュータ") < cost("コンピューター", "コンピューター"));
+        // ひらがなで入力された外来語は文字バイグラムで見積もり、カタカナを優先する。
+        assert_eq!(cost("コーヒー", "こーひー"), 25_f32);
+        assert_eq!(cost("こーひー", "こーひー"), 25.5_f32);
+        Ok(())
+    }
+}
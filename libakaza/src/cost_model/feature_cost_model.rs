@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use crate::cost_model::base::CostModel;
+use crate::cost_model::features::{edge_features, node_features, FeatureWeights};
+use crate::graph::word_node::WordNode;
+
+/**
+ * 他のコストモデルのコストに、素性の重みを足す。
+ * 単語単位の頻度ではとらえにくい「送り仮名がついているほうが自然」といった傾向を補正する。
+ */
+pub struct FeatureCostModel {
+    base: Arc<dyn CostModel>,
+    weights: Arc<FeatureWeights>,
+}
+
+impl FeatureCostModel {
+    pub fn new(base: Arc<dyn CostModel>, weights: Arc<FeatureWeights>) -> FeatureCostModel {
+        FeatureCostModel { base, weights }
+    }
+}
+
+impl CostModel for FeatureCostModel {
+    fn get_node_cost(&self, node: &WordNode) -> f32 {
+        self.base.get_node_cost(node) + self.weights.score(&node_features(node))
+    }
+
+    fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32 {
+        self.base.get_edge_cost(prev, node) + self.weights.score(&edge_features(prev, node))
+    }
+
+    fn get_trigram_edge_cost(&self, prev2: &WordNode, prev: &WordNode, node: &WordNode) -> f32 {
+        self.base.get_trigram_edge_cost(prev2, prev, node)
+            + self.weights.score(&edge_features(prev, node))
+    }
+
+    fn uses_trigram(&self) -> bool {
+        self.base.uses_trigram()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    struct FlatCostModel {}
+
+    impl CostModel for FlatCostModel {
+        fn get_node_cost(&self, _node: &WordNode) -> f32 {
+            5_f32
+        }
+
+        fn get_edge_cost(&self, _prev: &WordNode, _node: &WordNode) -> f32 {
+            1_f32
+        }
+    }
+
+    #[test]
+    fn test_feature_cost() {
+        let cost_model = FeatureCostModel::new(
+            Arc::new(FlatCostModel {}),
+            Arc::new(FeatureWeights::new(HashMap::from([
+                ("okuri:3".to_string(), -1_f32),
+                ("trans:BOS>Kanji".to_string(), 0.5_f32),
+            ]))),
+        );
+        let yokunai = WordNode::new(0, "良くない", "よくない", None);
+        let yokunai_wrong = WordNode::new(0, "翼内", "よくない", None);
+        // 送り仮名がついているほうが有利になる。
+        assert_eq!(cost_model.get_node_cost(&yokunai), 4_f32);
+        assert_eq!(cost_model.get_node_cost(&yokunai_wrong), 5_f32);
+        assert_eq!(
+            cost_model.get_edge_cost(&WordNode::create_bos(), &yokunai),
+            1.5_f32
+        );
+        assert!(!cost_model.uses_trigram());
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::RwLock;
+
+use anyhow::{Context, Result};
+use log::info;
+
+use crate::char_type::{char_type_runs, CharType};
+use crate::graph::word_node::WordNode;
+
+const BOS: &str = "__BOS__";
+const EOS: &str = "__EOS__";
+
+/**
+ * ノードの素性。品詞は使わず、表層と読みだけから求める。
+ *
+ * - type: 表層の文字種の並び。「良くない」なら Kanji+Hiragana
+ * - ratio: 読みに対する表層の長さの比。漢字が多いほど小さくなる
+ * - okuri: 漢字のあとに続く送り仮名の長さ
+ * - single_term: single term 辞書から引いた候補
+ */
+pub fn node_features(node: &WordNode) -> Vec<String> {
+    if node.surface == BOS || node.surface == EOS {
+        return Vec::new();
+    }
+
+    let mut features: Vec<String> = Vec::new();
+    let runs = char_type_runs(&node.surface);
+    if runs.len() > 3 {
+        features.push("type:Mixed".to_string());
+    } else {
+        features.push(format!(
+            "type:{}",
+            runs.iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>()
+                .join("+")
+        ));
+    }
+
+    // 0.25 刻みにまるめる。
+    let ratio = node.surface.chars().count() as f32 / node.yomi.chars().count().max(1) as f32;
+    features.push(format!(
+        "ratio:{:.2}",
+        ((ratio * 4_f32).round() / 4_f32).min(1.5)
+    ));
+
+    if runs == [CharType::Kanji, CharType::Hiragana] {
+        let okuri = node
+            .surface
+            .chars()
+            .rev()
+            .take_while(|c| CharType::of(*c) == CharType::Hiragana)
+            .collect::<String>();
+        let okuri = okuri.chars().rev().collect::<String>();
+        if node.yomi.ends_with(okuri.as_str()) {
+            features.push(format!("okuri:{}", okuri.chars().count().min(3)));
+        }
+    }
+
+    if node.single_term {
+        features.push("single_term".to_string());
+    }
+    features
+}
+
+/// エッジの素性。前の単語の末尾と、後ろの単語の先頭の文字種の組み合わせ。
+pub fn edge_features(prev: &WordNode, node: &WordNode) -> Vec<String> {
+    let prev_type = if prev.surface == BOS {
+        "BOS".to_string()
+    } else {
+        prev.surface
+            .chars()
+            .last()
+            .map(|c| CharType::of(c).to_string())
+            .unwrap_or_default()
+    };
+    let node_type = if node.surface == EOS {
+        "EOS".to_string()
+    } else {
+        node.surface
+            .chars()
+            .next()
+            .map(|c| CharType::of(c).to_string())
+            .unwrap_or_default()
+    };
+    vec![format!("trans:{}>{}", prev_type, node_type)]
+}
+
+/// 文頭から文末までの経路にあらわれる素性をすべて返す。学習用。
+pub fn path_features(nodes: &[WordNode]) -> Vec<String> {
+    let mut features: Vec<String> = Vec::new();
+    let bos = WordNode::create_bos();
+    let eos = WordNode::create_eos(0);
+    let path = [&[bos], nodes, &[eos]].concat();
+    for node in &path {
+        features.extend(node_features(node));
+    }
+    for i in 1..path.len() {
+        features.extend(edge_features(&path[i - 1], &path[i]));
+    }
+    features
+}
+
+/**
+ * 素性の重み。コストに足しこむので、負の値であればその素性をもつ候補が有利になる。
+ * 学習中に更新できるように、RwLock で保持する。
+ */
+#[derive(Default)]
+pub struct FeatureWeights {
+    weights: RwLock<HashMap<String, f32>>,
+}
+
+impl FeatureWeights {
+    pub fn new(weights: HashMap<String, f32>) -> FeatureWeights {
+        FeatureWeights {
+            weights: RwLock::new(weights),
+        }
+    }
+
+    /// "{素性} {重み}" の形式のファイルを読む。
+    pub fn load(path: &str) -> Result<FeatureWeights> {
+        info!("Loading feature weights: {}", path);
+        let file = File::open(path)?;
+        let mut weights: HashMap<String, f32> = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.context("Cannot read feature weights file")?;
+            let Some((feature, weight)) = line.trim().rsplit_once(' ') else {
+                continue;
+            };
+            let weight = weight
+                .parse::<f32>()
+                .with_context(|| format!("Invalid line in feature weights: {}", line))?;
+            weights.insert(feature.to_string(), weight);
+        }
+        Ok(FeatureWeights::new(weights))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut weights = self.as_hash_map().into_iter().collect::<Vec<_>>();
+        weights.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut file = File::create(path)?;
+        for (feature, weight) in weights {
+            file.write_fmt(format_args!("{} {}\n", feature, weight))?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, feature: &str) -> f32 {
+        self.weights
+            .read()
+            .unwrap()
+            .get(feature)
+            .cloned()
+            .unwrap_or(0_f32)
+    }
+
+    pub fn score(&self, features: &[String]) -> f32 {
+        let weights = self.weights.read().unwrap();
+        features
+            .iter()
+            .map(|f| weights.get(f).cloned().unwrap_or(0_f32))
+            .sum()
+    }
+
+    pub fn update(&self, feature: &str, weight: f32) {
+        self.weights
+            .write()
+            .unwrap()
+            .insert(feature.to_string(), weight);
+    }
+
+    pub fn set_weights(&self, weights: HashMap<String, f32>) {
+        *self.weights.write().unwrap() = weights;
+    }
+
+    pub fn as_hash_map(&self) -> HashMap<String, f32> {
+        self.weights.read().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_node_features() {
+        assert_eq!(
+            node_features(&WordNode::new(0, "良くない", "よくない", None)),
+            vec!["type:Kanji+Hiragana", "ratio:1.00", "okuri:3"]
+        );
+        assert_eq!(
+            node_features(&WordNode::new(0, "翼内", "よくない", None)),
+            vec!["type:Kanji", "ratio:0.50"]
+        );
+        let mut node = WordNode::new(0, "ハイ", "はい", None);
+        node.single_term = true;
+        assert_eq!(
+            node_features(&node),
+            vec!["type:Katakana", "ratio:1.00", "single_term"]
+        );
+    }
+
+    #[test]
+    fn test_path_features() {
+        let got = path_features(&[
+            WordNode::new(0, "私", "わたし", None),
+            WordNode::new(9, "は", "は", None),
+        ]);
+        assert_eq!(
+            got,
+            vec![
+                "type:Kanji",
+                "ratio:0.25",
+                "type:Hiragana",
+                "ratio:1.00",
+                "trans:BOS>Kanji",
+                "trans:Kanji>Hiragana",
+                "trans:Hiragana>EOS",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() -> anyhow::Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let path = tmpfile.path().to_str().unwrap().to_string();
+
+        let weights = FeatureWeights::default();
+        weights.update("okuri:3", -1.5);
+        weights.update("trans:BOS>Kanji", 0.25);
+        weights.save(&path)?;
+
+        let weights = FeatureWeights::load(&path)?;
+        assert_eq!(weights.get("okuri:3"), -1.5);
+        assert_eq!(
+            weights.score(&["okuri:3".to_string(), "trans:BOS>Kanji".to_string()]),
+            -1.25
+        );
+        assert_eq!(weights.get("unknown"), 0_f32);
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Result};
+
+use crate::config::InterpolationConfig;
+use crate::cost_model::base::CostModel;
+use crate::cost_model::default_cost_model::{
+    system_edge_cost, system_node_cost, BLACKLISTED_EDGE_COST,
+};
+use crate::graph::word_node::WordNode;
+use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
+use crate::user_side_data::user_data::UserData;
+
+/**
+ * ユーザー言語モデルとシステム言語モデルを、確率の空間で線形補間する。
+ *
+ *   P(w) = λ * P_user(w) + (1 - λ) * P_system(w)
+ *
+ * ユーザー言語モデルにエントリーがない場合は P_user(w) = 0 として扱う。
+ * 一度だけ誤って確定した単語でも、システム言語モデルのスコアが残るので
+ * それだけで変換結果が固定されてしまうことがない。
+ */
+pub struct InterpolatedCostModel<U: SystemUnigramLM, B: SystemBigramLM> {
+    user_data: Arc<Mutex<UserData>>,
+    system_unigram_lm: Arc<U>,
+    system_bigram_lm: Arc<B>,
+    unigram_lambda: f32,
+    bigram_lambda: f32,
+}
+
+impl<U: SystemUnigramLM, B: SystemBigramLM> InterpolatedCostModel<U, B> {
+    pub fn new(
+        user_data: Arc<Mutex<UserData>>,
+        system_unigram_lm: Arc<U>,
+        system_bigram_lm: Arc<B>,
+        config: &InterpolationConfig,
+    ) -> Result<InterpolatedCostModel<U, B>> {
+        for (name, lambda) in [
+            ("unigram_lambda", config.unigram_lambda),
+            ("bigram_lambda", config.bigram_lambda),
+        ] {
+            // λ=1 だとユーザー言語モデルにない単語の確率が 0 になってしまう。
+            if !(0_f32..1_f32).contains(&lambda) {
+                bail!("{} must be in [0, 1): {}", name, lambda);
+            }
+        }
+
+        Ok(InterpolatedCostModel {
+            user_data,
+            system_unigram_lm,
+            system_bigram_lm,
+            unigram_lambda: config.unigram_lambda,
+            bigram_lambda: config.bigram_lambda,
+        })
+    }
+}
+
+/// コスト(-log10 P)の空間のまま補間した結果を返す。
+fn interpolate(lambda: f32, user_cost: Option<f32>, system_cost: f32) -> f32 {
+    let system_prob = (1_f32 - lambda) * 10_f32.powf(-system_cost);
+    let user_prob = user_cost.map_or(0_f32, |cost| lambda * 10_f32.powf(-cost));
+    -f32::log10(user_prob + system_prob)
+}
+
+impl<U: SystemUnigramLM, B: SystemBigramLM> CostModel for InterpolatedCostModel<U, B> {
+    fn get_node_cost(&self, node: &WordNode) -> f32 {
+        let user_cost = self.user_data.lock().unwrap().get_unigram_cost(node);
+        let system_cost = system_node_cost(self.system_unigram_lm.as_ref(), node);
+        interpolate(self.unigram_lambda, user_cost, system_cost)
+    }
+
+    fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32 {
+        let user_cost = {
+            let user_data = self.user_data.lock().unwrap();
+            if user_data.is_blacklisted_bigram(prev, node) {
+                return BLACKLISTED_EDGE_COST;
+            }
+            user_data.get_bigram_cost(prev, node)
+        };
+        let system_cost = system_edge_cost(
+            self.system_unigram_lm.as_ref(),
+            self.system_bigram_lm.as_ref(),
+            prev,
+            node,
+        );
+        interpolate(self.bigram_lambda, user_cost, system_cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::candidate::Candidate;
+    use crate::lm::system_bigram::MarisaSystemBigramLMBuilder;
+    use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+
+    use super::*;
+
+    fn build(user_data: UserData, config: &InterpolationConfig) -> Result<impl CostModel> {
+        InterpolatedCostModel::new(
+            Arc::new(Mutex::new(user_data)),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+            config,
+        )
+    }
+
+    #[test]
+    fn test_interpolate() {
+        // λ=0 ならシステム言語モデルのコストそのもの。
+        assert!((interpolate(0_f32, Some(1_f32), 5_f32) - 5_f32).abs() < 1e-4);
+        // ユーザー言語モデルにエントリーがなければ、(1-λ) の分だけコストが上がる。
+        assert!((interpolate(0.9, None, 5_f32) - 6_f32).abs() < 1e-4);
+        // 補間結果は、二つのコストの間に入る。
+        let got = interpolate(0.5, Some(1_f32), 5_f32);
+        assert!(1_f32 < got && got < 5_f32);
+    }
+
+    // ユーザー言語モデル上は同じコストでも、システム言語モデルのスコアの差が残る。
+    #[test]
+    fn test_system_score_is_kept() -> Result<()> {
+        let mut user_data = UserData::default();
+        user_data.record_entries(&[
+            Candidate::new("わたし", "私", 0_f32),
+            Candidate::new("わたし", "渡し", 0_f32),
+        ]);
+        let cost_model = build(user_data, &InterpolationConfig::default())?;
+
+        let watashi = WordNode::new(0, "私", "わたし", Some((1, 3_f32)));
+        let watashi2 = WordNode::new(0, "渡し", "わたし", Some((2, 6_f32)));
+        assert!(cost_model.get_node_cost(&watashi) < cost_model.get_node_cost(&watashi2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_lambda() {
+        let got = build(
+            UserData::default(),
+            &InterpolationConfig {
+                unigram_lambda: 1_f32,
+                bigram_lambda: 0.3,
+            },
+        );
+        assert!(got.is_err());
+    }
+}
@@ -0,0 +1,10 @@
+/*
+ * ラティスのノード・エッジにコストをつけるためのモデル。
+ * 言語モデルの組み合わせ方を差し替えられるようにしている。
+ */
+pub mod base;
+pub mod default_cost_model;
+pub mod feature_cost_model;
+pub mod features;
+pub mod interpolated_cost_model;
+pub mod trigram_cost_model;
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use crate::cost_model::base::CostModel;
+use crate::graph::word_node::WordNode;
+use crate::lm::base::SystemTrigramLM;
+
+/**
+ * 他のコストモデルのエッジコストに、システムの trigram 言語モデルによる補正値を足す。
+ * ノードのコストや、trigram 言語モデルにエントリーがない場合のコストは元のモデルのまま。
+ */
+pub struct TrigramCostModel<T: SystemTrigramLM> {
+    base: Arc<dyn CostModel>,
+    system_trigram_lm: Arc<T>,
+}
+
+impl<T: SystemTrigramLM> TrigramCostModel<T> {
+    pub fn new(base: Arc<dyn CostModel>, system_trigram_lm: Arc<T>) -> TrigramCostModel<T> {
+        TrigramCostModel {
+            base,
+            system_trigram_lm,
+        }
+    }
+}
+
+impl<T: SystemTrigramLM> CostModel for TrigramCostModel<T> {
+    fn get_node_cost(&self, node: &WordNode) -> f32 {
+        self.base.get_node_cost(node)
+    }
+
+    fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32 {
+        self.base.get_edge_cost(prev, node)
+    }
+
+    fn get_trigram_edge_cost(&self, prev2: &WordNode, prev: &WordNode, node: &WordNode) -> f32 {
+        let cost = self.base.get_trigram_edge_cost(prev2, prev, node);
+        let (Some((id1, _)), Some((id2, _)), Some((id3, _))) = (
+            prev2.word_id_and_score,
+            prev.word_id_and_score,
+            node.word_id_and_score,
+        ) else {
+            return cost;
+        };
+        cost + self
+            .system_trigram_lm
+            .get_trigram_cost(id1, id2, id3)
+            .unwrap_or(0_f32)
+    }
+
+    fn uses_trigram(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cost_model::default_cost_model::DefaultCostModel;
+    use crate::lm::system_bigram::MarisaSystemBigramLMBuilder;
+    use crate::lm::system_trigram::MarisaSystemTrigramLMBuilder;
+    use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+    use crate::user_side_data::user_data::UserData;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn test_trigram_edge_cost() -> anyhow::Result<()> {
+        let base = DefaultCostModel::new(
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let mut trigram = MarisaSystemTrigramLMBuilder::default();
+        trigram.add(1, 2, 3, -2_f32);
+        let cost_model = TrigramCostModel::new(Arc::new(base), Arc::new(trigram.build()));
+
+        let w1 = WordNode::new(0, "私", "わたし", Some((1, 5_f32)));
+        let w2 = WordNode::new(9, "の", "の", Some((2, 5_f32)));
+        let w3 = WordNode::new(12, "名前", "なまえ", Some((3, 5_f32)));
+        let w4 = WordNode::new(12, "なまえ", "なまえ", None);
+        assert_eq!(cost_model.get_edge_cost(&w2, &w3), 20_f32);
+        assert_eq!(cost_model.get_trigram_edge_cost(&w1, &w2, &w3), 18_f32);
+        assert_eq!(cost_model.get_trigram_edge_cost(&w1, &w2, &w4), 20_f32);
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use encoding_rs::Encoding;
+use log::{info, warn};
+
+use crate::config::DictConfig;
+use crate::dict::compiled_dict::CompiledDict;
+
+/*
+   パース済みの辞書のキャッシュ。
+
+   SKK-JISYO.L のような大きな辞書を毎回パースすると起動に時間がかかるので、
+   DictConfig ごとにパースした結果をコンパイル済みの辞書として $XDG_CACHE_HOME/akaza/ に保存しておく。
+
+   キャッシュには、元の辞書ファイルのパス、更新日時、サイズ、文字コード、内容のハッシュ値を
+   fingerprint として記録しておき、どれかが変わっていればキャッシュを使わずにパースしなおす。
+*/
+
+/// パース結果の形が変わったら上げる。古いキャッシュは使わずにパースしなおす。
+const PARSER_VERSION: u32 = 3;
+
+pub struct DictCache {
+    dir: PathBuf,
+}
+
+impl DictCache {
+    pub fn new(dir: PathBuf) -> DictCache {
+        DictCache { dir }
+    }
+
+    /// $XDG_CACHE_HOME/akaza/ をキャッシュディレクトリにする。
+    pub fn from_xdg() -> Result<DictCache> {
+        let basedir = xdg::BaseDirectories::with_prefix("akaza")?;
+        Ok(DictCache::new(basedir.get_cache_home()))
+    }
+
+    fn cache_path(&self, dict: &DictConfig) -> PathBuf {
+        let key = format!("{}\t{}", dict.path, dict.dict_type);
+        self.dir
+            .join(format!("dict-{:016x}.marisa", fnv1a(key.as_bytes())))
+    }
+
+    /// fingerprint が一致するキャッシュがあれば、それを返す。
+    pub fn get(&self, dict: &DictConfig, fingerprint: &str) -> Option<CompiledDict> {
+        let path = self.cache_path(dict);
+        if !path.exists() {
+            return None;
+        }
+        let compiled = match CompiledDict::load(path.to_string_lossy().to_string().as_str()) {
+            Ok(compiled) => compiled,
+            Err(err) => {
+                warn!("Cannot load dictionary cache: {:?}: {}", path, err);
+                return None;
+            }
+        };
+        if compiled.fingerprint().as_deref() != Some(fingerprint) {
+            info!("Dictionary cache is outdated: {:?}", path);
+            return None;
+        }
+        Some(compiled)
+    }
+
+    pub fn put(
+        &self,
+        dict: &DictConfig,
+        fingerprint: &str,
+        result: &HashMap<String, Vec<String>>,
+    ) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.cache_path(dict);
+        // 書きかけのファイルを読んでしまわないように、別名で書いてから置き換える。
+        let tmp_path = path.with_extension("marisa.tmp");
+        CompiledDict::save_with_fingerprint(
+            result,
+            tmp_path.to_string_lossy().to_string().as_str(),
+            Some(fingerprint),
+        )?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+/// 辞書ファイルの fingerprint。パーサーのバージョン、パス、更新日時、サイズ、文字コード、内容のハッシュ値からなる。
+pub fn fingerprint(path: &Path, encoding: &'static Encoding, content: &[u8]) -> Result<String> {
+    let metadata = fs::metadata(path)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_nanos();
+    Ok(format!(
+        "{}\t{}\t{}\t{}\t{}\t{:016x}",
+        PARSER_VERSION,
+        path.to_string_lossy(),
+        mtime,
+        metadata.len(),
+        encoding.name(),
+        fnv1a(content)
+    ))
+}
+
+/// FNV-1a (64bit)
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use encoding_rs::{EUC_JP, UTF_8};
+    use tempfile::{tempdir, NamedTempFile};
+
+    use super::*;
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_cache() -> Result<()> {
+        let cache_dir = tempdir()?;
+        let cache = DictCache::new(cache_dir.path().to_path_buf());
+
+        let mut file = NamedTempFile::new()?;
+        file.write_all("かんじ /漢字/\n".as_bytes())?;
+        let path = file.path().to_path_buf();
+        let config = DictConfig {
+            path: path.to_string_lossy().to_string(),
+            encoding: None,
+            dict_type: "skk".to_string(),
+            ..Default::default()
+        };
+        let dict = HashMap::from([("かんじ".to_string(), vec!["漢字".to_string()])]);
+
+        let content = fs::read(&path)?;
+        let fp = fingerprint(&path, UTF_8, &content)?;
+        assert!(cache.get(&config, &fp).is_none());
+
+        cache.put(&config, &fp, &dict)?;
+        assert_eq!(cache.get(&config, &fp).map(|it| it.to_dict()), Some(dict));
+
+        // 文字コードや内容が変わったら、キャッシュは使わない。
+        assert!(cache
+            .get(&config, &fingerprint(&path, EUC_JP, &content)?)
+            .is_none());
+        file.write_all("かん /缶/\n".as_bytes())?;
+        let content = fs::read(&path)?;
+        assert!(cache
+            .get(&config, &fingerprint(&path, UTF_8, &content)?)
+            .is_none());
+        Ok(())
+    }
+}
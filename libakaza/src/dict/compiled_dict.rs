@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use marisa_sys::{Keyset, Marisa};
+
+use crate::dict::skk::read::is_numeric_template_yomi;
+
+/*
+   コンパイル済みの辞書。テキストの SKK 辞書をパースしなくてすむように、
+   読みから候補のリストを引けるようにした marisa-trie に保存する。
+
+   {yomi}        # in utf-8
+   0xff          # marker
+   {surface}     # in utf-8。"表層;アノテーション" の形のまま
+   0xfe          # 候補の区切り
+   {surface}
+   ...
+
+   0xff, 0xfe は UTF-8 の文字列には現れないので、区切り文字として使える。
+   0xff ではじまるキーは、フォーマットのバージョンなどのメタデータに使う。
+   "#ねん" のような数値変換のエントリーは、通常の読みで引けないように
+   0xff "numeric-template" 0xff {yomi} 0xff ... のキーに入れておく。
+*/
+
+const YOMI_SEPARATOR: u8 = b'\xff';
+const SURFACE_SEPARATOR: u8 = b'\xfe';
+// 数値変換のエントリーを分けて持つようにしたので 3 にした。
+const VERSION_KEY: &[u8] = b"\xffakaza-compiled-dict\xff3";
+const FINGERPRINT_KEY: &[u8] = b"\xfffingerprint\xff";
+const NUMERIC_TEMPLATE_KEY: &[u8] = b"\xffnumeric-template\xff";
+
+pub struct CompiledDict {
+    marisa: Marisa,
+}
+
+impl CompiledDict {
+    /// 辞書をコンパイルして、ファイルに保存する。
+    pub fn save(dict: &HashMap<String, Vec<String>>, fname: &str) -> Result<()> {
+        Self::save_with_fingerprint(dict, fname, None)
+    }
+
+    /// fingerprint には、元になった辞書ファイルを識別する文字列を指定する。
+    /// キャッシュとして使うときに、元の辞書が変更されていないかの確認に使う。
+    pub fn save_with_fingerprint(
+        dict: &HashMap<String, Vec<String>>,
+        fname: &str,
+        fingerprint: Option<&str>,
+    ) -> Result<()> {
+        let mut keyset = Keyset::default();
+        keyset.push_back(VERSION_KEY);
+        if let Some(fingerprint) = fingerprint {
+            keyset.push_back(
+                [FINGERPRINT_KEY, fingerprint.as_bytes()]
+                    .concat()
+                    .as_slice(),
+            );
+        }
+        for (yomi, surfaces) in dict {
+            if yomi.is_empty() || surfaces.is_empty() {
+                continue;
+            }
+            let mut key = if is_numeric_template_yomi(yomi) {
+                NUMERIC_TEMPLATE_KEY.to_vec()
+            } else {
+                Vec::new()
+            };
+            key.extend_from_slice(yomi.as_bytes());
+            key.push(YOMI_SEPARATOR);
+            for (i, surface) in surfaces.iter().enumerate() {
+                if i > 0 {
+                    key.push(SURFACE_SEPARATOR);
+                }
+                key.extend_from_slice(surface.as_bytes());
+            }
+            keyset.push_back(key.as_slice());
+        }
+
+        let mut marisa = Marisa::default();
+        marisa.build(&keyset);
+        marisa.save(fname)?;
+        info!(
+            "Saved compiled dictionary: {} entries to {}",
+            dict.len(),
+            fname
+        );
+        Ok(())
+    }
+
+    /// コンパイル済みの辞書を mmap して読み込む。
+    pub fn load(fname: &str) -> Result<CompiledDict> {
+        let mut marisa = Marisa::default();
+        marisa.mmap(fname)?;
+
+        let mut has_version = false;
+        marisa.predictive_search(VERSION_KEY, |key, _| {
+            has_version = key == VERSION_KEY;
+            false
+        });
+        if !has_version {
+            bail!("Unsupported compiled dictionary format: {}", fname);
+        }
+        Ok(CompiledDict { marisa })
+    }
+
+    /// 保存時に指定した fingerprint。
+    pub fn fingerprint(&self) -> Option<String> {
+        let mut result: Option<String> = None;
+        self.marisa.predictive_search(FINGERPRINT_KEY, |key, _| {
+            result = Some(String::from_utf8_lossy(&key[FINGERPRINT_KEY.len()..]).to_string());
+            false
+        });
+        result
+    }
+
+    /// 読みに対応する候補のリストを返す。数値変換のエントリーは numeric_templates で引く。
+    pub fn get(&self, yomi: &str) -> Option<Vec<String>> {
+        let query = [yomi.as_bytes(), &[YOMI_SEPARATOR]].concat();
+        let mut result: Option<Vec<String>> = None;
+        self.marisa.predictive_search(query.as_slice(), |key, _| {
+            result = Some(Self::parse_surfaces(&key[query.len()..]));
+            false
+        });
+        result
+    }
+
+    /// query の先頭部分になっている読みを列挙する。
+    /// 分節に使うので、"#ねん" のような数値変換のエントリーの読みも含める。
+    pub fn common_prefix_yomis(&self, query: &str) -> Vec<String> {
+        query
+            .char_indices()
+            .map(|(i, c)| &query[..i + c.len_utf8()])
+            .filter(|yomi| {
+                if is_numeric_template_yomi(yomi) {
+                    self.contains_with_prefix(NUMERIC_TEMPLATE_KEY, yomi)
+                } else {
+                    self.contains_with_prefix(&[], yomi)
+                }
+            })
+            .map(|yomi| yomi.to_string())
+            .collect()
+    }
+
+    /// query で始まる読みを列挙する。
+    pub fn predictive_yomis(&self, query: &str) -> Vec<String> {
+        let mut result: Vec<String> = Vec::new();
+        self.marisa.predictive_search(query.as_bytes(), |key, _| {
+            if let Some(idx) = key.iter().position(|b| *b == YOMI_SEPARATOR) {
+                if idx > 0 {
+                    result.push(String::from_utf8_lossy(&key[0..idx]).to_string());
+                }
+            }
+            true
+        });
+        result
+    }
+
+    /// 数値変換のエントリー。"#ねん" → ["#1年", "#3年"] のような読みと表記の組。
+    pub fn numeric_templates(&self) -> HashMap<String, Vec<String>> {
+        let mut dict: HashMap<String, Vec<String>> = HashMap::new();
+        self.marisa
+            .predictive_search(NUMERIC_TEMPLATE_KEY, |key, _| {
+                let key = &key[NUMERIC_TEMPLATE_KEY.len()..];
+                if let Some(idx) = key.iter().position(|b| *b == YOMI_SEPARATOR) {
+                    let yomi = String::from_utf8_lossy(&key[0..idx]).to_string();
+                    dict.insert(yomi, Self::parse_surfaces(&key[idx + 1..]));
+                }
+                true
+            });
+        dict
+    }
+
+    fn contains_with_prefix(&self, prefix: &[u8], yomi: &str) -> bool {
+        let query = [prefix, yomi.as_bytes(), &[YOMI_SEPARATOR]].concat();
+        let mut found = false;
+        self.marisa.predictive_search(query.as_slice(), |_, _| {
+            found = true;
+            false
+        });
+        found
+    }
+
+    /// 全エントリーを、テキストの辞書を読んだときと同じ形の HashMap にする。
+    pub fn to_dict(&self) -> HashMap<String, Vec<String>> {
+        let mut dict: HashMap<String, Vec<String>> = HashMap::new();
+        self.marisa.predictive_search(b"", |key, _| {
+            if key.first() == Some(&YOMI_SEPARATOR) {
+                // メタデータ
+                return true;
+            }
+            if let Some(idx) = key.iter().position(|b| *b == YOMI_SEPARATOR) {
+                let yomi = String::from_utf8_lossy(&key[0..idx]).to_string();
+                dict.insert(yomi, Self::parse_surfaces(&key[idx + 1..]));
+            }
+            true
+        });
+        dict.extend(self.numeric_templates());
+        dict
+    }
+
+    fn parse_surfaces(bytes: &[u8]) -> Vec<String> {
+        bytes
+            .split(|b| *b == SURFACE_SEPARATOR)
+            .map(|surface| String::from_utf8_lossy(surface).to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_compiled_dict() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let fname = tmpfile.path().to_str().unwrap();
+
+        let dict = HashMap::from([
+            (
+                "かんじ".to_string(),
+                vec!["漢字".to_string(), "感じ;feeling".to_string()],
+            ),
+            ("かん".to_string(), vec!["缶".to_string()]),
+            ("#ねん".to_string(), vec!["#1年".to_string()]),
+            ("から".to_string(), vec![]),
+        ]);
+        CompiledDict::save(&dict, fname)?;
+
+        let compiled = CompiledDict::load(fname)?;
+        assert_eq!(compiled.fingerprint(), None);
+        assert_eq!(
+            compiled.get("かんじ"),
+            Some(vec!["漢字".to_string(), "感じ;feeling".to_string()])
+        );
+        assert_eq!(compiled.get("かん"), Some(vec!["缶".to_string()]));
+        assert_eq!(compiled.get("か"), None);
+        assert_eq!(compiled.get("から"), None);
+        // 数値変換のエントリーは、通常の読みとしては引けない。
+        assert_eq!(compiled.get("#ねん"), None);
+        assert_eq!(
+            compiled.numeric_templates(),
+            HashMap::from([("#ねん".to_string(), vec!["#1年".to_string()])])
+        );
+        assert_eq!(
+            compiled.common_prefix_yomis("#ねんど"),
+            vec!["#ねん".to_string()]
+        );
+        assert_eq!(
+            compiled.common_prefix_yomis("かんじる"),
+            vec!["かん".to_string(), "かんじ".to_string()]
+        );
+        let mut predicted = compiled.predictive_yomis("かん");
+        predicted.sort();
+        assert_eq!(predicted, vec!["かん".to_string(), "かんじ".to_string()]);
+
+        let mut expected = dict;
+        expected.remove("から");
+        assert_eq!(compiled.to_dict(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let fname = tmpfile.path().to_str().unwrap();
+
+        let dict = HashMap::from([("かん".to_string(), vec!["缶".to_string()])]);
+        CompiledDict::save_with_fingerprint(&dict, fname, Some("SKK-JISYO.L\t1234"))?;
+
+        let compiled = CompiledDict::load(fname)?;
+        assert_eq!(
+            compiled.fingerprint(),
+            Some("SKK-JISYO.L\t1234".to_string())
+        );
+        assert_eq!(compiled.to_dict(), dict);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_format() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let fname = tmpfile.path().to_str().unwrap();
+
+        let mut keyset = Keyset::default();
+        keyset.push_back(
+            ["かんじ".as_bytes(), b"\xff", "漢字".as_bytes()]
+                .concat()
+                .as_slice(),
+        );
+        let mut marisa = Marisa::default();
+        marisa.build(&keyset);
+        marisa.save(fname)?;
+
+        assert!(CompiledDict::load(fname).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::conjugation::{conjugate, ConjugationClass};
+use crate::dict::skk::lisp::{eval_lisp, is_lisp};
+use crate::dict::skk::okuri::{is_okuri_ari_yomi, split_okuri_block_key};
+use crate::dict::skk::read::{join_annotation, split_annotation, unescape_annotation};
+
+/*
+   ほかの IME のユーザー辞書をテキストで書き出したもの。
+
+   Mozc(Google 日本語入力) のユーザー辞書:
+     # コメント
+     よみ<TAB>単語<TAB>品詞<TAB>コメント
+
+   MS-IME, ATOK の単語一覧:
+     !Microsoft IME Dictionary Tool
+     よみ<TAB>単語<TAB>品詞
+
+   どちらも、コメントは SKK 辞書のアノテーションとして扱う。
+   品詞が動詞や形容詞なら、読み込むときに活用した形("ググった", "ググらない" など)も登録する。
+   MS-IME の書き出す UTF-16 のファイルは、BOM があれば文字コードの設定によらず読める。
+*/
+
+/// 変換候補にしない、Mozc の品詞。
+const MOZC_SKIPPED_POS: [&str; 2] = ["抑制単語", "サジェストのみ"];
+/// 書き出すときの品詞。
+const DEFAULT_POS: &str = "名詞";
+
+/// Mozc のユーザー辞書をパースする。
+pub fn parse_mozc_dict(src: &str) -> Result<HashMap<String, Vec<String>>> {
+    Ok(parse_tsv_dict(src, '#', |pos| {
+        !MOZC_SKIPPED_POS.contains(&pos)
+    }))
+}
+
+/// MS-IME や ATOK の単語一覧をパースする。
+pub fn parse_msime_dict(src: &str) -> Result<HashMap<String, Vec<String>>> {
+    Ok(parse_tsv_dict(src, '!', |_| true))
+}
+
+fn parse_tsv_dict(
+    src: &str,
+    comment_prefix: char,
+    accept_pos: impl Fn(&str) -> bool,
+) -> HashMap<String, Vec<String>> {
+    let mut dict: HashMap<String, Vec<String>> = HashMap::new();
+    for line in src.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim_end_matches('\r');
+        if line.is_empty() || line.starts_with(comment_prefix) {
+            continue;
+        }
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() < 2 || columns[0].is_empty() || columns[1].is_empty() {
+            info!("Invalid line: {}", line);
+            continue;
+        }
+        let pos = columns.get(2).unwrap_or(&"");
+        if !accept_pos(pos) {
+            continue;
+        }
+        insert_word(
+            &mut dict,
+            columns[0],
+            columns[1],
+            pos,
+            columns.get(3).copied(),
+        );
+    }
+    dict
+}
+
+/// 単語を辞書に追加する。品詞が動詞や形容詞なら、活用した形も追加する。
+pub(crate) fn insert_word(
+    dict: &mut HashMap<String, Vec<String>>,
+    yomi: &str,
+    surface: &str,
+    pos: &str,
+    annotation: Option<&str>,
+) {
+    let words = match ConjugationClass::from_pos(pos) {
+        Some(class) => conjugate(yomi, surface, class),
+        None => vec![(yomi.to_string(), surface.to_string())],
+    };
+    for (yomi, surface) in words {
+        let surfaces = dict.entry(yomi).or_default();
+        if !surfaces.iter().any(|it| split_annotation(it).0 == surface) {
+            surfaces.push(join_annotation(&surface, annotation));
+        }
+    }
+}
+
+/// Mozc のユーザー辞書の形式で書き出す。
+pub fn write_mozc_dict(ofname: &str, dict: &HashMap<String, Vec<String>>) -> Result<()> {
+    info!("Writing {}", ofname);
+    let mut wfp = File::create(ofname)?;
+    for (yomi, surface, annotation) in tsv_entries(dict) {
+        wfp.write_fmt(format_args!(
+            "{}\t{}\t{}\t{}\n",
+            yomi,
+            surface,
+            DEFAULT_POS,
+            annotation.unwrap_or_default()
+        ))?;
+    }
+    Ok(())
+}
+
+/// MS-IME の単語一覧の形式で書き出す。MS-IME が読めるように、BOM つきの UTF-16LE にする。
+pub fn write_msime_dict(ofname: &str, dict: &HashMap<String, Vec<String>>) -> Result<()> {
+    info!("Writing {}", ofname);
+    let mut buf = String::from("\u{feff}!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\n");
+    for (yomi, surface, _) in tsv_entries(dict) {
+        buf += format!("{}\t{}\t{}\r\n", yomi, surface, DEFAULT_POS).as_str();
+    }
+    let bytes: Vec<u8> = buf.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    File::create(ofname)?.write_all(&bytes)?;
+    Ok(())
+}
+
+/// 書き出すエントリーを、読みの順にならべて返す。
+/// (concat ...) は評価した結果を書き出す。現在時刻などに依存する式は書き出せないので省く。
+fn tsv_entries(dict: &HashMap<String, Vec<String>>) -> Vec<(String, String, Option<String>)> {
+    let mut yomis: Vec<&String> = dict.keys().collect();
+    yomis.sort();
+
+    let mut entries: Vec<(String, String, Option<String>)> = Vec::new();
+    for yomi in yomis {
+        // 送りありエントリーは、ほかの IME の単語としては書き出せない。
+        if is_okuri_ari_yomi(yomi) || split_okuri_block_key(yomi).is_some() {
+            continue;
+        }
+        for entry in &dict[yomi] {
+            let (surface, annotation) = split_annotation(entry);
+            let surface = if is_lisp(surface) {
+                match eval_lisp(surface, None) {
+                    Ok(surface) => surface,
+                    Err(err) => {
+                        warn!("Skip dynamic entry: {}: {}", surface, err);
+                        continue;
+                    }
+                }
+            } else {
+                surface.to_string()
+            };
+            // 区切り文字はほかの IME で読めないので、空白にしておく。
+            let sanitize = |s: &str| s.replace(['\t', '\r', '\n'], " ");
+            entries.push((
+                sanitize(yomi),
+                sanitize(&surface),
+                annotation.map(|it| sanitize(&unescape_annotation(it))),
+            ));
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use encoding_rs::UTF_16LE;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_mozc_dict() -> Result<()> {
+        let src = "# Mozc user dictionary\n\
+            ぐぐる\tググる\t動詞ラ行五段\t\n\
+            とくひろ\t徳宏\t人名\t友人\n\
+            とくひろ\t徳宏\t人名\t\n\
+            かおもじ\t(笑)\t顔文字\t\n\
+            ばか\tバカ\t抑制単語\t\n\
+            invalid line\n";
+        let mut dict = parse_mozc_dict(src)?;
+        // 動詞は活用した形も登録する。
+        assert_eq!(dict.get("ぐぐる"), Some(&vec!["ググる".to_string()]));
+        assert_eq!(dict.get("ぐぐった"), Some(&vec!["ググった".to_string()]));
+        assert_eq!(
+            dict.get("ぐぐらない"),
+            Some(&vec!["ググらない".to_string()])
+        );
+        dict.retain(|yomi, _| !yomi.starts_with("ぐぐ"));
+        assert_eq!(
+            dict,
+            HashMap::from([
+                ("とくひろ".to_string(), vec!["徳宏;友人".to_string()]),
+                (
+                    "かおもじ".to_string(),
+                    vec![r#"(concat "\050笑\051")"#.to_string()]
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_msime_dict() -> Result<()> {
+        let src = "\u{feff}!Microsoft IME Dictionary Tool\r\n\
+            !Format:WORDLIST\r\n\
+            \r\n\
+            あかざ\t赤座\t固有名詞\r\n\
+            あかざ\tアカザ\t名詞\r\n";
+        let dict = parse_msime_dict(src)?;
+        assert_eq!(
+            dict,
+            HashMap::from([(
+                "あかざ".to_string(),
+                vec!["赤座".to_string(), "アカザ".to_string()]
+            )])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_mozc_dict() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let fname = tmpfile.path().to_str().unwrap();
+        let dict = HashMap::from([
+            (
+                "とくひろ".to_string(),
+                vec!["徳宏;友人".to_string(), "徳大".to_string()],
+            ),
+            (
+                "かおもじ".to_string(),
+                vec![
+                    r#"(concat "\050笑\051")"#.to_string(),
+                    "(current-time-string)".to_string(),
+                ],
+            ),
+        ]);
+        write_mozc_dict(fname, &dict)?;
+        let written = fs::read_to_string(fname)?;
+        assert_eq!(
+            written,
+            "かおもじ\t(笑)\t名詞\t\n\
+            とくひろ\t徳宏\t名詞\t友人\n\
+            とくひろ\t徳大\t名詞\t\n"
+        );
+        assert_eq!(
+            parse_mozc_dict(&written)?,
+            HashMap::from([
+                (
+                    "とくひろ".to_string(),
+                    vec!["徳宏;友人".to_string(), "徳大".to_string()]
+                ),
+                (
+                    "かおもじ".to_string(),
+                    vec![r#"(concat "\050笑\051")"#.to_string()]
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_msime_dict() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let fname = tmpfile.path().to_str().unwrap();
+        let dict = HashMap::from([("あかざ".to_string(), vec!["赤座;人名".to_string()])]);
+        write_msime_dict(fname, &dict)?;
+
+        let bytes = fs::read(fname)?;
+        assert_eq!(&bytes[0..2], &[0xff, 0xfe]);
+        let (decoded, _, _) = UTF_16LE.decode(&bytes);
+        assert_eq!(
+            decoded,
+            "!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\nあかざ\t赤座\t名詞\r\n"
+        );
+        assert_eq!(
+            parse_msime_dict(&decoded)?,
+            HashMap::from([("あかざ".to_string(), vec!["赤座".to_string()])])
+        );
+        Ok(())
+    }
+}
@@ -1,23 +1,53 @@
 use std::collections::HashMap;
+use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::SystemTime;
 
-use anyhow::bail;
 use anyhow::Result;
-use encoding_rs::{EUC_JP, UTF_8};
-use log::{error, info};
+use anyhow::{bail, Context};
+use encoding_rs::{EUC_JP, SHIFT_JIS, UTF_16LE, UTF_8};
+use log::{error, info, warn};
 
 use crate::config::DictConfig;
+use crate::dict::cache::{fingerprint, DictCache};
+use crate::dict::ime_dict::{parse_mozc_dict, parse_msime_dict};
 use crate::dict::merge_dict::merge_dict;
-use crate::dict::skk::read::read_skkdict;
+use crate::dict::skk::read::parse_skkdict;
+use crate::dict::source::{DictData, DictSource, SourcedDict};
 
-pub fn load_dicts(dict_configs: &Vec<DictConfig>) -> Result<HashMap<String, Vec<String>>> {
-    let mut dicts: Vec<HashMap<String, Vec<String>>> = Vec::new();
+pub fn load_dicts(dict_configs: &[DictConfig]) -> Result<HashMap<String, Vec<String>>> {
+    let dicts = load_sourced_dicts(dict_configs)
+        .into_iter()
+        .map(|it| it.dict.into_map())
+        .collect();
+    Ok(merge_dict(dicts))
+}
+
+/// 設定ファイルに書かれた順に辞書を読む。どの辞書から引いた候補かがわかるように、出どころをつけておく。
+/// キャッシュがあれば、コンパイル済みの辞書をそのまま引く。
+pub fn load_sourced_dicts(dict_configs: &[DictConfig]) -> Vec<SourcedDict> {
+    // テストでは、ユーザーのキャッシュディレクトリに書き込まない。
+    let cache = if cfg!(test) {
+        None
+    } else {
+        match DictCache::from_xdg() {
+            Ok(cache) => Some(cache),
+            Err(err) => {
+                warn!("Dictionary cache is disabled: {}", err);
+                None
+            }
+        }
+    };
+
+    let mut dicts: Vec<SourcedDict> = Vec::new();
     for dict_config in dict_configs {
-        match load_dict(dict_config) {
+        match load_dict_data(dict_config, cache.as_ref()) {
             Ok(dict) => {
-                // TODO 辞書をうまく使う
-                dicts.push(dict);
+                dicts.push(SourcedDict {
+                    source: Some(Arc::new(DictSource::from_config(dict_config))),
+                    dict,
+                });
             }
             Err(err) => {
                 error!("Cannot load dictionary: {:?}. {}", dict_config, err);
@@ -26,11 +56,23 @@ pub fn load_dicts(dict_configs: &Vec<DictConfig>) -> Result<HashMap<String, Vec<
             }
         }
     }
-    Ok(merge_dict(dicts))
+    dicts
 }
 
 pub fn load_dict(dict: &DictConfig) -> Result<HashMap<String, Vec<String>>> {
-    // TODO キャッシュ機構を入れる。
+    load_dict_with_cache(dict, None)
+}
+
+/// cache が指定されていれば、パースした結果をキャッシュする。
+pub fn load_dict_with_cache(
+    dict: &DictConfig,
+    cache: Option<&DictCache>,
+) -> Result<HashMap<String, Vec<String>>> {
+    Ok(load_dict_data(dict, cache)?.into_map())
+}
+
+/// キャッシュがあればコンパイル済みの辞書を、なければパースした辞書を返す。
+fn load_dict_data(dict: &DictConfig, cache: Option<&DictCache>) -> Result<DictData> {
     info!(
         "Loading dictionary: {} {:?} {}",
         dict.path, dict.encoding, dict.dict_type
@@ -39,6 +81,8 @@ pub fn load_dict(dict: &DictConfig) -> Result<HashMap<String, Vec<String>>> {
         Some(encoding) => match encoding.to_ascii_lowercase().as_str() {
             "euc-jp" | "euc_jp" => EUC_JP,
             "utf-8" => UTF_8,
+            "utf-16le" | "utf-16" => UTF_16LE,
+            "shift_jis" | "shift-jis" | "sjis" | "cp932" => SHIFT_JIS,
             _ => {
                 bail!(
                     "Unknown enconding in configuration: {} for {}",
@@ -50,25 +94,108 @@ pub fn load_dict(dict: &DictConfig) -> Result<HashMap<String, Vec<String>>> {
         None => UTF_8,
     };
 
-    match dict.dict_type.as_str() {
-        "skk" => {
-            let t1 = SystemTime::now();
-            let merged = read_skkdict(Path::new(dict.path.as_str()), encoding)?;
-            let t2 = SystemTime::now();
+    let t1 = SystemTime::now();
+    let path = Path::new(dict.path.as_str());
+    let buf = fs::read(path).with_context(|| dict.path.clone())?;
+    let fingerprint = match cache {
+        Some(_) => Some(fingerprint(path, encoding, &buf)?),
+        None => None,
+    };
+    if let (Some(cache), Some(fingerprint)) = (cache, &fingerprint) {
+        if let Some(cached) = cache.get(dict, fingerprint) {
             info!(
-                "Loaded {}: {} entries in {} msec",
+                "Loaded {} from cache in {} msec",
                 dict.path,
-                merged.len(),
-                t2.duration_since(t1).unwrap().as_millis()
+                SystemTime::now().duration_since(t1)?.as_millis()
             );
-            Ok(merged)
+            return Ok(DictData::Compiled(Arc::new(cached)));
         }
-        _ => {
-            bail!(
-                "Unknown dictionary type: {} for {}",
-                dict.dict_type,
-                dict.path
-            );
+    }
+
+    // BOM があれば、encoding よりも BOM の文字コードが優先される。
+    let (decoded, _, _) = encoding.decode(buf.as_slice());
+    let merged = parse_dict(&dict.dict_type, &decoded)
+        .with_context(|| format!("{} for {}", dict.dict_type, dict.path))?;
+    let t2 = SystemTime::now();
+    info!(
+        "Loaded {}: {} entries in {} msec",
+        dict.path,
+        merged.len(),
+        t2.duration_since(t1).unwrap().as_millis()
+    );
+    if let (Some(cache), Some(fingerprint)) = (cache, &fingerprint) {
+        if let Err(err) = cache.put(dict, fingerprint, &merged) {
+            warn!("Cannot save dictionary cache for {}: {}", dict.path, err);
         }
     }
+    Ok(DictData::Parsed(merged))
+}
+
+fn parse_dict(dict_type: &str, src: &str) -> Result<HashMap<String, Vec<String>>> {
+    match dict_type {
+        "skk" => parse_skkdict(src),
+        "mozc" => parse_mozc_dict(src),
+        "msime" | "atok" => parse_msime_dict(src),
+        _ => bail!("Unknown dictionary type: {}", dict_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::{tempdir, NamedTempFile};
+
+    use super::*;
+
+    #[test]
+    fn test_load_dict_with_cache() -> Result<()> {
+        let cache_dir = tempdir()?;
+        let cache = DictCache::new(cache_dir.path().to_path_buf());
+
+        let mut file = NamedTempFile::new()?;
+        file.write_all(";; okuri-nasi entries.\nかんじ /漢字/感じ/\n".as_bytes())?;
+        let config = DictConfig {
+            path: file.path().to_string_lossy().to_string(),
+            encoding: None,
+            dict_type: "skk".to_string(),
+            ..Default::default()
+        };
+
+        let parsed = load_dict_with_cache(&config, Some(&cache))?;
+        assert_eq!(
+            parsed.get("かんじ"),
+            Some(&vec!["漢字".to_string(), "感じ".to_string()])
+        );
+        assert_eq!(fs::read_dir(cache_dir.path())?.count(), 1);
+        // 2回目はキャッシュから読む。
+        assert_eq!(load_dict_with_cache(&config, Some(&cache))?, parsed);
+
+        // 辞書を書き換えたら、パースしなおす。
+        file.write_all("かん /缶/\n".as_bytes())?;
+        let reparsed = load_dict_with_cache(&config, Some(&cache))?;
+        assert_eq!(reparsed.get("かん"), Some(&vec!["缶".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_mozc_dict() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all("ぐぐる\tググる\t動詞ラ行五段\t\n".as_bytes())?;
+        let config = DictConfig {
+            path: file.path().to_string_lossy().to_string(),
+            dict_type: "mozc".to_string(),
+            ..Default::default()
+        };
+        let dict = load_dict(&config)?;
+        assert_eq!(dict.get("ぐぐる"), Some(&vec!["ググる".to_string()]));
+        assert_eq!(dict.get("ぐぐって"), Some(&vec!["ググって".to_string()]));
+
+        let config = DictConfig {
+            dict_type: "unknown".to_string(),
+            ..config
+        };
+        assert!(load_dict(&config).is_err());
+        Ok(())
+    }
 }
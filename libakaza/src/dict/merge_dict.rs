@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+use crate::dict::skk::read::split_annotation;
+
+/// 辞書をまとめる。アノテーションだけが異なる候補は、先に出てきたものを残す。
 pub fn merge_dict(dicts: Vec<HashMap<String, Vec<String>>>) -> HashMap<String, Vec<String>> {
     let mut result: HashMap<String, Vec<String>> = HashMap::new();
 
@@ -7,7 +10,8 @@ pub fn merge_dict(dicts: Vec<HashMap<String, Vec<String>>>) -> HashMap<String, V
         for (yomi, kanjis) in dict {
             let target = result.entry(yomi).or_default();
             for kanji in kanjis {
-                if !target.contains(&kanji) {
+                let surface = split_annotation(&kanji).0;
+                if !target.iter().any(|it| split_annotation(it).0 == surface) {
                     target.push(kanji);
                 }
             }
@@ -53,4 +57,19 @@ mod tests {
             HashMap::from([("ご".to_string(), vec!["語".to_string(), "碁".to_string()]),])
         );
     }
+
+    #[test]
+    fn test_merge_dict_annotation() {
+        let got = merge_dict(vec![
+            HashMap::from([("ご".to_string(), vec!["語;言葉".to_string()])]),
+            HashMap::from([("ご".to_string(), vec!["語".to_string(), "碁".to_string()])]),
+        ]);
+        assert_eq!(
+            got,
+            HashMap::from([(
+                "ご".to_string(),
+                vec!["語;言葉".to_string(), "碁".to_string()]
+            )])
+        );
+    }
 }
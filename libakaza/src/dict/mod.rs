@@ -1,3 +1,7 @@
+pub mod cache;
+pub mod compiled_dict;
+pub mod ime_dict;
 pub mod loader;
 pub mod merge_dict;
 pub mod skk;
+pub mod source;
@@ -0,0 +1,245 @@
+use anyhow::{bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Local};
+
+use crate::wareki::{format_wareki_year, to_wareki};
+
+/*
+   SKK 辞書に含まれる Lisp 式の評価器。
+
+   SKK 辞書には (concat "...") のような Emacs Lisp の式が書かれていることがある。
+   任意の式を評価するのは危険なので、よく使われる以下の関数だけに対応する。
+
+   - (concat "a" "b")            文字列の連結。"\057" のような8進数のエスケープを含む
+   - (current-time-string)       "Mon Jan 16 15:14:16 2023"
+   - (skk-current-date)          "令和5年1月16日(月)"
+   - (format-time-string "%Y")   strftime 形式の日付
+*/
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Str(String),
+    Symbol(String),
+    List(Vec<Expr>),
+}
+
+/// Lisp 式として書かれた候補かどうか。
+pub fn is_lisp(s: &str) -> bool {
+    s.starts_with('(') && s.ends_with(')')
+}
+
+/**
+ * Lisp 式を評価する。
+ * now が None の場合は、現在時刻に依存する関数を評価できないものとしてエラーにする。
+ * 辞書の読み込み時に、(concat ...) のような定数の式だけを先に評価しておくために使う。
+ */
+pub fn eval_lisp(src: &str, now: Option<DateTime<Local>>) -> Result<String> {
+    let mut chars = src.chars().peekable();
+    let expr = parse(&mut chars)?;
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        bail!("Trailing characters in lisp expression: {}", src);
+    }
+    eval(&expr, now)
+}
+
+/**
+ * 文字列を、評価するとその文字列になる (concat "...") の式にする。
+ * 「/」や「;」のように SKK 辞書の書式と衝突する文字は、8進数でエスケープする。
+ */
+pub fn to_concat(s: &str) -> String {
+    let mut buf = String::from("(concat \"");
+    for c in s.chars() {
+        match c {
+            '/' | ';' | '"' | '\\' | '(' | ')' => buf += format!("\\{:03o}", c as u32).as_str(),
+            _ => buf.push(c),
+        }
+    }
+    buf += "\")";
+    buf
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Expr> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some('(') => {
+            let mut list: Vec<Expr> = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                match chars.peek() {
+                    Some(')') => {
+                        chars.next();
+                        return Ok(Expr::List(list));
+                    }
+                    Some(_) => list.push(parse(chars)?),
+                    None => bail!("Unclosed list in lisp expression"),
+                }
+            }
+        }
+        Some('"') => {
+            let mut buf = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Expr::Str(buf)),
+                    Some('\\') => buf.push(parse_escape(chars)?),
+                    Some(c) => buf.push(c),
+                    None => bail!("Unclosed string in lisp expression"),
+                }
+            }
+        }
+        Some(c) if c != ')' => {
+            let mut buf = c.to_string();
+            while let Some(c) = chars.peek() {
+                if c.is_whitespace() || *c == '(' || *c == ')' || *c == '"' {
+                    break;
+                }
+                buf.push(*c);
+                chars.next();
+            }
+            Ok(Expr::Symbol(buf))
+        }
+        _ => bail!("Unexpected end of lisp expression"),
+    }
+}
+
+/// 文字列中の \ のあとの部分。"\057" のような8進数は、その文字コードの文字にする。
+fn parse_escape(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<char> {
+    match chars.next() {
+        Some('n') => Ok('\n'),
+        Some('t') => Ok('\t'),
+        Some(c) if c.is_digit(8) => {
+            let mut code = c.to_digit(8).unwrap();
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(8)) {
+                    Some(d) => {
+                        code = code * 8 + d;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            match char::from_u32(code) {
+                Some(c) => Ok(c),
+                None => bail!("Invalid octal escape: {}", code),
+            }
+        }
+        Some(c) => Ok(c),
+        None => bail!("Unexpected end of string escape"),
+    }
+}
+
+fn eval(expr: &Expr, now: Option<DateTime<Local>>) -> Result<String> {
+    let list = match expr {
+        Expr::Str(s) => return Ok(s.to_string()),
+        Expr::Symbol(s) => bail!("Unsupported symbol: {}", s),
+        Expr::List(list) => list,
+    };
+    let Some(Expr::Symbol(function)) = list.first() else {
+        bail!("Function name is missing: {:?}", expr);
+    };
+    let args = list[1..]
+        .iter()
+        .map(|arg| eval(arg, now))
+        .collect::<Result<Vec<_>>>()?;
+
+    match function.as_str() {
+        "concat" => Ok(args.concat()),
+        "current-time-string" => Ok(require_now(now, function)?
+            .format("%a %b %e %H:%M:%S %Y")
+            .to_string()),
+        "skk-current-date" => {
+            let now = require_now(now, function)?;
+            let weekday = ["月", "火", "水", "木", "金", "土", "日"]
+                [now.weekday().num_days_from_monday() as usize];
+            // SKK と同じく、和暦で表示する。
+            let year = match to_wareki(&now) {
+                Some((era, year)) => format_wareki_year(era, year),
+                None => now.year().to_string(),
+            };
+            Ok(format!(
+                "{}年{}月{}日({})",
+                year,
+                now.month(),
+                now.day(),
+                weekday
+            ))
+        }
+        "format-time-string" => {
+            let now = require_now(now, function)?;
+            let Some(format) = args.first() else {
+                bail!("format-time-string requires a format string");
+            };
+            // 不正な書式を to_string すると panic するので、先に確認しておく。
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                bail!("Invalid time format: {}", format);
+            }
+            Ok(now.format(format).to_string())
+        }
+        _ => bail!("Unsupported lisp function: {}", function),
+    }
+}
+
+fn require_now(now: Option<DateTime<Local>>, function: &str) -> Result<DateTime<Local>> {
+    match now {
+        Some(now) => Ok(now),
+        None => bail!("{} depends on the current time", function),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_concat() -> Result<()> {
+        assert_eq!(eval_lisp(r#"(concat "a" "b")"#, None)?, "ab");
+        assert_eq!(
+            eval_lisp(r#"(concat "http:\057\057example.com")"#, None)?,
+            "http://example.com"
+        );
+        assert_eq!(eval_lisp(r#"(concat "\073" "\"")"#, None)?, ";\"");
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_concat() -> Result<()> {
+        assert_eq!(to_concat("a/b;c"), r#"(concat "a\057b\073c")"#);
+        for s in ["(笑)", "a/b;c", r#"say "hi" \o/"#] {
+            assert_eq!(eval_lisp(&to_concat(s), None)?, s);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_time() -> Result<()> {
+        let now = Some(Local.with_ymd_and_hms(2023, 1, 16, 15, 14, 16).unwrap());
+        assert_eq!(
+            eval_lisp("(current-time-string)", now)?,
+            "Mon Jan 16 15:14:16 2023"
+        );
+        assert_eq!(eval_lisp("(skk-current-date)", now)?, "令和5年1月16日(月)");
+        assert_eq!(
+            eval_lisp(r#"(format-time-string (concat "%Y\057%m\057%d"))"#, now)?,
+            "2023/01/16"
+        );
+        // 定数の式として評価しようとした場合はエラー
+        assert!(eval_lisp("(current-time-string)", None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(eval_lisp(r#"(shell-command "rm -rf /")"#, None).is_err());
+        assert!(eval_lisp(r#"(concat "a""#, None).is_err());
+        assert!(eval_lisp(r#"(concat "a") "b""#, None).is_err());
+        assert!(eval_lisp(r#"(format-time-string "%Q")"#, Some(Local::now())).is_err());
+    }
+}
@@ -1,3 +1,4 @@
-pub mod ari2nasi;
+pub mod lisp;
+pub mod okuri;
 pub mod read;
 pub mod write;
@@ -0,0 +1,208 @@
+use crate::conjugation::onbin_consonants;
+use crate::dict::skk::read::split_annotation;
+use crate::dict::source::DictData;
+
+/*
+   SKK 辞書の送りありエントリー。
+
+     かk /書/描/[く/書/]/[け/書/]/
+
+   送りありエントリーは、読みの語幹と送り仮名の子音("かk")をキーにしたまま辞書に入れておき、
+   変換時に、実際に続いている送り仮名と照らしあわせて候補を作る。
+
+   "[く/書/]" のような送り仮名ごとのブロックは、"かk[く]" というキーで別に入れておく。
+   ブロックの候補は、送り仮名が一致したときに、ほかの候補より先に使う。
+*/
+
+/// 送りありエントリーのキーかどうか。"かk" のように、かなの語幹のあとに子音(または母音)が1文字つく。
+pub fn is_okuri_ari_yomi(yomi: &str) -> bool {
+    let mut chars = yomi.chars().rev();
+    matches!(
+        (chars.next(), chars.next()),
+        (Some(okuri), Some(stem)) if okuri.is_ascii_lowercase() && !stem.is_ascii()
+    )
+}
+
+/// 送り仮名ごとのブロックのキー。例: ("かk", "く") → "かk[く]"
+pub fn okuri_block_key(key: &str, okuri: &str) -> String {
+    format!("{}[{}]", key, okuri)
+}
+
+/// 送り仮名ごとのブロックのキーを、送りありエントリーのキーと送り仮名に分ける。
+pub fn split_okuri_block_key(key: &str) -> Option<(&str, &str)> {
+    let (yomi, okuri) = key.strip_suffix(']')?.split_once('[')?;
+    if is_okuri_ari_yomi(yomi) && !okuri.is_empty() {
+        Some((yomi, okuri))
+    } else {
+        None
+    }
+}
+
+/**
+ * 送り仮名の先頭のかなを入力するときの、ローマ字の先頭の文字。SKK 辞書のキーの末尾に使われる。
+ * 「じ」のように、辞書によって "j" と "z" のどちらも使われるものもある。
+ */
+pub fn okuri_letters(okuri: char) -> &'static [char] {
+    match okuri {
+        'あ' => &['a'],
+        'い' => &['i'],
+        'う' => &['u'],
+        'え' => &['e'],
+        'お' => &['o'],
+        'か' | 'き' | 'く' | 'け' | 'こ' => &['k'],
+        'が' | 'ぎ' | 'ぐ' | 'げ' | 'ご' => &['g'],
+        'さ' | 'し' | 'す' | 'せ' | 'そ' => &['s'],
+        'じ' => &['j', 'z'],
+        'ざ' | 'ず' | 'ぜ' | 'ぞ' => &['z'],
+        'た' | 'つ' | 'て' | 'と' | 'っ' => &['t'],
+        'ち' => &['t', 'c'],
+        'だ' | 'ぢ' | 'づ' | 'で' | 'ど' => &['d'],
+        'な' | 'に' | 'ぬ' | 'ね' | 'の' | 'ん' => &['n'],
+        'は' | 'ひ' | 'へ' | 'ほ' => &['h'],
+        'ふ' => &['h', 'f'],
+        'ば' | 'び' | 'ぶ' | 'べ' | 'ぼ' => &['b'],
+        'ぱ' | 'ぴ' | 'ぷ' | 'ぺ' | 'ぽ' => &['p'],
+        'ま' | 'み' | 'む' | 'め' | 'も' => &['m'],
+        'や' | 'ゆ' | 'よ' => &['y'],
+        'ら' | 'り' | 'る' | 'れ' | 'ろ' => &['r'],
+        'わ' | 'を' => &['w'],
+        _ => &[],
+    }
+}
+
+/**
+ * 読みを、語幹と送り仮名(末尾の1文字)に分けたときの、送りありエントリーのキーを列挙する。
+ * 送り仮名が五段活用の音便の形なら、その行のキーも探す。
+ *
+ * 例: "かく" → [("かk", "く")], "かい" → [("かi", "い"), ("かk", "い"), ("かg", "い")]
+ */
+pub fn okuri_ari_keys(yomi: &str) -> Vec<(String, &str)> {
+    let Some(okuri) = yomi.chars().last() else {
+        return Vec::new();
+    };
+    let stem = &yomi[..yomi.len() - okuri.len_utf8()];
+    if stem.is_empty() {
+        return Vec::new();
+    }
+    let mut letters = okuri_letters(okuri).to_vec();
+    for letter in onbin_consonants(okuri) {
+        if !letters.contains(&letter) {
+            letters.push(letter);
+        }
+    }
+    letters
+        .iter()
+        .map(|letter| (format!("{}{}", stem, letter), &yomi[stem.len()..]))
+        .collect()
+}
+
+/**
+ * 送りありエントリーから、yomi に対応する候補を作る。候補には送り仮名をつける。
+ * 送り仮名が一致するブロックの候補を先にする。アノテーションはそのまま残す。
+ *
+ * 例: "かく" と "かk /書/描/" → ["書く", "描く"]
+ */
+pub fn okuri_ari_candidates(dict: &DictData, yomi: &str) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for (key, okuri) in okuri_ari_keys(yomi) {
+        let Some(surfaces) = dict.get(&key) else {
+            continue;
+        };
+        let block = dict.get(&okuri_block_key(&key, okuri));
+        for entry in block.iter().flatten().chain(&surfaces) {
+            let (surface, annotation) = split_annotation(entry);
+            // 語幹が Lisp 式のものは、送り仮名をつけられない。
+            if surface.starts_with('(') {
+                continue;
+            }
+            let surface = format!("{}{}", surface, okuri);
+            if result.iter().any(|it| split_annotation(it).0 == surface) {
+                continue;
+            }
+            result.push(match annotation {
+                Some(annotation) => format!("{};{}", surface, annotation),
+                None => surface,
+            });
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_is_okuri_ari_yomi() {
+        assert!(is_okuri_ari_yomi("かk"));
+        assert!(is_okuri_ari_yomi("いu"));
+        assert!(!is_okuri_ari_yomi("かく"));
+        assert!(!is_okuri_ari_yomi("k"));
+        assert!(!is_okuri_ari_yomi("sars-cov"));
+        assert!(!is_okuri_ari_yomi("かk[く]"));
+    }
+
+    #[test]
+    fn test_okuri_block_key() {
+        assert_eq!(okuri_block_key("かk", "く"), "かk[く]");
+        assert_eq!(split_okuri_block_key("かk[く]"), Some(("かk", "く")));
+        assert_eq!(split_okuri_block_key("かk"), None);
+        assert_eq!(split_okuri_block_key("[かっこ]"), None);
+    }
+
+    #[test]
+    fn test_okuri_ari_keys() {
+        assert_eq!(okuri_ari_keys("かく"), vec![("かk".to_string(), "く")]);
+        assert_eq!(
+            okuri_ari_keys("かんじ"),
+            vec![("かんj".to_string(), "じ"), ("かんz".to_string(), "じ")]
+        );
+        assert_eq!(
+            okuri_ari_keys("よん"),
+            vec![
+                ("よn".to_string(), "ん"),
+                ("よb".to_string(), "ん"),
+                ("よm".to_string(), "ん")
+            ]
+        );
+        assert_eq!(okuri_ari_keys("く"), vec![]);
+        assert_eq!(okuri_ari_keys("かー"), vec![]);
+    }
+
+    #[test]
+    fn test_okuri_ari_candidates() {
+        let dict = DictData::Parsed(HashMap::from([
+            (
+                "かk".to_string(),
+                vec!["書".to_string(), "描;picture".to_string()],
+            ),
+            ("かk[け]".to_string(), vec!["掛".to_string()]),
+            ("かt".to_string(), vec!["勝".to_string(), "買".to_string()]),
+        ]));
+        assert_eq!(
+            okuri_ari_candidates(&dict, "かく"),
+            vec!["書く".to_string(), "描く;picture".to_string()]
+        );
+        assert_eq!(
+            okuri_ari_candidates(&dict, "かけ"),
+            vec![
+                "掛け".to_string(),
+                "書け".to_string(),
+                "描け;picture".to_string()
+            ]
+        );
+        assert_eq!(
+            okuri_ari_candidates(&dict, "かっ"),
+            vec!["勝っ".to_string(), "買っ".to_string()]
+        );
+        // 「書いた」の「書い」のような音便の形も候補にする。
+        assert_eq!(
+            okuri_ari_candidates(&dict, "かい"),
+            vec!["書い".to_string(), "描い;picture".to_string()]
+        );
+        // 送り仮名の行が違うものは候補にしない。
+        assert!(okuri_ari_candidates(&dict, "かさ").is_empty());
+    }
+}
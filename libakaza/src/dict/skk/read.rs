@@ -6,10 +6,10 @@ use std::path::Path;
 use anyhow::{Context, Result};
 use encoding_rs::Encoding;
 use log::info;
-use regex::Regex;
 
-use crate::dict::merge_dict::merge_dict;
-use crate::dict::skk::ari2nasi::Ari2Nasi;
+use crate::dict::skk::lisp::{eval_lisp, is_lisp, to_concat};
+use crate::dict::skk::okuri::{is_okuri_ari_yomi, okuri_block_key};
+use crate::numeric::is_supported_numeric_template;
 
 enum ParserState {
     OkuriAri,
@@ -30,21 +30,21 @@ pub fn read_skkdict(
 
 /**
  * SKK 辞書をパースします。
+ *
+ * 送りありエントリーは "かk" のようなキーのまま残す。"[く/書/]" のような送り仮名ごとのブロックは、
+ * "かk[く]" のキーに入れる。
  */
 pub fn parse_skkdict(src: &str) -> Result<HashMap<String, Vec<String>>> {
-    let mut ari: HashMap<String, Vec<String>> = HashMap::new();
-    let mut nasi: HashMap<String, Vec<String>> = HashMap::new();
-    let mut target = &mut ari;
-
-    let comment_regex = Regex::new(";.*")?;
+    let mut dict: HashMap<String, Vec<String>> = HashMap::new();
+    let mut state = ParserState::OkuriAri;
 
     for line in src.lines() {
         if line.starts_with(";;") {
             if line.contains(";; okuri-ari entries.") {
-                target = &mut ari;
+                state = ParserState::OkuriAri;
                 continue;
             } else if line.contains(";; okuri-nasi entries.") {
-                target = &mut nasi;
+                state = ParserState::OkuriNasi;
                 continue;
             } else {
                 // skip comment
@@ -60,24 +60,177 @@ pub fn parse_skkdict(src: &str) -> Result<HashMap<String, Vec<String>>> {
             info!("Invalid line: {}", line);
             continue;
         };
+        assert!(!yomi.is_empty(), "yomi must not empty: line={}", line);
 
         // example:
         // とくひろ /徳宏/徳大/徳寛/督弘/
         // 末尾の slash が抜けてる場合もあるエントリーが SKK-JISYO.L に入っていたりするので注意。
-        let surfaces: Vec<String> = surfaces
+        let tokens: Vec<&str> = surfaces
             .trim_start_matches('/')
             .trim_end_matches('/')
             .split('/')
-            .map(|s| comment_regex.replace(s, "").to_string())
-            .filter(|it| !it.is_empty())
             .collect();
-        assert!(!yomi.is_empty(), "yomi must not empty: line={}", line);
-        target.insert(yomi.to_string(), surfaces);
+
+        if matches!(state, ParserState::OkuriAri) && is_okuri_ari_yomi(yomi) {
+            let (surfaces, blocks) = split_okuri_blocks(&tokens);
+            for (okuri, surfaces) in blocks {
+                let key = okuri_block_key(yomi, okuri);
+                let block = dict.entry(key).or_default();
+                for surface in parse_surfaces(yomi, &surfaces) {
+                    if !block.contains(&surface) {
+                        block.push(surface);
+                    }
+                }
+            }
+            dict.insert(yomi.to_string(), parse_surfaces(yomi, &surfaces));
+        } else {
+            dict.insert(yomi.to_string(), parse_surfaces(yomi, &tokens));
+        }
+    }
+
+    Ok(dict)
+}
+
+/**
+ * 送りありエントリーの候補を、通常の候補と送り仮名ごとのブロックに分ける。
+ * ブロックの送り仮名は、変換時に照らしあわせる先頭の1文字だけを使う。
+ *
+ * 例: ["書", "[く", "書", "]"] → (["書"], [("く", ["書"])])
+ */
+fn split_okuri_blocks<'a>(tokens: &[&'a str]) -> (Vec<&'a str>, Vec<(&'a str, Vec<&'a str>)>) {
+    let mut surfaces: Vec<&str> = Vec::new();
+    let mut blocks: Vec<(&str, Vec<&str>)> = Vec::new();
+    let mut in_block = false;
+    for token in tokens {
+        if let Some(okuri) = token.strip_prefix('[') {
+            match okuri.chars().next() {
+                Some(c) => {
+                    blocks.push((&okuri[..c.len_utf8()], Vec::new()));
+                    in_block = true;
+                }
+                None => info!("Invalid okuri block: {:?}", tokens),
+            }
+        } else if *token == "]" {
+            in_block = false;
+        } else if in_block {
+            blocks.last_mut().unwrap().1.push(token);
+        } else {
+            surfaces.push(token);
+        }
     }
+    (surfaces, blocks)
+}
 
-    let ari2nasi = Ari2Nasi::default();
-    let ari = ari2nasi.ari2nasi(&ari)?;
-    Ok(merge_dict(vec![ari, nasi]))
+/// 「;」以降のアノテーションは、"表層;アノテーション" の形のまま残す。
+fn parse_surfaces(yomi: &str, tokens: &[&str]) -> Vec<String> {
+    let surfaces = tokens
+        .iter()
+        .map(|it| eval_static_lisp(it))
+        .filter(|it| !split_annotation(it).0.is_empty());
+    // #ねん /#1年/#3年/ のような数値変換のエントリーは、対応している表記だけを残す。
+    if is_numeric_template_yomi(yomi) {
+        surfaces
+            .filter(|it| is_supported_numeric_template(split_annotation(it).0))
+            .collect()
+    } else {
+        surfaces.collect()
+    }
+}
+
+/**
+ * SKK 辞書の候補を、表層とアノテーションに分ける。
+ * "(concat "...");アノテーション" のような Lisp 式の中の「;」では区切らない。
+ *
+ * 例: "徳宏;人名" → ("徳宏", Some("人名"))
+ */
+pub fn split_annotation(entry: &str) -> (&str, Option<&str>) {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in entry.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' if depth > 0 => in_string = true,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' if depth <= 0 => return (&entry[..i], Some(&entry[i + 1..])),
+            _ => {}
+        }
+    }
+    (entry, None)
+}
+
+/**
+ * 表層とアノテーションを、split_annotation で分けられる辞書の候補の形にする。
+ * 表層やアノテーションが辞書の書式と衝突する場合は、(concat "...") の式にしておく。
+ *
+ * 例: ("徳宏", Some("人名")) → "徳宏;人名"
+ */
+pub fn join_annotation(surface: &str, annotation: Option<&str>) -> String {
+    let escape = |s: &str| {
+        if s.contains(['/', ';']) || is_lisp(s) {
+            to_concat(s)
+        } else {
+            s.to_string()
+        }
+    };
+    let surface = escape(surface);
+    match annotation {
+        Some(annotation) if !annotation.is_empty() => {
+            format!("{};{}", surface, escape(annotation))
+        }
+        _ => surface,
+    }
+}
+
+/// join_annotation で (concat "...") の式にしたアノテーションを、もとの文字列にもどす。
+pub fn unescape_annotation(annotation: &str) -> String {
+    if is_lisp(annotation) {
+        if let Ok(evaluated) = eval_lisp(annotation, None) {
+            return evaluated;
+        }
+    }
+    annotation.to_string()
+}
+
+/// (concat "...") のような、現在時刻などに依存しない Lisp 式は、読み込み時に評価しておく。
+/// 評価した結果が「/」や「;」を含む場合は、辞書の書式と衝突するので式のまま残す。
+fn eval_static_lisp(entry: &str) -> String {
+    let (surface, annotation) = split_annotation(entry);
+    if !is_lisp(surface) {
+        return entry.to_string();
+    }
+    match eval_lisp(surface, None) {
+        Ok(evaluated) if !evaluated.contains(['/', ';']) => match annotation {
+            Some(annotation) => format!("{};{}", evaluated, annotation),
+            None => evaluated,
+        },
+        _ => entry.to_string(),
+    }
+}
+
+/// "#ねん" のような、数値変換のエントリーの読みかどうか。"#" そのものは記号のエントリー。
+pub fn is_numeric_template_yomi(yomi: &str) -> bool {
+    yomi.contains('#') && yomi.chars().any(|c| c != '#')
+}
+
+/**
+ * 辞書を、通常のエントリーと数値変換のエントリーに分ける。
+ * 数値変換のエントリーは、GraphBuilder が別の辞書として持ち、変換時に入力された数字で "#1" などを置き換えて使う。
+ */
+pub fn split_numeric_templates(
+    dict: HashMap<String, Vec<String>>,
+) -> (HashMap<String, Vec<String>>, HashMap<String, Vec<String>>) {
+    dict.into_iter()
+        .partition(|(yomi, _)| !is_numeric_template_yomi(yomi))
 }
 
 #[cfg(test)]
@@ -143,4 +296,111 @@ mod tests {
 
         Ok(())
     }
+
+    /// アノテーションは残し、定数の Lisp 式は評価しておく。
+    #[test]
+    fn annotation_and_lisp() -> anyhow::Result<()> {
+        let src = r#";; okuri-nasi entries.
+とくひろ /徳宏;人名/徳大/
+あっと /(concat "\100");at mark/
+すらっしゅ /(concat "\057")/
+いま /(current-time-string)/
+"#;
+        let dict = parse_skkdict(src)?;
+        assert_eq!(
+            *dict.get("とくひろ").unwrap(),
+            vec!["徳宏;人名".to_string(), "徳大".to_string()]
+        );
+        assert_eq!(*dict.get("あっと").unwrap(), vec!["@;at mark".to_string()]);
+        assert_eq!(
+            *dict.get("すらっしゅ").unwrap(),
+            vec![r#"(concat "\057")"#.to_string()]
+        );
+        assert_eq!(
+            *dict.get("いま").unwrap(),
+            vec!["(current-time-string)".to_string()]
+        );
+        Ok(())
+    }
+
+    /// 送りありエントリーは展開せずに、送り仮名ごとのブロックとあわせて残す。
+    #[test]
+    fn okuri_ari() -> anyhow::Result<()> {
+        let src = ";; okuri-ari entries.\n\
+            かk /書/描/[く/書/]/[け/掛/書/]/\n\
+            いu /言/\n\
+            ;; okuri-nasi entries.\n\
+            かく /格/核/\n";
+        let dict = parse_skkdict(src)?;
+        assert_eq!(
+            dict,
+            HashMap::from([
+                ("かk".to_string(), vec!["書".to_string(), "描".to_string()]),
+                ("かk[く]".to_string(), vec!["書".to_string()]),
+                (
+                    "かk[け]".to_string(),
+                    vec!["掛".to_string(), "書".to_string()]
+                ),
+                ("いu".to_string(), vec!["言".to_string()]),
+                ("かく".to_string(), vec!["格".to_string(), "核".to_string()]),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_annotation() {
+        assert_eq!(split_annotation("徳宏;人名"), ("徳宏", Some("人名")));
+        assert_eq!(split_annotation("徳宏"), ("徳宏", None));
+        assert_eq!(
+            split_annotation(r#"(concat "a;b");c"#),
+            (r#"(concat "a;b")"#, Some("c"))
+        );
+    }
+
+    #[test]
+    fn test_join_annotation() {
+        assert_eq!(join_annotation("徳宏", Some("人名")), "徳宏;人名");
+        assert_eq!(join_annotation("徳宏", Some("")), "徳宏");
+        assert_eq!(join_annotation("徳宏", None), "徳宏");
+        let joined = join_annotation("a;b", Some("c"));
+        assert_eq!(joined, r#"(concat "a\073b");c"#);
+        assert_eq!(split_annotation(&joined).1, Some("c"));
+
+        let joined = join_annotation("URL", Some("https://example.com;a"));
+        assert_eq!(joined, r#"URL;(concat "https:\057\057example.com\073a")"#);
+        let (surface, annotation) = split_annotation(&joined);
+        assert_eq!(surface, "URL");
+        assert_eq!(
+            unescape_annotation(annotation.unwrap()),
+            "https://example.com;a"
+        );
+        assert_eq!(unescape_annotation("人名"), "人名");
+    }
+
+    #[test]
+    fn numeric_template() -> anyhow::Result<()> {
+        let src = ";; okuri-nasi entries.\n\
+            # /＃/\n\
+            #ねん /#1年/#3年/#4年/\n\
+            ねん /年/\n";
+        let (dict, numeric_templates) = split_numeric_templates(parse_skkdict(src)?);
+        assert_eq!(
+            dict,
+            HashMap::from([
+                ("#".to_string(), vec!["＃".to_string()]),
+                ("ねん".to_string(), vec!["年".to_string()]),
+            ])
+        );
+        // #4 には対応していないので取り除く。
+        assert_eq!(
+            numeric_templates,
+            HashMap::from([(
+                "#ねん".to_string(),
+                vec!["#1年".to_string(), "#3年".to_string()]
+            )])
+        );
+
+        Ok(())
+    }
 }
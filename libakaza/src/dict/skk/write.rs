@@ -5,6 +5,7 @@ use std::io::Write;
 use log::info;
 
 use crate::dict::merge_dict::merge_dict;
+use crate::dict::skk::okuri::{is_okuri_ari_yomi, split_okuri_block_key};
 
 pub fn write_skk_dict(
     ofname: &str,
@@ -12,15 +13,73 @@ pub fn write_skk_dict(
 ) -> anyhow::Result<()> {
     info!("Writing {}", ofname);
     let merged_dict = merge_dict(dicts);
+
+    // 送り仮名ごとのブロックは、送りありエントリーの末尾に "[く/書/]" の形で書く。
+    let mut blocks: HashMap<&str, Vec<(&str, &Vec<String>)>> = HashMap::new();
+    for (key, kanjis) in merged_dict.iter() {
+        if let Some((yomi, okuri)) = split_okuri_block_key(key) {
+            blocks.entry(yomi).or_default().push((okuri, kanjis));
+        }
+    }
+
     {
         let mut wfp = File::create(ofname)?;
         wfp.write_all(";; okuri-ari entries.\n".as_bytes())?;
+        for (yomi, kanjis) in merged_dict.iter() {
+            if !is_okuri_ari_yomi(yomi) {
+                continue;
+            }
+            let mut kanjis = kanjis.join("/");
+            if let Some(blocks) = blocks.get_mut(yomi.as_str()) {
+                blocks.sort();
+                for (okuri, block) in blocks {
+                    kanjis += format!("/[{}/{}/]", okuri, block.join("/")).as_str();
+                }
+            }
+            wfp.write_fmt(format_args!("{} /{}/\n", yomi, kanjis))?;
+        }
         wfp.write_all(";; okuri-nasi entries.\n".as_bytes())?;
         for (yomi, kanjis) in merged_dict.iter() {
             assert!(!yomi.is_empty(), "yomi must not be empty: {:?}", kanjis);
+            if is_okuri_ari_yomi(yomi) || split_okuri_block_key(yomi).is_some() {
+                continue;
+            }
             let kanjis = kanjis.join("/");
             wfp.write_fmt(format_args!("{} /{}/\n", yomi, kanjis))?;
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::NamedTempFile;
+
+    use crate::dict::skk::read::parse_skkdict;
+
+    use super::*;
+
+    #[test]
+    fn test_write_okuri_ari() -> anyhow::Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let fname = tmpfile.path().to_str().unwrap();
+        let dict = HashMap::from([
+            ("かk".to_string(), vec!["書".to_string(), "描".to_string()]),
+            ("かk[く]".to_string(), vec!["書".to_string()]),
+            ("かく".to_string(), vec!["格".to_string()]),
+        ]);
+        write_skk_dict(fname, vec![dict.clone()])?;
+        let written = fs::read_to_string(fname)?;
+        assert_eq!(
+            written,
+            ";; okuri-ari entries.\n\
+            かk /書/描/[く/書/]/\n\
+            ;; okuri-nasi entries.\n\
+            かく /格/\n"
+        );
+        assert_eq!(parse_skkdict(&written)?, dict);
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::DictConfig;
+use crate::dict::compiled_dict::CompiledDict;
+use crate::dict::skk::read::split_numeric_templates;
+
+/// システム辞書(SKK-JISYO.akaza)の名前。
+pub const SYSTEM_DICT_NAME: &str = "system";
+
+/// IME から登録した単語のユーザー辞書の名前。
+pub const USER_DICT_NAME: &str = "user";
+
+/// 候補がどの辞書から引かれたか。
+#[derive(Debug, PartialEq)]
+pub struct DictSource {
+    /// 辞書の名前。
+    pub name: String,
+    /// 候補のうしろに辞書の名前を表示するか。
+    pub show_name: bool,
+    /// 優先度。同じ表層の候補が複数の辞書にある場合は、優先度が高い辞書のものを使う。
+    pub priority: i32,
+    /// 辞書から引いた単語のコストに足す値。負の値にすると変換されやすくなる。
+    pub weight: f32,
+}
+
+impl DictSource {
+    pub fn system() -> DictSource {
+        DictSource {
+            name: SYSTEM_DICT_NAME.to_string(),
+            show_name: false,
+            priority: 0,
+            weight: 0_f32,
+        }
+    }
+
+    /// ユーザー辞書。ほかのどの辞書よりも優先する。
+    pub fn user() -> DictSource {
+        DictSource {
+            name: USER_DICT_NAME.to_string(),
+            show_name: false,
+            priority: i32::MAX,
+            weight: 0_f32,
+        }
+    }
+
+    /// 名前が指定されていなければ、辞書のファイル名を名前にする。
+    pub fn from_config(config: &DictConfig) -> DictSource {
+        let name = match &config.name {
+            Some(name) => name.to_string(),
+            None => Path::new(&config.path)
+                .file_name()
+                .map(|it| it.to_string_lossy().to_string())
+                .unwrap_or_else(|| config.path.to_string()),
+        };
+        DictSource {
+            name,
+            show_name: config.show_name,
+            priority: config.priority,
+            weight: config.weight,
+        }
+    }
+
+    /// 候補のうしろに表示する名前。表示しない辞書なら None。
+    pub fn label(&self) -> Option<&str> {
+        self.show_name.then_some(self.name.as_str())
+    }
+}
+
+/// 辞書の中身。テキストの辞書をパースしたものか、コンパイル済みの辞書。
+/// コンパイル済みの辞書は、HashMap に展開せずにそのまま引く。
+pub enum DictData {
+    Parsed(HashMap<String, Vec<String>>),
+    Compiled(Arc<CompiledDict>),
+}
+
+impl DictData {
+    /// 読みに対応する候補のリスト。
+    pub fn get(&self, yomi: &str) -> Option<Vec<String>> {
+        match self {
+            DictData::Parsed(dict) => dict.get(yomi).cloned(),
+            DictData::Compiled(dict) => dict.get(yomi),
+        }
+    }
+
+    /// "#ねん" のような数値変換のエントリーを返す。パースした辞書からは取りのぞく。
+    pub fn take_numeric_templates(&mut self) -> HashMap<String, Vec<String>> {
+        match self {
+            DictData::Parsed(dict) => {
+                let (rest, templates) = split_numeric_templates(std::mem::take(dict));
+                *dict = rest;
+                templates
+            }
+            DictData::Compiled(dict) => dict.numeric_templates(),
+        }
+    }
+
+    pub fn into_map(self) -> HashMap<String, Vec<String>> {
+        match self {
+            DictData::Parsed(dict) => dict,
+            DictData::Compiled(dict) => dict.to_dict(),
+        }
+    }
+}
+
+/// 出どころつきの辞書。
+pub struct SourcedDict {
+    pub source: Option<Arc<DictSource>>,
+    pub dict: DictData,
+}
+
+impl SourcedDict {
+    pub fn new(source: Option<Arc<DictSource>>, dict: HashMap<String, Vec<String>>) -> SourcedDict {
+        SourcedDict {
+            source,
+            dict: DictData::Parsed(dict),
+        }
+    }
+
+    pub fn compiled(source: Option<Arc<DictSource>>, dict: Arc<CompiledDict>) -> SourcedDict {
+        SourcedDict {
+            source,
+            dict: DictData::Compiled(dict),
+        }
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.source.as_ref().map(|it| it.priority).unwrap_or(0)
+    }
+}
+
+/// 優先度の高い順にならべる。同じ優先度のものは、もとの順番のままにする。
+pub fn sort_by_priority(dicts: &mut [SourcedDict]) {
+    dicts.sort_by_key(|it| -it.priority());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_config() {
+        let source = DictSource::from_config(&DictConfig {
+            path: "/usr/share/skk/SKK-JISYO.L".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(source.name, "SKK-JISYO.L");
+        assert_eq!(source.label(), None);
+
+        let source = DictSource::from_config(&DictConfig {
+            path: "/home/user/glossary.txt".to_string(),
+            name: Some("社内用語".to_string()),
+            show_name: true,
+            priority: 10,
+            weight: -2_f32,
+            ..Default::default()
+        });
+        assert_eq!(
+            source,
+            DictSource {
+                name: "社内用語".to_string(),
+                show_name: true,
+                priority: 10,
+                weight: -2_f32,
+            }
+        );
+        assert_eq!(source.label(), Some("社内用語"));
+    }
+
+    #[test]
+    fn test_sort_by_priority() {
+        let dict = |name: &str, priority: i32| {
+            SourcedDict::new(
+                Some(Arc::new(DictSource {
+                    name: name.to_string(),
+                    show_name: false,
+                    priority,
+                    weight: 0_f32,
+                })),
+                HashMap::new(),
+            )
+        };
+        let mut dicts = vec![dict("a", 0), dict("b", 0), dict("c", 10), dict("d", -1)];
+        sort_by_priority(&mut dicts);
+        let names: Vec<String> = dicts
+            .iter()
+            .map(|it| it.source.as_ref().unwrap().name.to_string())
+            .collect();
+        assert_eq!(names, vec!["c", "a", "b", "d"]);
+    }
+}
@@ -0,0 +1,9 @@
+/// 入力全体から、動的に候補を作る。
+/// 変換エンジンを複数のスレッドから共有できるように、Send + Sync であること。
+pub trait DynamicCandidateProvider: Send + Sync {
+    /// 候補の表層を返す。該当しなければ空。
+    ///
+    /// input はローマ字かな変換する前の入力で、"u+3042" や "=1+2" のような記号を含む入力に使う。
+    /// yomi はローマ字かな変換したあとの読み。
+    fn provide(&self, input: &str, yomi: &str) -> Vec<String>;
+}
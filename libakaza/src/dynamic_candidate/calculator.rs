@@ -0,0 +1,143 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::dynamic_candidate::base::DynamicCandidateProvider;
+
+/// "=1+2" のような入力を計算して、結果を候補にする。四則演算と括弧に対応する。
+#[derive(Default)]
+pub struct CalculatorCandidateProvider {}
+
+impl DynamicCandidateProvider for CalculatorCandidateProvider {
+    fn provide(&self, input: &str, _yomi: &str) -> Vec<String> {
+        let Some(expr) = input.strip_prefix('=') else {
+            return Vec::new();
+        };
+        match calculate(expr) {
+            Some(value) => vec![format_value(value)],
+            None => Vec::new(),
+        }
+    }
+}
+
+fn calculate(expr: &str) -> Option<f64> {
+    let mut chars = expr.chars().peekable();
+    let value = parse_expr(&mut chars)?;
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return None;
+    }
+    if value.is_finite() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// 整数になる場合は小数点以下を付けない。
+fn format_value(value: f64) -> String {
+    if value.fract() == 0_f64 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        let s = format!("{:.10}", value);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+// expr := term (('+' | '-') term)*
+fn parse_expr(chars: &mut Peekable<Chars>) -> Option<f64> {
+    let mut value = parse_term(chars)?;
+    loop {
+        skip_whitespace(chars);
+        match chars.peek() {
+            Some('+') => {
+                chars.next();
+                value += parse_term(chars)?;
+            }
+            Some('-') => {
+                chars.next();
+                value -= parse_term(chars)?;
+            }
+            _ => return Some(value),
+        }
+    }
+}
+
+// term := factor (('*' | '/') factor)*
+fn parse_term(chars: &mut Peekable<Chars>) -> Option<f64> {
+    let mut value = parse_factor(chars)?;
+    loop {
+        skip_whitespace(chars);
+        match chars.peek() {
+            Some('*') => {
+                chars.next();
+                value *= parse_factor(chars)?;
+            }
+            Some('/') => {
+                chars.next();
+                let divisor = parse_factor(chars)?;
+                if divisor == 0_f64 {
+                    return None;
+                }
+                value /= divisor;
+            }
+            _ => return Some(value),
+        }
+    }
+}
+
+// factor := '-' factor | '(' expr ')' | number
+fn parse_factor(chars: &mut Peekable<Chars>) -> Option<f64> {
+    skip_whitespace(chars);
+    match chars.peek()? {
+        '-' => {
+            chars.next();
+            Some(-parse_factor(chars)?)
+        }
+        '(' => {
+            chars.next();
+            let value = parse_expr(chars)?;
+            skip_whitespace(chars);
+            if chars.next()? != ')' {
+                return None;
+            }
+            Some(value)
+        }
+        _ => {
+            let mut buf = String::new();
+            while let Some(c) = chars.peek() {
+                if !c.is_ascii_digit() && *c != '.' {
+                    break;
+                }
+                buf.push(*c);
+                chars.next();
+            }
+            buf.parse::<f64>().ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculator() {
+        let provider = CalculatorCandidateProvider::default();
+        let calc = |input: &str| provider.provide(input, input);
+        assert_eq!(calc("=1+2"), vec!["3"]);
+        assert_eq!(calc("=2*(3+4)"), vec!["14"]);
+        assert_eq!(calc("=1/4"), vec!["0.25"]);
+        assert_eq!(calc("=10/3"), vec!["3.3333333333"]);
+        assert_eq!(calc("=-2*-3"), vec!["6"]);
+        assert_eq!(calc("=1/0"), Vec::<String>::new());
+        assert_eq!(calc("=1+"), Vec::<String>::new());
+        assert_eq!(calc("=(1+2"), Vec::<String>::new());
+        assert_eq!(calc("1+2"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::Datelike;
+
+use crate::config::DateCandidateConfig;
+use crate::dynamic_candidate::base::DynamicCandidateProvider;
+use crate::graph::candidate::now;
+use crate::wareki::{format_wareki_year, to_wareki};
+
+const WEEKDAYS: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+
+/**
+ * 「きょう」のような読みに対して、現在の日時を候補にする。
+ *
+ * 書式は strftime 形式。加えて、以下のものが使える。
+ *
+ * - {wareki}: 元号つきの年。"令和5"
+ * - {weekday}: 曜日。"月"
+ */
+pub struct DateCandidateProvider {
+    configs: Vec<DateCandidateConfig>,
+}
+
+impl DateCandidateProvider {
+    pub fn new(configs: Vec<DateCandidateConfig>) -> DateCandidateProvider {
+        DateCandidateProvider { configs }
+    }
+}
+
+impl DynamicCandidateProvider for DateCandidateProvider {
+    fn provide(&self, _input: &str, yomi: &str) -> Vec<String> {
+        let now = now();
+        let wareki = to_wareki(&now)
+            .map(|(era, year)| format_wareki_year(era, year))
+            .unwrap_or_default();
+        let weekday = WEEKDAYS[now.weekday().num_days_from_monday() as usize];
+
+        self.configs
+            .iter()
+            .filter(|config| config.yomi == yomi)
+            .flat_map(|config| config.formats.iter())
+            .filter_map(|format| {
+                let format = format
+                    .replace("{wareki}", &wareki)
+                    .replace("{weekday}", weekday);
+                // 不正な書式を to_string すると panic するので、先に確認しておく。
+                if StrftimeItems::new(&format).any(|item| item == Item::Error) {
+                    return None;
+                }
+                Some(now.format(&format).to_string())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        let provider = DateCandidateProvider::new(vec![
+            DateCandidateConfig {
+                yomi: "きょう".to_string(),
+                formats: vec![
+                    "%Y-%m-%d".to_string(),
+                    "{wareki}年%-m月%-d日({weekday})".to_string(),
+                    "%Q".to_string(),
+                ],
+            },
+            DateCandidateConfig {
+                yomi: "いま".to_string(),
+                formats: vec!["%H:%M".to_string()],
+            },
+        ]);
+        assert_eq!(
+            provider.provide("kyou", "きょう"),
+            vec!["2023-01-16", "令和5年1月16日(月)"]
+        );
+        assert_eq!(provider.provide("ima", "いま"), vec!["15:14"]);
+        assert_eq!(provider.provide("asu", "あす"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,9 @@
+/*
+ * 入力から動的に作る候補。日付や計算結果のように、辞書には書いておけないものを扱う。
+ * DynamicCandidateProvider を実装して変換エンジンに登録すれば、候補を追加できる。
+ */
+pub mod base;
+pub mod calculator;
+pub mod date;
+pub mod unicode;
+pub mod wareki;
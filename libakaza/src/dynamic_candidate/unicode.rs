@@ -0,0 +1,39 @@
+use crate::dynamic_candidate::base::DynamicCandidateProvider;
+
+/// "u+3042" のようなコードポイントを、その文字に変換する。
+#[derive(Default)]
+pub struct UnicodeCandidateProvider {}
+
+impl DynamicCandidateProvider for UnicodeCandidateProvider {
+    fn provide(&self, input: &str, _yomi: &str) -> Vec<String> {
+        let Some(hex) = input
+            .strip_prefix("u+")
+            .or_else(|| input.strip_prefix("U+"))
+        else {
+            return Vec::new();
+        };
+        if hex.is_empty() || hex.len() > 6 {
+            return Vec::new();
+        }
+        u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .map(|c| vec![c.to_string()])
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode() {
+        let provider = UnicodeCandidateProvider::default();
+        assert_eq!(provider.provide("u+3042", "う+3042"), vec!["あ"]);
+        assert_eq!(provider.provide("U+1F363", "U+1F363"), vec!["🍣"]);
+        assert_eq!(provider.provide("u+d800", "う+d800"), Vec::<String>::new());
+        assert_eq!(provider.provide("u+", "う+"), Vec::<String>::new());
+        assert_eq!(provider.provide("aiu", "あいう"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::dynamic_candidate::base::DynamicCandidateProvider;
+use crate::wareki::{eras_of_year, format_wareki_year, from_wareki, parse_wareki_yomi};
+
+/**
+ * 和暦と西暦の年を相互に変換する。
+ *
+ * - "れいわ6ねん" → "2024年", "令和6年"
+ * - "2024ねん" → "令和6年"
+ */
+#[derive(Default)]
+pub struct WarekiCandidateProvider {}
+
+impl DynamicCandidateProvider for WarekiCandidateProvider {
+    fn provide(&self, _input: &str, yomi: &str) -> Vec<String> {
+        if let Some((era, year)) = parse_wareki_yomi(yomi) {
+            let Some(seireki) = from_wareki(era, year) else {
+                return Vec::new();
+            };
+            return vec![
+                format!("{}年", seireki),
+                format!("{}年", format_wareki_year(era, year)),
+            ];
+        }
+
+        let Some(year) = yomi.strip_suffix("ねん") else {
+            return Vec::new();
+        };
+        if year.is_empty() || !year.chars().all(|c| c.is_ascii_digit()) {
+            return Vec::new();
+        }
+        let Ok(year) = year.parse::<i32>() else {
+            return Vec::new();
+        };
+        eras_of_year(year)
+            .iter()
+            .map(|(era, year)| format!("{}年", format_wareki_year(era, *year)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wareki() {
+        let provider = WarekiCandidateProvider::default();
+        assert_eq!(
+            provider.provide("reiwa6nenn", "れいわ6ねん"),
+            vec!["2024年", "令和6年"]
+        );
+        assert_eq!(
+            provider.provide("heiseigannnenn", "へいせいがんねん"),
+            vec!["1989年", "平成元年"]
+        );
+        assert_eq!(provider.provide("2024nenn", "2024ねん"), vec!["令和6年"]);
+        assert_eq!(
+            provider.provide("2019nenn", "2019ねん"),
+            vec!["令和元年", "平成31年"]
+        );
+        assert_eq!(
+            provider.provide("reiwa2147483647nenn", "れいわ2147483647ねん"),
+            Vec::<String>::new()
+        );
+        assert_eq!(provider.provide("nenn", "ねん"), Vec::<String>::new());
+        assert_eq!(
+            provider.provide("1000nenn", "1000ねん"),
+            Vec::<String>::new()
+        );
+    }
+}
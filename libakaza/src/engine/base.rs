@@ -2,13 +2,27 @@ use std::collections::vec_deque::VecDeque;
 use std::ops::Range;
 
 use crate::graph::candidate::Candidate;
+use crate::graph::graph_resolver::Sentence;
 
 pub trait HenkanEngine {
-    fn learn(&mut self, candidates: &[Candidate]);
+    fn learn(&self, candidates: &[Candidate]);
 
     fn convert(
         &self,
         yomi: &str,
         force_ranges: Option<&[Range<usize>]>,
     ) -> anyhow::Result<Vec<VecDeque<Candidate>>>;
+
+    /// 文全体の変換結果を、コストの低い順に最大 n 件返す。
+    /// 別の文節区切りも含めて列挙するので、リランキングなどに利用できる。
+    fn convert_nbest(
+        &self,
+        yomi: &str,
+        force_ranges: Option<&[Range<usize>]>,
+        n: usize,
+    ) -> anyhow::Result<Vec<Sentence>>;
+
+    /// 入力途中の読みから、続きを補完した候補(予測変換)をコストの低い順に最大 max_candidates 件返す。
+    /// ユーザーの入力履歴、システム辞書、言語モデルから候補を作る。
+    fn predict(&self, yomi: &str, max_candidates: usize) -> anyhow::Result<Vec<Candidate>>;
 }
@@ -1,43 +1,68 @@
 use std::collections::vec_deque::VecDeque;
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::env;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use anyhow::{bail, Result};
 use encoding_rs::UTF_8;
+use log::{info, warn};
 
 use crate::config::Config;
-use crate::dict::loader::load_dicts;
-use crate::dict::merge_dict::merge_dict;
+use crate::cost_model::base::CostModel;
+use crate::cost_model::default_cost_model::DefaultCostModel;
+use crate::cost_model::feature_cost_model::FeatureCostModel;
+use crate::cost_model::features::FeatureWeights;
+use crate::cost_model::interpolated_cost_model::InterpolatedCostModel;
+use crate::cost_model::trigram_cost_model::TrigramCostModel;
+use crate::dict::compiled_dict::CompiledDict;
+use crate::dict::loader::load_sourced_dicts;
 use crate::dict::skk::read::read_skkdict;
+use crate::dict::source::{DictData, DictSource, SourcedDict};
+use crate::dynamic_candidate::base::DynamicCandidateProvider;
+use crate::dynamic_candidate::calculator::CalculatorCandidateProvider;
+use crate::dynamic_candidate::date::DateCandidateProvider;
+use crate::dynamic_candidate::unicode::UnicodeCandidateProvider;
+use crate::dynamic_candidate::wareki::WarekiCandidateProvider;
 use crate::engine::base::HenkanEngine;
 use crate::graph::candidate::Candidate;
 use crate::graph::graph_builder::GraphBuilder;
-use crate::graph::graph_resolver::GraphResolver;
+use crate::graph::graph_resolver::{GraphResolver, Sentence};
 use crate::graph::lattice_graph::LatticeGraph;
 use crate::graph::segmenter::Segmenter;
+use crate::graph::word_node::WordNode;
+use crate::kana_trie::base::KanaTrie;
 use crate::kana_trie::cedarwood_kana_trie::CedarwoodKanaTrie;
-use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
+use crate::kana_trie::compiled_dict_kana_trie::CompiledDictKanaTrie;
+use crate::lm::base::SystemUnigramLM;
 use crate::lm::system_bigram::MarisaSystemBigramLM;
+use crate::lm::system_trigram::MarisaSystemTrigramLM;
 use crate::lm::system_unigram_lm::MarisaSystemUnigramLM;
 use crate::romkan::RomKanConverter;
 use crate::user_side_data::user_data::UserData;
 
+/// 予測変換で、辞書による補完の対象とする読みの最小文字数。
+/// 短すぎると候補が膨大になるうえ、ほとんど役に立たないため。
+const MIN_PREDICTION_YOMI_CHARS: usize = 2;
+/// 予測変換で、入力履歴をたどって後ろに継ぎ足す単語の最大数。
+const MAX_PREDICTION_CHAIN_WORDS: usize = 3;
+
 /// バイグラムのビタビベースかな漢字変換エンジンです。
 /// 単語バイグラムを採用しています。
-pub struct BigramWordViterbiEngine<U: SystemUnigramLM, B: SystemBigramLM> {
-    graph_builder: GraphBuilder<U, B>,
+///
+/// Send + Sync なので、Arc で包めば複数のスレッドや入力コンテキストから同時に変換できます。
+pub struct BigramWordViterbiEngine<U: SystemUnigramLM> {
+    graph_builder: GraphBuilder<U>,
     pub segmenter: Segmenter,
     pub graph_resolver: GraphResolver,
     romkan_converter: RomKanConverter,
     pub user_data: Arc<Mutex<UserData>>,
+    dynamic_candidate_providers: Vec<Arc<dyn DynamicCandidateProvider>>,
 }
 
-impl<U: SystemUnigramLM, B: SystemBigramLM> HenkanEngine for BigramWordViterbiEngine<U, B> {
-    fn learn(&mut self, candidates: &[Candidate]) {
+impl<U: SystemUnigramLM + 'static> HenkanEngine for BigramWordViterbiEngine<U> {
+    fn learn(&self, candidates: &[Candidate]) {
         self.user_data.lock().unwrap().record_entries(candidates);
     }
 
@@ -46,71 +71,231 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> HenkanEngine for BigramWordViterbiEn
         yomi: &str,
         force_ranges: Option<&[Range<usize>]>,
     ) -> Result<Vec<VecDeque<Candidate>>> {
-        // 先頭が大文字なケースと、URL っぽい文字列のときは変換処理を実施しない。
-        if (!yomi.is_empty()
+        let clauses = if Self::should_skip_conversion(yomi, force_ranges) {
+            vec![VecDeque::from([Candidate::new(yomi, yomi, 0_f32)])]
+        } else {
+            let lattice = self.to_lattice(yomi, force_ranges)?;
+            self.resolve(&lattice)?
+        };
+
+        // 文節を明示的に区切っている場合は、入力全体から作る動的な候補は使わない。
+        if !force_ranges.unwrap_or_default().is_empty() {
+            return Ok(clauses);
+        }
+        let dynamic_candidates = self.dynamic_candidates(yomi);
+        Ok(Self::add_dynamic_candidates(clauses, dynamic_candidates))
+    }
+
+    fn convert_nbest(
+        &self,
+        yomi: &str,
+        force_ranges: Option<&[Range<usize>]>,
+        n: usize,
+    ) -> Result<Vec<Sentence>> {
+        if Self::should_skip_conversion(yomi, force_ranges) {
+            return Ok(vec![Sentence {
+                clauses: vec![Candidate::new(yomi, yomi, 0_f32)],
+                cost: 0_f32,
+            }]);
+        }
+
+        let lattice = self.to_lattice(yomi, force_ranges)?;
+        self.graph_resolver.resolve_nbest(&lattice, n)
+    }
+
+    fn predict(&self, yomi: &str, max_candidates: usize) -> Result<Vec<Candidate>> {
+        if yomi.is_empty() || Self::should_skip_conversion(yomi, None) {
+            return Ok(Vec::new());
+        }
+
+        let lattice = self.to_lattice(yomi, None)?;
+        let clauses: Vec<Candidate> = self
+            .resolve(&lattice)?
+            .iter()
+            .map(|candidates| candidates[0].clone())
+            .collect();
+        let Some((last, prefix)) = clauses.split_last() else {
+            return Ok(Vec::new());
+        };
+
+        // 最後の文節を、辞書にあるより長い読みの単語で補完する。
+        let mut completions: Vec<Vec<Candidate>> = Vec::new();
+        if last.yomi.chars().count() >= MIN_PREDICTION_YOMI_CHARS {
+            let prefix_cost = prefix.last().map(|c| c.cost).unwrap_or(0_f32);
+            let prev_node = match prefix.last() {
+                Some(prev) => self
+                    .graph_builder
+                    .create_word_node(0, &prev.surface, &prev.yomi),
+                None => WordNode::create_bos(),
+            };
+            for predicted_yomi in self.segmenter.predictive_search(&last.yomi) {
+                if predicted_yomi == last.yomi {
+                    continue;
+                }
+                for surface in self.graph_builder.get_surfaces(&predicted_yomi) {
+                    let node = self
+                        .graph_builder
+                        .create_word_node(0, &surface, &predicted_yomi);
+                    let cost = prefix_cost
+                        + lattice.get_edge_cost(&prev_node, &node)
+                        + lattice.get_node_cost(&node);
+                    let mut words = prefix.to_vec();
+                    words.push(Candidate::new(&predicted_yomi, &surface, cost));
+                    completions.push(words);
+                }
+            }
+            completions.sort_by(|a, b| {
+                let a = a.last().unwrap().cost;
+                let b = b.last().unwrap().cost;
+                a.partial_cmp(&b).unwrap()
+            });
+            completions.truncate(max_candidates);
+        }
+
+        // 変換結果そのものと補完した結果のそれぞれについて、入力履歴でよく後ろに続いた単語をつなげる。
+        let mut predictions: Vec<Vec<Candidate>> = self.extend_by_history(&lattice, &clauses);
+        for words in completions {
+            predictions.extend(self.extend_by_history(&lattice, &words));
+            predictions.push(words);
+        }
+
+        let mut result: Vec<Candidate> = predictions
+            .iter()
+            .map(|words| {
+                Candidate::new(
+                    &words.iter().map(|c| c.yomi.as_str()).collect::<String>(),
+                    &words.iter().map(|c| c.surface.as_str()).collect::<String>(),
+                    words.last().unwrap().cost,
+                )
+            })
+            .collect();
+        result.sort_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap());
+        let mut seen: HashSet<String> = HashSet::new();
+        result.retain(|candidate| seen.insert(candidate.surface.clone()));
+        result.truncate(max_candidates);
+        Ok(result)
+    }
+}
+
+impl<U: SystemUnigramLM + 'static> BigramWordViterbiEngine<U> {
+    /// 先頭が大文字なケースと、URL っぽい文字列のときは変換処理を実施しない。
+    fn should_skip_conversion(yomi: &str, force_ranges: Option<&[Range<usize>]>) -> bool {
+        (!yomi.is_empty()
             && yomi.chars().next().unwrap().is_ascii_uppercase()
             && (force_ranges.is_none()
                 || (force_ranges.is_none() && force_ranges.unwrap().is_empty())))
             || yomi.starts_with("https://")
             || yomi.starts_with("http://")
-        {
-            return Ok(vec![VecDeque::from([Candidate::new(yomi, yomi, 0_f32)])]);
+    }
+
+    /// 登録されている DynamicCandidateProvider から、入力全体に対する候補を集める。
+    fn dynamic_candidates(&self, input: &str) -> Vec<Candidate> {
+        let yomi = self.romkan_converter.to_hiragana(input);
+        let mut seen: HashSet<String> = HashSet::new();
+        let candidates: Vec<String> = self
+            .dynamic_candidate_providers
+            .iter()
+            .flat_map(|provider| provider.provide(input, &yomi))
+            .filter(|surface| seen.insert(surface.clone()))
+            .collect();
+        // 候補から削除した単語は、動的な候補にも出さない。
+        let user_data = self.user_data.lock().unwrap();
+        candidates
+            .into_iter()
+            .filter(|surface| !user_data.is_blacklisted_word(&yomi, surface))
+            .map(|surface| Candidate::new(&yomi, &surface, 0_f32))
+            .collect()
+    }
+
+    /**
+     * 動的な候補を変換結果に加える。
+     *
+     * 変換結果が1文節であれば、最初の候補のうしろに入れる。「きょう」を「今日」に変換したい場合を優先する。
+     * 複数の文節に分かれていれば、"=1+2" のように辞書では扱えない入力なので、
+     * 全体を1文節にまとめて動的な候補を先頭にする。
+     */
+    fn add_dynamic_candidates(
+        mut clauses: Vec<VecDeque<Candidate>>,
+        dynamic_candidates: Vec<Candidate>,
+    ) -> Vec<VecDeque<Candidate>> {
+        if dynamic_candidates.is_empty() {
+            return clauses;
+        }
+        if clauses.len() == 1 {
+            let clause = &mut clauses[0];
+            for (i, candidate) in dynamic_candidates.into_iter().enumerate() {
+                if !clause.iter().any(|c| c.surface == candidate.surface) {
+                    clause.insert((i + 1).min(clause.len()), candidate);
+                }
+            }
+            return clauses;
         }
 
-        let lattice = self.to_lattice(yomi, force_ranges)?;
-        self.resolve(&lattice)
+        let mut clause: VecDeque<Candidate> = VecDeque::from(dynamic_candidates);
+        let yomi = clauses
+            .iter()
+            .map(|c| c[0].yomi.as_str())
+            .collect::<String>();
+        let surface = clauses
+            .iter()
+            .map(|c| c[0].surface.as_str())
+            .collect::<String>();
+        if !clause.iter().any(|c| c.surface == surface) {
+            clause.push_back(Candidate::new(&yomi, &surface, 0_f32));
+        }
+        vec![clause]
     }
-}
 
-impl<U: SystemUnigramLM, B: SystemBigramLM> BigramWordViterbiEngine<U, B> {
-    pub fn resolve(&self, lattice: &LatticeGraph<U, B>) -> Result<Vec<VecDeque<Candidate>>> {
+    pub fn resolve(&self, lattice: &LatticeGraph) -> Result<Vec<VecDeque<Candidate>>> {
         self.graph_resolver.resolve(lattice)
     }
 
+    /// 入力履歴で words の後ろによく続いていた単語を、一語ずつつなげていった候補を返す。
+    fn extend_by_history(
+        &self,
+        lattice: &LatticeGraph,
+        words: &[Candidate],
+    ) -> Vec<Vec<Candidate>> {
+        let mut result: Vec<Vec<Candidate>> = Vec::new();
+        let mut current = words.to_vec();
+        for _ in 0..MAX_PREDICTION_CHAIN_WORDS {
+            let Some(last) = current.last() else {
+                break;
+            };
+            let next_words = self.user_data.lock().unwrap().get_next_words(&last.key());
+            let Some((next_key, _)) = next_words.first() else {
+                break;
+            };
+            // 同じ単語が繰り返し続くような履歴では、そこで打ち切る。
+            if current.iter().any(|c| &c.key() == next_key) {
+                break;
+            }
+            let Some((surface, yomi)) = next_key.rsplit_once('/') else {
+                break;
+            };
+
+            let prev_node = self
+                .graph_builder
+                .create_word_node(0, &last.surface, &last.yomi);
+            let node = self.graph_builder.create_word_node(0, surface, yomi);
+            let cost =
+                last.cost + lattice.get_edge_cost(&prev_node, &node) + lattice.get_node_cost(&node);
+            current.push(Candidate::new(yomi, surface, cost));
+            result.push(current.clone());
+        }
+        result
+    }
+
     pub fn to_lattice(
         &self,
         yomi: &str,
         force_ranges: Option<&[Range<usize>]>,
-    ) -> Result<LatticeGraph<U, B>> {
+    ) -> Result<LatticeGraph> {
         // ローマ字からひらがなへの変換をする。
         let yomi = self.romkan_converter.to_hiragana(yomi);
 
-        /*
-            TODO: C++ 版 akaza では子音を先に取り除いておいて、あとからまたくっつけるという処理をしていたようだが、
-            これをやる意味が今はわからないので一旦あとまわし。
-
-                // 子音だが、N は NN だと「ん」になるので処理しない。
-        std::string consonant;
-        {
-            std::wregex trailing_consonant(cnv.from_bytes(R"(^(.*?)([qwrtypsdfghjklzxcvbm]+)$)"));
-            std::wsmatch sm;
-            if (std::regex_match(whiragana, sm, trailing_consonant)) {
-                hiragana = cnv.to_bytes(sm.str(1));
-                consonant = cnv.to_bytes(sm.str(2));
-                D(std::cout << "CONSONANT=" << consonant << std::endl);
-            }
-        }
-
-        Graph graph = graphResolver_->graph_construct(cnv.from_bytes(hiragana), forceSelectedClauses);
-        graphResolver_->fill_cost(graph);
-        D(graph.dump());
-        std::vector<std::vector<std::shared_ptr<akaza::Node>>> nodes = graphResolver_->find_nbest(graph);
-        if (consonant.empty()) {
-            return nodes;
-        } else {
-            D(std::cout << " Adding Consonant=" << consonant << std::endl);
-            nodes.push_back({{
-                                     akaza::create_node(
-                                             graphResolver_->system_unigram_lm_,
-                                             src.size(),
-                                             cnv.from_bytes(consonant),
-                                             cnv.from_bytes(consonant)
-                                     )
-                             }});
-            return nodes;
-        }
-             */
-
+        // "kanjik" のように末尾に子音が残っている場合は、Segmenter が子音を
+        // 辞書引きに使わず、最後の文節として扱う。
         let self1 = &self.segmenter;
         let segmentation_result = self1.build(yomi.as_str(), force_ranges);
         let lattice = self
@@ -123,6 +308,7 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> BigramWordViterbiEngine<U, B> {
 pub struct BigramWordViterbiEngineBuilder {
     user_data: Option<Arc<Mutex<UserData>>>,
     load_user_config: bool,
+    dynamic_candidate_providers: Vec<Arc<dyn DynamicCandidateProvider>>,
     pub config: Config,
 }
 
@@ -131,6 +317,7 @@ impl BigramWordViterbiEngineBuilder {
         BigramWordViterbiEngineBuilder {
             user_data: None,
             load_user_config: false,
+            dynamic_candidate_providers: Vec::new(),
             config,
         }
     }
@@ -147,9 +334,16 @@ impl BigramWordViterbiEngineBuilder {
         self
     }
 
-    pub fn build(
-        &self,
-    ) -> Result<BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM>> {
+    /// 設定ファイルで指定できるもの以外の、動的な候補を追加する。
+    pub fn dynamic_candidate_provider(
+        &mut self,
+        provider: Arc<dyn DynamicCandidateProvider>,
+    ) -> &mut Self {
+        self.dynamic_candidate_providers.push(provider);
+        self
+    }
+
+    pub fn build(&self) -> Result<BigramWordViterbiEngine<MarisaSystemUnigramLM>> {
         let system_unigram_lm = MarisaSystemUnigramLM::load(
             Self::try_load("unigram.model")?
                 .to_string_lossy()
@@ -162,60 +356,154 @@ impl BigramWordViterbiEngineBuilder {
                 .to_string()
                 .as_str(),
         )?;
-        let system_dict = read_skkdict(Self::try_load("SKK-JISYO.akaza")?.as_path(), UTF_8)?;
+        let system_dict = Self::load_system_dict()?;
 
         let user_data = if let Some(d) = &self.user_data {
             d.clone()
         } else {
             Arc::new(Mutex::new(UserData::default()))
         };
+        user_data
+            .lock()
+            .unwrap()
+            .set_stats_config(&self.config.user_stats);
 
-        let dict = load_dicts(&self.config.dicts)?;
-        let dict = merge_dict(vec![system_dict, dict]);
+        // システム辞書は優先度 0 の辞書として、設定ファイルの辞書より先に置く。
+        let mut dicts = vec![SourcedDict {
+            source: Some(Arc::new(DictSource::system())),
+            dict: system_dict,
+        }];
+        dicts.extend(load_sourced_dicts(&self.config.dicts));
 
         let single_term = if let Some(st) = &&self.config.single_term {
-            load_dicts(st)?
+            load_sourced_dicts(st)
         } else {
-            HashMap::new()
+            Vec::new()
         };
 
-        // 辞書を元に、トライを作成していく。
+        // 辞書を元に、トライを作成していく。コンパイル済みの辞書は、そのままトライとして引く。
         let mut kana_trie = CedarwoodKanaTrie::default();
-        for yomi in dict.keys() {
-            assert!(!yomi.is_empty());
-            kana_trie.update(yomi.as_str());
-        }
-        for yomi in single_term.keys() {
-            assert!(!yomi.is_empty());
-            kana_trie.update(yomi.as_str());
+        let mut tries: Vec<Arc<Mutex<dyn KanaTrie>>> = Vec::new();
+        for dict in dicts.iter().chain(single_term.iter()) {
+            match &dict.dict {
+                DictData::Parsed(dict) => {
+                    for yomi in dict.keys() {
+                        assert!(!yomi.is_empty());
+                        kana_trie.update(yomi.as_str());
+                    }
+                }
+                DictData::Compiled(dict) => {
+                    tries.push(Arc::new(Mutex::new(CompiledDictKanaTrie::new(
+                        dict.clone(),
+                    ))));
+                }
+            }
         }
+        tries.insert(0, Arc::new(Mutex::new(kana_trie)));
+        tries.push(user_data.lock().unwrap().kana_trie.clone());
 
-        let segmenter = Segmenter::new(vec![
-            Arc::new(Mutex::new(kana_trie)),
-            user_data.lock().unwrap().kana_trie.clone(),
-        ]);
+        let segmenter = Segmenter::new(tries);
 
-        let graph_builder = GraphBuilder::new_with_default_score(
-            dict,
-            single_term,
-            user_data.clone(),
-            Rc::new(system_unigram_lm),
-            Rc::new(system_bigram_lm),
-        );
+        let system_unigram_lm = Arc::new(system_unigram_lm);
+        let system_bigram_lm = Arc::new(system_bigram_lm);
+        let cost_model: Arc<dyn CostModel> = if let Some(interpolation) = &self.config.interpolation
+        {
+            Arc::new(InterpolatedCostModel::new(
+                user_data.clone(),
+                system_unigram_lm.clone(),
+                system_bigram_lm,
+                interpolation,
+            )?)
+        } else {
+            Arc::new(DefaultCostModel::new(
+                user_data.clone(),
+                system_unigram_lm.clone(),
+                system_bigram_lm,
+            ))
+        };
+        // trigram.model はなくても変換できる。あれば二次のビタビアルゴリズムで変換する。
+        let cost_model: Arc<dyn CostModel> = match Self::try_load("trigram.model") {
+            Ok(path) => {
+                let system_trigram_lm =
+                    MarisaSystemTrigramLM::load(path.to_string_lossy().to_string().as_str())?;
+                Arc::new(TrigramCostModel::new(
+                    cost_model,
+                    Arc::new(system_trigram_lm),
+                ))
+            }
+            Err(err) => {
+                info!("Trigram language model is disabled: {}", err);
+                cost_model
+            }
+        };
+        // feature.model も同様に、あれば素性の重みをコストに足す。
+        let cost_model: Arc<dyn CostModel> = match Self::try_load("feature.model") {
+            Ok(path) => {
+                let weights = FeatureWeights::load(path.to_string_lossy().to_string().as_str())?;
+                Arc::new(FeatureCostModel::new(cost_model, Arc::new(weights)))
+            }
+            Err(err) => {
+                info!("Feature weights are disabled: {}", err);
+                cost_model
+            }
+        };
+
+        let mut graph_builder =
+            GraphBuilder::new_with_sourced_dicts(dicts, single_term, system_unigram_lm, cost_model);
+        graph_builder.set_user_data(user_data.clone());
 
         let graph_resolver = GraphResolver::default();
 
         let romkan_converter = RomKanConverter::new();
 
+        let dynamic_config = &self.config.dynamic_candidates;
+        let mut dynamic_candidate_providers: Vec<Arc<dyn DynamicCandidateProvider>> = Vec::new();
+        if !dynamic_config.date.is_empty() {
+            dynamic_candidate_providers.push(Arc::new(DateCandidateProvider::new(
+                dynamic_config.date.clone(),
+            )));
+        }
+        if dynamic_config.unicode {
+            dynamic_candidate_providers.push(Arc::new(UnicodeCandidateProvider::default()));
+        }
+        if dynamic_config.calculator {
+            dynamic_candidate_providers.push(Arc::new(CalculatorCandidateProvider::default()));
+        }
+        if dynamic_config.wareki {
+            dynamic_candidate_providers.push(Arc::new(WarekiCandidateProvider::default()));
+        }
+        dynamic_candidate_providers.extend(self.dynamic_candidate_providers.iter().cloned());
+
         Ok(BigramWordViterbiEngine {
             graph_builder,
             segmenter,
             graph_resolver,
             romkan_converter,
             user_data,
+            dynamic_candidate_providers,
         })
     }
 
+    /// システム辞書を読む。`akaza-data compile-dict` でコンパイルした辞書があれば、
+    /// テキストの SKK-JISYO.akaza をパースするかわりにそちらをそのまま引く。
+    fn load_system_dict() -> Result<DictData> {
+        let text = Self::try_load("SKK-JISYO.akaza");
+        if let Ok(compiled) = Self::try_load("SKK-JISYO.akaza.marisa") {
+            if text.as_ref().is_ok_and(|text| is_newer(text, &compiled)) {
+                warn!(
+                    "{:?} is older than SKK-JISYO.akaza. Ignore the compiled dictionary.",
+                    compiled
+                );
+            } else {
+                match CompiledDict::load(compiled.to_string_lossy().to_string().as_str()) {
+                    Ok(dict) => return Ok(DictData::Compiled(Arc::new(dict))),
+                    Err(err) => warn!("Cannot load compiled dictionary: {}", err),
+                }
+            }
+        }
+        Ok(DictData::Parsed(read_skkdict(text?.as_path(), UTF_8)?))
+    }
+
     pub fn try_load(file_name: &str) -> Result<PathBuf> {
         if cfg!(test) {
             let path = Path::new(env!("CARGO_MANIFEST_DIR"));
@@ -247,3 +535,173 @@ impl BigramWordViterbiEngineBuilder {
         }
     }
 }
+
+/// a の更新日時が b よりも新しいかどうか。更新日時がとれない場合は false。
+fn is_newer(a: &Path, b: &Path) -> bool {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    match (modified(a), modified(b)) {
+        (Some(a), Some(b)) => a > b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::lm::system_bigram::MarisaSystemBigramLMBuilder;
+    use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+
+    use super::*;
+
+    /// 辞書と言語モデルのファイルを使わずに、小さな辞書からエンジンを作る。
+    fn build_engine(
+        dict: HashMap<String, Vec<String>>,
+        user_data: UserData,
+    ) -> Result<BigramWordViterbiEngine<MarisaSystemUnigramLM>> {
+        let user_data = Arc::new(Mutex::new(user_data));
+        let kana_trie = CedarwoodKanaTrie::build(dict.keys().cloned().collect());
+        let segmenter = Segmenter::new(vec![
+            Arc::new(Mutex::new(kana_trie)),
+            user_data.lock().unwrap().kana_trie.clone(),
+        ]);
+        let graph_builder = GraphBuilder::new_with_default_score(
+            dict,
+            HashMap::new(),
+            user_data.clone(),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        Ok(BigramWordViterbiEngine {
+            graph_builder,
+            segmenter,
+            graph_resolver: GraphResolver::default(),
+            romkan_converter: RomKanConverter::new(),
+            user_data,
+            dynamic_candidate_providers: Vec::new(),
+        })
+    }
+
+    fn prediction_surfaces(candidates: &[Candidate]) -> Vec<String> {
+        candidates.iter().map(|c| c.surface.to_string()).collect()
+    }
+
+    #[test]
+    fn test_predict_completion() -> Result<()> {
+        let engine = build_engine(
+            HashMap::from([
+                ("てんき".to_string(), vec!["天気".to_string()]),
+                ("てんきよほう".to_string(), vec!["天気予報".to_string()]),
+            ]),
+            UserData::default(),
+        )?;
+        let got = prediction_surfaces(&engine.predict("てんき", 10)?);
+        assert!(got.contains(&"天気予報".to_string()), "{:?}", got);
+        assert!(engine.predict("", 10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_predict_history() -> Result<()> {
+        let mut user_data = UserData::default();
+        user_data.record_entries(&[
+            Candidate::new("きょう", "今日", 0_f32),
+            Candidate::new("は", "は", 0_f32),
+            Candidate::new("はれ", "晴れ", 0_f32),
+        ]);
+        let engine = build_engine(
+            HashMap::from([
+                ("きょう".to_string(), vec!["今日".to_string()]),
+                ("はれ".to_string(), vec!["晴れ".to_string()]),
+            ]),
+            user_data,
+        )?;
+        let got = prediction_surfaces(&engine.predict("きょう", 10)?);
+        assert!(got.contains(&"今日は".to_string()), "{:?}", got);
+        assert!(got.contains(&"今日は晴れ".to_string()), "{:?}", got);
+        Ok(())
+    }
+
+    struct FixedCandidateProvider;
+
+    impl DynamicCandidateProvider for FixedCandidateProvider {
+        fn provide(&self, _input: &str, _yomi: &str) -> Vec<String> {
+            vec!["今日".to_string(), "2023年1月16日".to_string()]
+        }
+    }
+
+    #[test]
+    fn test_dynamic_candidates_blacklist() -> Result<()> {
+        let mut user_data = UserData::default();
+        user_data.forget_word("きょう", "2023年1月16日");
+        let mut engine = build_engine(HashMap::new(), user_data)?;
+        engine
+            .dynamic_candidate_providers
+            .push(Arc::new(FixedCandidateProvider));
+        let got: Vec<String> = engine
+            .dynamic_candidates("kyou")
+            .iter()
+            .map(|c| c.surface.to_string())
+            .collect();
+        assert_eq!(got, vec!["今日"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BigramWordViterbiEngine<MarisaSystemUnigramLM>>();
+    }
+
+    fn surfaces(clauses: &[VecDeque<Candidate>]) -> Vec<Vec<String>> {
+        clauses
+            .iter()
+            .map(|c| c.iter().map(|c| c.surface.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_add_dynamic_candidates() {
+        // 1文節なら、最初の候補のうしろに入れる。
+        let got = BigramWordViterbiEngine::<MarisaSystemUnigramLM>::add_dynamic_candidates(
+            vec![VecDeque::from([
+                Candidate::new("きょう", "今日", 0_f32),
+                Candidate::new("きょう", "京", 0_f32),
+            ])],
+            vec![Candidate::new("きょう", "令和5年1月16日", 0_f32)],
+        );
+        assert_eq!(surfaces(&got), vec![vec!["今日", "令和5年1月16日", "京"]]);
+
+        // 複数の文節に分かれていれば、まとめて先頭にする。
+        let got = BigramWordViterbiEngine::<MarisaSystemUnigramLM>::add_dynamic_candidates(
+            vec![
+                VecDeque::from([Candidate::new("=1", "=1", 0_f32)]),
+                VecDeque::from([Candidate::new("+2", "+2", 0_f32)]),
+            ],
+            vec![Candidate::new("=1+2", "3", 0_f32)],
+        );
+        assert_eq!(surfaces(&got), vec![vec!["3", "=1+2"]]);
+
+        // まとめた変換結果が動的な候補と同じであれば、重複させない。
+        let got = BigramWordViterbiEngine::<MarisaSystemUnigramLM>::add_dynamic_candidates(
+            vec![
+                VecDeque::from([Candidate::new("れいわ", "令和", 0_f32)]),
+                VecDeque::from([Candidate::new("6ねん", "6年", 0_f32)]),
+            ],
+            vec![
+                Candidate::new("れいわ6ねん", "2024年", 0_f32),
+                Candidate::new("れいわ6ねん", "令和6年", 0_f32),
+            ],
+        );
+        assert_eq!(surfaces(&got), vec![vec!["2024年", "令和6年"]]);
+    }
+}
@@ -1,15 +1,24 @@
 #[allow(unused_imports)]
 use chrono::{DateTime, Local, TimeZone};
+use log::warn;
+
+use crate::dict::skk::lisp::{eval_lisp, is_lisp};
 
 #[derive(Debug, Clone)]
 pub struct Candidate {
     pub surface: String,
     pub yomi: String,
     pub cost: f32,
+    /// 辞書に書かれていたアノテーション。候補の説明として表示する。
+    pub annotation: Option<String>,
+    /// 候補のうしろに表示する、候補を引いた辞書の名前。名前を表示する設定の辞書のみ。
+    pub dict_label: Option<String>,
+    /// single term 辞書から引いた候補かどうか。素性の学習に使う。
+    pub single_term: bool,
 }
 
 impl Candidate {
-    pub(crate) fn key(&self) -> String {
+    pub fn key(&self) -> String {
         self.surface.to_string() + "/" + self.yomi.as_str()
     }
 }
@@ -20,18 +29,22 @@ impl Candidate {
             yomi: yomi.to_string(),
             surface: surface.to_string(),
             cost,
+            annotation: None,
+            dict_label: None,
+            single_term: false,
         }
     }
 
     /// 動的なエントリーも考慮した上での surface を得る。
+    /// SKK 辞書の (current-time-string) のような Lisp 式は、ここで評価する。
     pub fn surface_with_dynamic(&self) -> String {
-        if self.surface.starts_with("(*(*(") {
-            match self.surface.as_str() {
-                "(*(*(TODAY-HYPHEN" => now().format("%Y-%m-%d").to_string(),
-                "(*(*(TODAY-SLASH" => now().format("%Y/%m/%d").to_string(),
-                "(*(*(TODAY-KANJI" => now().format("%Y年%m月%d日").to_string(),
-                "(*(*(NOW-KANJI" => now().format("%H時%M分").to_string(),
-                _ => "不明な動的変換: ".to_string() + self.surface.as_str(),
+        if is_lisp(&self.surface) {
+            match eval_lisp(&self.surface, Some(now())) {
+                Ok(surface) => surface,
+                Err(err) => {
+                    warn!("Cannot evaluate {}: {}", self.surface, err);
+                    "不明な動的変換: ".to_string() + self.surface.as_str()
+                }
             }
         } else {
             self.surface.to_string()
@@ -40,12 +53,12 @@ impl Candidate {
 }
 
 #[cfg(not(test))]
-fn now() -> DateTime<Local> {
+pub(crate) fn now() -> DateTime<Local> {
     Local::now()
 }
 
 #[cfg(test)]
-fn now() -> DateTime<Local> {
+pub(crate) fn now() -> DateTime<Local> {
     Local.with_ymd_and_hms(2023, 1, 16, 15, 14, 16).unwrap()
 }
 
@@ -59,9 +72,13 @@ mod tests {
             Candidate::new("きょう", surface, 0.0_f32).surface_with_dynamic()
         }
 
-        assert_eq!(test("(*(*(TODAY-HYPHEN"), "2023-01-16");
-        assert_eq!(test("(*(*(TODAY-SLASH"), "2023/01/16");
-        assert_eq!(test("(*(*(TODAY-KANJI"), "2023年01月16日");
-        assert_eq!(test("(*(*(NOW-KANJI"), "15時14分");
+        assert_eq!(test(r#"(format-time-string "%Y-%m-%d")"#), "2023-01-16");
+        assert_eq!(
+            test(r#"(format-time-string (concat "%Y\057%m\057%d"))"#),
+            "2023/01/16"
+        );
+        assert_eq!(test("(skk-current-date)"), "令和5年1月16日(月)");
+        assert_eq!(test("(current-time-string)"), "Mon Jan 16 15:14:16 2023");
+        assert_eq!(test("今日"), "今日");
     }
 }
@@ -1,49 +1,67 @@
 use std::collections::btree_map::BTreeMap;
 use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use kelp::{hira2kata, ConvOption};
 use log::trace;
 
+use crate::cost_model::base::CostModel;
+use crate::cost_model::default_cost_model::DefaultCostModel;
+use crate::dict::skk::okuri::okuri_ari_candidates;
+use crate::dict::skk::read::{split_annotation, unescape_annotation};
+use crate::dict::source::{sort_by_priority, DictData, DictSource, SourcedDict};
 use crate::graph::lattice_graph::LatticeGraph;
-use crate::graph::segmenter::SegmentationResult;
+use crate::graph::segmenter::{split_trailing_consonant, SegmentationResult};
 use crate::graph::word_node::WordNode;
 use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
+use crate::numeric::{
+    counter_surfaces, expand_numeric_template, is_digits, numeric_surfaces, numeric_template_key,
+};
 use crate::user_side_data::user_data::UserData;
 
-pub struct GraphBuilder<U: SystemUnigramLM, B: SystemBigramLM> {
-    system_kana_kanji_dict: HashMap<String, Vec<String>>,
-    system_single_term_dict: HashMap<String, Vec<String>>,
-    user_data: Arc<Mutex<UserData>>,
-    system_unigram_lm: Rc<U>,
-    system_bigram_lm: Rc<B>,
+pub struct GraphBuilder<U: SystemUnigramLM> {
+    // 優先度の高い順にならべた辞書。同じ表層の候補は、先にある辞書のものを使う。
+    system_kana_kanji_dicts: Vec<SourcedDict>,
+    system_single_term_dicts: Vec<SourcedDict>,
+    // "#ねん" → ["#1年", "#3年"] のような数値変換のエントリー。かな漢字辞書からは分けて持つ。
+    numeric_templates: HashMap<String, Vec<String>>,
+    system_unigram_lm: Arc<U>,
+    cost_model: Arc<dyn CostModel>,
+    // ユーザー辞書を引くために使う。
+    user_data: Option<Arc<Mutex<UserData>>>,
+    user_dict_source: Arc<DictSource>,
 }
 
-impl<U: SystemUnigramLM, B: SystemBigramLM> GraphBuilder<U, B> {
-    pub fn new(
+impl<U: SystemUnigramLM + 'static> GraphBuilder<U> {
+    pub fn new<B: SystemBigramLM + 'static>(
         system_kana_kanji_dict: HashMap<String, Vec<String>>,
         system_single_term_dict: HashMap<String, Vec<String>>,
         user_data: Arc<Mutex<UserData>>,
-        system_unigram_lm: Rc<U>,
-        system_bigram_lm: Rc<B>,
-    ) -> GraphBuilder<U, B> {
-        GraphBuilder {
+        system_unigram_lm: Arc<U>,
+        system_bigram_lm: Arc<B>,
+    ) -> GraphBuilder<U> {
+        let cost_model = Arc::new(DefaultCostModel::new(
+            user_data.clone(),
+            system_unigram_lm.clone(),
+            system_bigram_lm,
+        ));
+        let mut graph_builder = Self::new_with_cost_model(
             system_kana_kanji_dict,
             system_single_term_dict,
-            user_data,
             system_unigram_lm,
-            system_bigram_lm,
-        }
+            cost_model,
+        );
+        graph_builder.set_user_data(user_data);
+        graph_builder
     }
 
-    pub fn new_with_default_score(
+    pub fn new_with_default_score<B: SystemBigramLM + 'static>(
         system_kana_kanji_dict: HashMap<String, Vec<String>>,
         system_single_term_dict: HashMap<String, Vec<String>>,
         user_data: Arc<Mutex<UserData>>,
-        system_unigram_lm: Rc<U>,
-        system_bigram_lm: Rc<B>,
-    ) -> GraphBuilder<U, B> {
+        system_unigram_lm: Arc<U>,
+        system_bigram_lm: Arc<B>,
+    ) -> GraphBuilder<U> {
         Self::new(
             system_kana_kanji_dict,
             system_single_term_dict,
@@ -53,7 +71,55 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> GraphBuilder<U, B> {
         )
     }
 
-    pub fn construct(&self, yomi: &str, words_ends_at: SegmentationResult) -> LatticeGraph<U, B> {
+    /// ノード・エッジのコスト計算を cost_model に任せる。
+    /// system_unigram_lm は、ノードに単語IDを振るためだけに使う。
+    pub fn new_with_cost_model(
+        system_kana_kanji_dict: HashMap<String, Vec<String>>,
+        system_single_term_dict: HashMap<String, Vec<String>>,
+        system_unigram_lm: Arc<U>,
+        cost_model: Arc<dyn CostModel>,
+    ) -> GraphBuilder<U> {
+        Self::new_with_sourced_dicts(
+            vec![SourcedDict::new(None, system_kana_kanji_dict)],
+            vec![SourcedDict::new(None, system_single_term_dict)],
+            system_unigram_lm,
+            cost_model,
+        )
+    }
+
+    /// 出どころつきの辞書を使う。辞書は優先度の高い順にならべかえて使う。
+    pub fn new_with_sourced_dicts(
+        mut system_kana_kanji_dicts: Vec<SourcedDict>,
+        mut system_single_term_dicts: Vec<SourcedDict>,
+        system_unigram_lm: Arc<U>,
+        cost_model: Arc<dyn CostModel>,
+    ) -> GraphBuilder<U> {
+        sort_by_priority(&mut system_kana_kanji_dicts);
+        sort_by_priority(&mut system_single_term_dicts);
+        let mut numeric_templates: HashMap<String, Vec<String>> = HashMap::new();
+        for dict in &mut system_kana_kanji_dicts {
+            for (yomi, templates) in dict.dict.take_numeric_templates() {
+                numeric_templates.entry(yomi).or_default().extend(templates);
+            }
+        }
+        GraphBuilder {
+            system_kana_kanji_dicts,
+            system_single_term_dicts,
+            numeric_templates,
+            system_unigram_lm,
+            cost_model,
+            user_data: None,
+            user_dict_source: Arc::new(DictSource::user()),
+        }
+    }
+
+    /// ユーザー辞書に登録された単語も候補にする。
+    pub fn set_user_data(&mut self, user_data: Arc<Mutex<UserData>>) -> &mut Self {
+        self.user_data = Some(user_data);
+        self
+    }
+
+    pub fn construct(&self, yomi: &str, words_ends_at: SegmentationResult) -> LatticeGraph {
         // このグラフのインデクスは単語の終了位置。
         let mut graph: BTreeMap<i32, Vec<WordNode>> = BTreeMap::new();
         graph.insert(0, vec![WordNode::create_bos()]);
@@ -62,32 +128,101 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> GraphBuilder<U, B> {
             vec![WordNode::create_eos(yomi.len() as i32)],
         );
 
+        let (_, consonant) = split_trailing_consonant(yomi);
+
+        // ユーザーが候補から削除した単語は、グラフに入れない。
+        let is_blacklisted = |yomi: &str, surface: &str| self.is_blacklisted_word(yomi, surface);
+
         for (end_pos, segmented_yomis) in words_ends_at.iter() {
             for segmented_yomi in segmented_yomis {
                 let vec = graph.entry(*end_pos as i32).or_default();
 
+                // 末尾に残った子音は、そのままの形でだけ候補にする。
+                if !consonant.is_empty() && *end_pos == yomi.len() && segmented_yomi == consonant {
+                    vec.push(WordNode::new(
+                        (end_pos - segmented_yomi.len()) as i32,
+                        segmented_yomi,
+                        segmented_yomi,
+                        None,
+                    ));
+                    continue;
+                }
+
                 let mut seen: HashSet<String> = HashSet::new();
 
+                // ユーザー辞書に登録された単語は、ほかの辞書より先にする。
+                for word in &self.get_user_dict_words(segmented_yomi) {
+                    let (surface, annotation) = split_annotation(word);
+                    // 活用形を忘れても、ユーザー辞書からは見出し語しか消えないので、ここでも確認する。
+                    if is_blacklisted(segmented_yomi, surface) || !seen.insert(surface.to_string())
+                    {
+                        continue;
+                    }
+                    let mut node = self.create_word_node(
+                        (end_pos - segmented_yomi.len()) as i32,
+                        surface,
+                        segmented_yomi,
+                    );
+                    node.annotation = annotation.map(unescape_annotation);
+                    node.dict_source = Some(self.user_dict_source.clone());
+                    vec.push(node);
+                }
+
                 // 漢字に変換した結果もあれば insert する。
-                if let Some(kanjis) = self.system_kana_kanji_dict.get(segmented_yomi) {
-                    for kanji in kanjis {
-                        let node = WordNode::new(
+                for dict in &self.system_kana_kanji_dicts {
+                    for kanji in &lookup_kana_kanji(&dict.dict, segmented_yomi) {
+                        let (kanji, annotation) = split_annotation(kanji);
+                        if seen.contains(kanji) || is_blacklisted(segmented_yomi, kanji) {
+                            continue;
+                        }
+                        let mut node = self.create_word_node(
                             (end_pos - segmented_yomi.len()) as i32,
                             kanji,
                             segmented_yomi,
-                            self.system_unigram_lm
-                                .find((kanji.to_string() + "/" + segmented_yomi).as_str()),
                         );
+                        node.annotation = annotation.map(unescape_annotation);
+                        node.dict_source = dict.source.clone();
                         trace!("WordIDScore: {:?}", node.word_id_and_score);
                         vec.push(node);
                         seen.insert(kanji.to_string());
                     }
                 }
+                // 数字、または数字と助数詞の組み合わせであれば、いろいろな表記を候補にする。
+                // "#ねん" → ["#1年", "#3年"] のような、辞書の数値変換のエントリーがあれば、そちらを先にする。
+                let mut numerics: Vec<String> = Vec::new();
+                if let Some((key, numbers)) = numeric_template_key(segmented_yomi) {
+                    for template in self.numeric_templates.get(&key).into_iter().flatten() {
+                        numerics.extend(expand_numeric_template(
+                            split_annotation(template).0,
+                            &numbers,
+                        ));
+                    }
+                }
+                if is_digits(segmented_yomi) {
+                    numerics.extend(numeric_surfaces(segmented_yomi));
+                } else {
+                    numerics.extend(counter_surfaces(segmented_yomi));
+                }
+                for surface in numerics {
+                    if seen.contains(&surface) || is_blacklisted(segmented_yomi, &surface) {
+                        continue;
+                    }
+                    let node = self.create_word_node(
+                        (end_pos - segmented_yomi.len()) as i32,
+                        &surface,
+                        segmented_yomi,
+                    );
+                    vec.push(node);
+                    seen.insert(surface);
+                }
                 for surface in [
                     segmented_yomi,
                     hira2kata(segmented_yomi, ConvOption::default()).as_str(),
                 ] {
-                    if seen.contains(surface) {
+                    // ひらがなそのものは、どの候補も削除されていても変換できるように残しておく。
+                    if seen.contains(surface)
+                        || (surface != segmented_yomi && is_blacklisted(segmented_yomi, surface))
+                    {
                         continue;
                     }
                     // ひらがなそのものと、カタカナ表現もエントリーとして登録しておく。
@@ -102,15 +237,28 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> GraphBuilder<U, B> {
 
                 // 変換範囲が全体になっていれば single term 辞書を利用する。
                 if segmented_yomi == yomi {
-                    if let Some(surfaces) = self.system_single_term_dict.get(yomi) {
-                        for surface in surfaces {
-                            let node = WordNode::new(
+                    let mut seen_single_term: HashSet<String> = HashSet::new();
+                    for dict in &self.system_single_term_dicts {
+                        let Some(surfaces) = dict.dict.get(yomi) else {
+                            continue;
+                        };
+                        for surface in &surfaces {
+                            let (surface, annotation) = split_annotation(surface);
+                            if is_blacklisted(yomi, surface)
+                                || !seen_single_term.insert(surface.to_string())
+                            {
+                                continue;
+                            }
+                            let mut node = WordNode::new(
                                 (end_pos - segmented_yomi.len()) as i32,
                                 surface,
                                 segmented_yomi,
                                 self.system_unigram_lm
                                     .find((surface.to_string() + "/" + segmented_yomi).as_str()),
                             );
+                            node.single_term = true;
+                            node.annotation = annotation.map(unescape_annotation);
+                            node.dict_source = dict.source.clone();
                             vec.push(node);
                         }
                     }
@@ -120,17 +268,76 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> GraphBuilder<U, B> {
         LatticeGraph {
             graph,
             yomi: yomi.to_string(),
-            user_data: self.user_data.clone(),
-            system_unigram_lm: self.system_unigram_lm.clone(),
-            system_bigram_lm: self.system_bigram_lm.clone(),
+            cost_model: self.cost_model.clone(),
         }
     }
+
+    /// かな漢字辞書に登録されている、yomi に対応する表層のリストを返す。候補から削除された単語は除く。
+    pub(crate) fn get_surfaces(&self, yomi: &str) -> Vec<String> {
+        let mut result: Vec<String> = Vec::new();
+        for word in self.get_user_dict_words(yomi) {
+            let surface = split_annotation(&word).0;
+            if result.iter().any(|it| it == surface) || self.is_blacklisted_word(yomi, surface) {
+                continue;
+            }
+            result.push(surface.to_string());
+        }
+        for dict in &self.system_kana_kanji_dicts {
+            for surface in &lookup_kana_kanji(&dict.dict, yomi) {
+                let surface = split_annotation(surface).0;
+                if result.iter().any(|it| it == surface) || self.is_blacklisted_word(yomi, surface)
+                {
+                    continue;
+                }
+                result.push(surface.to_string());
+            }
+        }
+        result
+    }
+
+    /// ユーザー辞書から読みに対応する候補を引く。
+    /// グラフを作っているあいだ学習などを待たせないように、ユーザーデータは引くたびにロックする。
+    fn get_user_dict_words(&self, yomi: &str) -> Vec<String> {
+        self.user_data
+            .as_ref()
+            .map(|it| it.lock().unwrap().get_user_dict_words(yomi))
+            .unwrap_or_default()
+    }
+
+    /// ユーザーが候補から削除した単語かどうか。ユーザーデータは確認するたびにロックする。
+    fn is_blacklisted_word(&self, yomi: &str, surface: &str) -> bool {
+        self.user_data
+            .as_ref()
+            .is_some_and(|it| it.lock().unwrap().is_blacklisted_word(yomi, surface))
+    }
+
+    /// システム言語モデルの単語IDとスコアを引いた上で WordNode を作成する。
+    pub(crate) fn create_word_node(&self, start_pos: i32, surface: &str, yomi: &str) -> WordNode {
+        WordNode::new(
+            start_pos,
+            surface,
+            yomi,
+            self.system_unigram_lm
+                .find((surface.to_string() + "/" + yomi).as_str()),
+        )
+    }
+}
+
+/// 読みに対応するかな漢字辞書の候補。送りありエントリーの候補は、送り仮名をつけてあとに足す。
+fn lookup_kana_kanji(dict: &DictData, yomi: &str) -> Vec<String> {
+    let mut result = dict.get(yomi).unwrap_or_default();
+    result.extend(okuri_ari_candidates(dict, yomi));
+    result
 }
 
 #[cfg(test)]
 mod tests {
+    use tempfile::NamedTempFile;
+
+    use crate::dict::compiled_dict::CompiledDict;
     use crate::lm::system_bigram::MarisaSystemBigramLMBuilder;
     use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+    use crate::user_side_data::user_dict::UserDictEntry;
 
     use super::*;
 
@@ -140,13 +347,13 @@ mod tests {
             HashMap::new(),
             HashMap::from([("すし".to_string(), vec!["🍣".to_string()])]),
             Arc::new(Mutex::new(UserData::default())),
-            Rc::new(
+            Arc::new(
                 MarisaSystemUnigramLMBuilder::default()
                     .set_default_cost(20_f32)
                     .set_default_cost_for_short(19_f32)
                     .build(),
             ),
-            Rc::new(
+            Arc::new(
                 MarisaSystemBigramLMBuilder::default()
                     .set_default_edge_cost(20_f32)
                     .build()?,
@@ -173,13 +380,13 @@ mod tests {
             HashMap::new(),
             HashMap::new(),
             Arc::new(Mutex::new(UserData::default())),
-            Rc::new(
+            Arc::new(
                 MarisaSystemUnigramLMBuilder::default()
                     .set_default_cost(20_f32)
                     .set_default_cost_for_short(19_f32)
                     .build(),
             ),
-            Rc::new(
+            Arc::new(
                 MarisaSystemBigramLMBuilder::default()
                     .set_default_edge_cost(20_f32)
                     .build()?,
@@ -203,13 +410,13 @@ mod tests {
             HashMap::from([("す".to_string(), vec!["す".to_string(), "ス".to_string()])]),
             HashMap::new(),
             Arc::new(Mutex::new(UserData::default())),
-            Rc::new(
+            Arc::new(
                 MarisaSystemUnigramLMBuilder::default()
                     .set_default_cost(20_f32)
                     .set_default_cost_for_short(19_f32)
                     .build(),
             ),
-            Rc::new(
+            Arc::new(
                 MarisaSystemBigramLMBuilder::default()
                     .set_default_edge_cost(20_f32)
                     .build()?,
@@ -225,4 +432,476 @@ mod tests {
         assert_eq!(got_surfaces, vec!["す".to_string(), "ス".to_string()]);
         Ok(())
     }
+
+    #[test]
+    fn test_numeric_terms() -> anyhow::Result<()> {
+        let graph_builder = GraphBuilder::new_with_default_score(
+            HashMap::from([("こ".to_string(), vec!["子".to_string()])]),
+            HashMap::new(),
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let got = graph_builder.construct(
+            "1234こ",
+            SegmentationResult::new(BTreeMap::from([
+                (4, vec!["1234".to_string()]),
+                (7, vec!["1234こ".to_string(), "こ".to_string()]),
+            ])),
+        );
+        let surfaces = |end_pos: i32| -> Vec<String> {
+            got.node_list(end_pos)
+                .unwrap()
+                .iter()
+                .map(|f| f.surface.to_string())
+                .collect()
+        };
+        assert_eq!(
+            surfaces(4),
+            vec!["1234", "１２３４", "千二百三十四", "一二三四", "1,234"]
+        );
+        assert_eq!(
+            surfaces(7),
+            vec![
+                "1234個",
+                "１２３４個",
+                "千二百三十四個",
+                "一二三四個",
+                "1,234個",
+                "1234こ",
+                "1234コ",
+                "子",
+                "こ",
+                "コ",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_templates() -> anyhow::Result<()> {
+        let graph_builder = GraphBuilder::new_with_default_score(
+            HashMap::from([
+                (
+                    "#がつ#にち".to_string(),
+                    vec!["#0月#0日".to_string(), "#3月#3日".to_string()],
+                ),
+                ("がつ".to_string(), vec!["月".to_string()]),
+            ]),
+            HashMap::new(),
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let got = graph_builder.construct(
+            "12がつ3にち",
+            SegmentationResult::new(BTreeMap::from([(15, vec!["12がつ3にち".to_string()])])),
+        );
+        let surfaces: Vec<String> = got
+            .node_list(15)
+            .unwrap()
+            .iter()
+            .map(|f| f.surface.to_string())
+            .collect();
+        assert_eq!(
+            surfaces,
+            vec!["12月3日", "十二月三日", "12がつ3にち", "12ガツ3ニチ"]
+        );
+        // 数値変換のエントリーは、かな漢字辞書からは取りのぞかれている。
+        assert_eq!(
+            graph_builder.get_surfaces("#がつ#にち"),
+            Vec::<String>::new()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_annotation() -> anyhow::Result<()> {
+        let graph_builder = GraphBuilder::new_with_default_score(
+            HashMap::from([(
+                "とくひろ".to_string(),
+                vec!["徳宏;人名".to_string(), "徳大".to_string()],
+            )]),
+            HashMap::new(),
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let got = graph_builder.construct(
+            "とくひろ",
+            SegmentationResult::new(BTreeMap::from([(12, vec!["とくひろ".to_string()])])),
+        );
+        let nodes: Vec<(String, Option<String>)> = got
+            .node_list(12)
+            .unwrap()
+            .iter()
+            .take(2)
+            .map(|f| (f.surface.to_string(), f.annotation.clone()))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("徳宏".to_string(), Some("人名".to_string())),
+                ("徳大".to_string(), None)
+            ]
+        );
+        Ok(())
+    }
+
+    struct KatakanaFirstCostModel {}
+
+    impl CostModel for KatakanaFirstCostModel {
+        fn get_node_cost(&self, node: &WordNode) -> f32 {
+            if node.surface == "ス" {
+                1_f32
+            } else {
+                10_f32
+            }
+        }
+
+        fn get_edge_cost(&self, _prev: &WordNode, _node: &WordNode) -> f32 {
+            0_f32
+        }
+    }
+
+    // 差し替えたコストモデルが、ラティスのコスト計算に使われる。
+    #[test]
+    fn test_new_with_cost_model() {
+        let graph_builder = GraphBuilder::new_with_cost_model(
+            HashMap::new(),
+            HashMap::new(),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(KatakanaFirstCostModel {}),
+        );
+        let got = graph_builder.construct(
+            "す",
+            SegmentationResult::new(BTreeMap::from([(3, vec!["す".to_string()])])),
+        );
+        let costs: Vec<(String, f32)> = got
+            .node_list(3)
+            .unwrap()
+            .iter()
+            .map(|node| (node.surface.to_string(), got.get_node_cost(node)))
+            .collect();
+        assert_eq!(
+            costs,
+            vec![("す".to_string(), 10_f32), ("ス".to_string(), 1_f32)]
+        );
+    }
+
+    // 優先度の高い辞書の候補が先にならび、辞書の重みがコストに足される。
+    #[test]
+    fn test_sourced_dicts() {
+        let source = |name: &str, priority: i32, weight: f32| {
+            Some(Arc::new(DictSource {
+                name: name.to_string(),
+                show_name: false,
+                priority,
+                weight,
+            }))
+        };
+        let graph_builder = GraphBuilder::new_with_sourced_dicts(
+            vec![
+                SourcedDict::new(
+                    source("SKK-JISYO.L", 0, 0_f32),
+                    HashMap::from([(
+                        "こうし".to_string(),
+                        vec!["講師".to_string(), "格子".to_string()],
+                    )]),
+                ),
+                SourcedDict::new(
+                    source("社内用語", 10, -5_f32),
+                    HashMap::from([(
+                        "こうし".to_string(),
+                        vec!["格子;社内".to_string(), "光士".to_string()],
+                    )]),
+                ),
+            ],
+            vec![],
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(KatakanaFirstCostModel {}),
+        );
+        let got = graph_builder.construct(
+            "こうし",
+            SegmentationResult::new(BTreeMap::from([(9, vec!["こうし".to_string()])])),
+        );
+        let nodes: Vec<(String, Option<String>, f32)> = got
+            .node_list(9)
+            .unwrap()
+            .iter()
+            .take(3)
+            .map(|node| {
+                (
+                    node.surface.to_string(),
+                    node.dict_source.as_ref().map(|it| it.name.to_string()),
+                    got.get_node_cost(node),
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("格子".to_string(), Some("社内用語".to_string()), 5_f32),
+                ("光士".to_string(), Some("社内用語".to_string()), 5_f32),
+                ("講師".to_string(), Some("SKK-JISYO.L".to_string()), 10_f32),
+            ]
+        );
+        assert_eq!(
+            graph_builder.get_surfaces("こうし"),
+            vec!["格子", "光士", "講師"]
+        );
+    }
+
+    #[test]
+    fn test_okuri_ari() -> anyhow::Result<()> {
+        let graph_builder = GraphBuilder::new_with_default_score(
+            HashMap::from([
+                ("かk".to_string(), vec!["書".to_string(), "描".to_string()]),
+                ("かk[け]".to_string(), vec!["掛".to_string()]),
+                ("かけ".to_string(), vec!["賭け".to_string()]),
+            ]),
+            HashMap::new(),
+            Arc::new(Mutex::new(UserData::default())),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let got = graph_builder.construct(
+            "かけ",
+            SegmentationResult::new(BTreeMap::from([(6, vec!["かけ".to_string()])])),
+        );
+        let got_surfaces: Vec<String> = got
+            .node_list(6)
+            .unwrap()
+            .iter()
+            .map(|f| f.surface.to_string())
+            .collect();
+        assert_eq!(
+            got_surfaces,
+            vec!["賭け", "掛け", "書け", "描け", "かけ", "カケ"]
+        );
+        assert_eq!(
+            graph_builder.get_surfaces("かく"),
+            vec!["書く".to_string(), "描く".to_string()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compiled_dict() -> anyhow::Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let fname = tmpfile.path().to_str().unwrap();
+        CompiledDict::save(
+            &HashMap::from([
+                ("かk".to_string(), vec!["書".to_string()]),
+                ("#ねん".to_string(), vec!["#1年".to_string()]),
+            ]),
+            fname,
+        )?;
+        let system_unigram_lm = Arc::new(
+            MarisaSystemUnigramLMBuilder::default()
+                .set_default_cost(20_f32)
+                .set_default_cost_for_short(19_f32)
+                .build(),
+        );
+        let cost_model = Arc::new(DefaultCostModel::new(
+            Arc::new(Mutex::new(UserData::default())),
+            system_unigram_lm.clone(),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        ));
+        // コンパイル済みの辞書は、HashMap に展開せずに引く。
+        let graph_builder = GraphBuilder::new_with_sourced_dicts(
+            vec![SourcedDict::compiled(
+                None,
+                Arc::new(CompiledDict::load(fname)?),
+            )],
+            vec![],
+            system_unigram_lm,
+            cost_model,
+        );
+        assert_eq!(graph_builder.get_surfaces("かく"), vec!["書く".to_string()]);
+        assert_eq!(graph_builder.get_surfaces("#ねん"), Vec::<String>::new());
+        let got = graph_builder.construct(
+            "2ねん",
+            SegmentationResult::new(BTreeMap::from([(7, vec!["2ねん".to_string()])])),
+        );
+        assert_eq!(got.node_list(7).unwrap()[0].surface, "２年");
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_dict() -> anyhow::Result<()> {
+        let mut user_data = UserData::default();
+        user_data.add_word(UserDictEntry::new("あかざ", "赤座"));
+        let graph_builder = GraphBuilder::new_with_default_score(
+            HashMap::from([("あかざ".to_string(), vec!["藜".to_string()])]),
+            HashMap::new(),
+            Arc::new(Mutex::new(user_data)),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let got = graph_builder.construct(
+            "あかざ",
+            SegmentationResult::new(BTreeMap::from([(9, vec!["あかざ".to_string()])])),
+        );
+        let nodes: Vec<(String, Option<String>)> = got
+            .node_list(9)
+            .unwrap()
+            .iter()
+            .take(2)
+            .map(|node| {
+                (
+                    node.surface.to_string(),
+                    node.dict_source.as_ref().map(|it| it.name.to_string()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("赤座".to_string(), Some("user".to_string())),
+                ("藜".to_string(), None),
+            ]
+        );
+        assert_eq!(graph_builder.get_surfaces("あかざ"), vec!["赤座", "藜"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_dict_blacklist() -> anyhow::Result<()> {
+        let mut user_data = UserData::default();
+        user_data.add_word(UserDictEntry {
+            pos: Some("動詞ラ行五段".to_string()),
+            ..UserDictEntry::new("ぐぐる", "ググる")
+        });
+        // 活用形を忘れても、見出し語はユーザー辞書に残る。
+        user_data.forget_word("ぐぐった", "ググった");
+        let graph_builder = GraphBuilder::new_with_default_score(
+            HashMap::new(),
+            HashMap::new(),
+            Arc::new(Mutex::new(user_data)),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let got = graph_builder.construct(
+            "ぐぐった",
+            SegmentationResult::new(BTreeMap::from([(12, vec!["ぐぐった".to_string()])])),
+        );
+        assert!(got
+            .node_list(12)
+            .unwrap()
+            .iter()
+            .all(|node| node.surface != "ググった"));
+        assert!(graph_builder.get_surfaces("ぐぐった").is_empty());
+        assert_eq!(graph_builder.get_surfaces("ぐぐる"), vec!["ググる"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blacklist() -> anyhow::Result<()> {
+        let mut user_data = UserData::default();
+        user_data.forget_word("わたし", "渡し");
+        user_data.forget_word("わたし", "ワタシ");
+        user_data.forget_word("わたし", "わたし");
+        let graph_builder = GraphBuilder::new_with_default_score(
+            HashMap::from([(
+                "わたし".to_string(),
+                vec!["私".to_string(), "渡し".to_string()],
+            )]),
+            HashMap::new(),
+            Arc::new(Mutex::new(user_data)),
+            Arc::new(
+                MarisaSystemUnigramLMBuilder::default()
+                    .set_default_cost(20_f32)
+                    .set_default_cost_for_short(19_f32)
+                    .build(),
+            ),
+            Arc::new(
+                MarisaSystemBigramLMBuilder::default()
+                    .set_default_edge_cost(20_f32)
+                    .build()?,
+            ),
+        );
+        let got = graph_builder.construct(
+            "わたし",
+            SegmentationResult::new(BTreeMap::from([(9, vec!["わたし".to_string()])])),
+        );
+        let got_surfaces: Vec<String> = got
+            .node_list(9)
+            .unwrap()
+            .iter()
+            .map(|node| node.surface.to_string())
+            .collect();
+        // ひらがなそのものは残す。
+        assert_eq!(got_surfaces, vec!["私".to_string(), "わたし".to_string()]);
+        assert_eq!(graph_builder.get_surfaces("わたし"), vec!["私"]);
+        Ok(())
+    }
 }
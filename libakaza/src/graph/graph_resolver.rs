@@ -1,5 +1,6 @@
+use std::cmp::Ordering;
 use std::collections::vec_deque::VecDeque;
-use std::collections::HashMap;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 
 use crate::graph::candidate::Candidate;
 use anyhow::Context;
@@ -8,7 +9,29 @@ use log::trace;
 
 use crate::graph::lattice_graph::LatticeGraph;
 use crate::graph::word_node::WordNode;
-use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
+
+/**
+ * N-best 探索で得られた、文全体の変換結果。
+ */
+#[derive(Debug, Clone)]
+pub struct Sentence {
+    /// 文節ごとの候補。cost には文頭からその文節までの累積コストが入る。
+    pub clauses: Vec<Candidate>,
+    /// 経路全体のコスト(EOS まで含む)
+    pub cost: f32,
+}
+
+impl Sentence {
+    pub fn surface(&self) -> String {
+        self.clauses.iter().map(|c| c.surface.as_str()).collect()
+    }
+}
+
+type PrevMap<'a> = HashMap<&'a WordNode, &'a WordNode>;
+type CostMap<'a> = HashMap<&'a WordNode, f32>;
+type PairKey<'a> = (&'a WordNode, &'a WordNode);
+type PairPrevMap<'a> = HashMap<PairKey<'a>, &'a WordNode>;
+type PairCostMap<'a> = HashMap<PairKey<'a>, f32>;
 
 /**
  * Segmenter により分割されたかな表現から、グラフを構築する。
@@ -20,13 +43,131 @@ impl GraphResolver {
     /**
      * ビタビアルゴリズムで最適な経路を見つける。
      */
-    pub fn resolve<U: SystemUnigramLM, B: SystemBigramLM>(
+    pub fn resolve(&self, lattice: &LatticeGraph) -> anyhow::Result<Vec<VecDeque<Candidate>>> {
+        let (path, costmap) = self.viterbi(lattice)?;
+
+        let mut result: Vec<VecDeque<Candidate>> = Vec::new();
+        for node in path {
+            // 同一の開始位置、終了位置を持つものを集める。
+            let end_pos = node.start_pos + (node.yomi.len() as i32);
+            let mut candidates: VecDeque<Candidate> = lattice
+                .node_list(end_pos)
+                .unwrap()
+                .iter()
+                .filter(|alt_node| {
+                    alt_node.start_pos == node.start_pos
+                        && alt_node.yomi.len() == node.yomi.len()
+                        && alt_node != &node
+                })
+                .map(|f| Candidate {
+                    surface: f.surface.clone(),
+                    yomi: f.yomi.clone(),
+                    cost: *costmap.get(f).unwrap(),
+                    annotation: f.annotation.clone(),
+                    dict_label: f
+                        .dict_source
+                        .as_ref()
+                        .and_then(|it| it.label())
+                        .map(|it| it.to_string()),
+                    single_term: f.single_term,
+                })
+                .collect();
+            candidates
+                .make_contiguous()
+                .sort_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap());
+            candidates.push_front(Candidate {
+                surface: node.surface.clone(),
+                yomi: node.yomi.clone(),
+                cost: *costmap.get(node).unwrap(),
+                annotation: node.annotation.clone(),
+                dict_label: node
+                    .dict_source
+                    .as_ref()
+                    .and_then(|it| it.label())
+                    .map(|it| it.to_string()),
+                single_term: node.single_term,
+            });
+            result.push(candidates);
+        }
+        Ok(result)
+    }
+
+    /**
+     * 最適な経路上のノードを文頭から順に並べたもの(BOS, EOS は含まない)と、
+     * 各ノードまでの最小コストを返す。
+     *
+     * コストモデルが trigram を扱う場合は、直前の2ノードの組を状態とする二次のビタビアルゴリズムを使う。
+     */
+    fn viterbi<'a>(
         &self,
-        lattice: &LatticeGraph<U, B>,
-    ) -> anyhow::Result<Vec<VecDeque<Candidate>>> {
+        lattice: &'a LatticeGraph,
+    ) -> anyhow::Result<(Vec<&'a WordNode>, CostMap<'a>)> {
+        let eos = lattice
+            .get((lattice.yomi.len() + 1) as i32)
+            .unwrap()
+            .first()
+            .unwrap();
+        let bos = lattice.get(0).unwrap().first().unwrap();
+
+        let mut path: Vec<&WordNode> = Vec::new();
+        let costmap = if lattice.uses_trigram() {
+            let (pair_prevmap, pair_costmap) = self.forward_trigram(lattice)?;
+
+            let mut costmap: CostMap = HashMap::new();
+            for ((_, node), cost) in &pair_costmap {
+                let entry = costmap.entry(node).or_insert(f32::MAX);
+                if *cost < *entry {
+                    *entry = *cost;
+                }
+            }
+
+            // EOS の直前のノードのうち、コストが最小のものから後ろ向きに辿る。
+            let mut node = eos;
+            let mut prev = lattice
+                .get_prev_nodes(eos)
+                .with_context(|| format!("Cannot get prev nodes for EOS: {:?}", lattice))?
+                .iter()
+                .min_by(|a, b| {
+                    let a = pair_costmap.get(&(*a, eos)).unwrap_or(&f32::MAX);
+                    let b = pair_costmap.get(&(*b, eos)).unwrap_or(&f32::MAX);
+                    a.total_cmp(b)
+                })
+                .with_context(|| format!("There's no node before EOS: {:?}", lattice))?;
+            while prev != bos {
+                path.push(prev);
+                let prev2 = *pair_prevmap.get(&(prev, node)).with_context(|| {
+                    format!(
+                        "Cannot get previous node: {} {}",
+                        prev.surface, node.surface
+                    )
+                })?;
+                node = prev;
+                prev = prev2;
+            }
+            costmap
+        } else {
+            let (prevmap, costmap) = self.forward(lattice)?;
+
+            let mut node = *prevmap.get(eos).unwrap();
+            while node != bos {
+                path.push(node);
+                node = prevmap
+                    .get(node)
+                    .unwrap_or_else(|| panic!("Cannot get previous node: {}", node.surface));
+            }
+            costmap
+        };
+        path.reverse();
+        Ok((path, costmap))
+    }
+
+    /**
+     * 文頭から各ノードまでの最小コストと、その経路における直前のノードを求める。
+     */
+    fn forward<'a>(&self, lattice: &'a LatticeGraph) -> anyhow::Result<(PrevMap<'a>, CostMap<'a>)> {
         let yomi = &lattice.yomi;
-        let mut prevmap: HashMap<&WordNode, &WordNode> = HashMap::new();
-        let mut costmap: HashMap<&WordNode, f32> = HashMap::new();
+        let mut prevmap: PrevMap = HashMap::new();
+        let mut costmap: CostMap = HashMap::new();
 
         for i in 1..yomi.len() + 2 {
             let Some(nodes) = &lattice.node_list(i as i32) else {
@@ -71,60 +212,232 @@ impl GraphResolver {
             }
         }
 
+        Ok((prevmap, costmap))
+    }
+
+    /**
+     * 二次のビタビアルゴリズムの前向き計算。
+     * 直前のノードと今のノードの組ごとに、文頭からの最小コストと、その経路における2つ前のノードを求める。
+     * 直前のノードが BOS の組には、2つ前のノードはない。
+     */
+    fn forward_trigram<'a>(
+        &self,
+        lattice: &'a LatticeGraph,
+    ) -> anyhow::Result<(PairPrevMap<'a>, PairCostMap<'a>)> {
+        let yomi = &lattice.yomi;
+        let bos = lattice.get(0).unwrap().first().unwrap();
+        let mut prevmap: PairPrevMap = HashMap::new();
+        let mut costmap: PairCostMap = HashMap::new();
+
+        for i in 1..yomi.len() + 2 {
+            let Some(nodes) = &lattice.node_list(i as i32) else {
+                continue;
+            };
+            for node in *nodes {
+                let node_cost = lattice.get_node_cost(node);
+                let prev_nodes = lattice.get_prev_nodes(node).with_context(|| {
+                    format!(
+                        "Cannot get prev nodes for '{}' start={} lattice={:?}",
+                        node.surface, node.start_pos, lattice
+                    )
+                })?;
+                for prev in prev_nodes {
+                    if prev == bos {
+                        costmap.insert((prev, node), lattice.get_edge_cost(prev, node) + node_cost);
+                        continue;
+                    }
+
+                    let mut cost = f32::MAX;
+                    let mut shortest_prev2 = None;
+                    for prev2 in lattice.get_prev_nodes(prev).into_iter().flatten() {
+                        let Some(prev_cost) = costmap.get(&(prev2, prev)) else {
+                            continue;
+                        };
+                        let tmp_cost = prev_cost
+                            + lattice.get_trigram_edge_cost(prev2, prev, node)
+                            + node_cost;
+                        if cost > tmp_cost {
+                            cost = tmp_cost;
+                            shortest_prev2 = Some(prev2);
+                        }
+                    }
+                    let Some(prev2) = shortest_prev2 else {
+                        continue;
+                    };
+                    prevmap.insert((prev, node), prev2);
+                    costmap.insert((prev, node), cost);
+                }
+            }
+        }
+
+        Ok((prevmap, costmap))
+    }
+
+    /**
+     * コストの低い順に、文全体の変換結果を最大 n 件列挙する。
+     *
+     * 前向きにビタビアルゴリズムで (直前のノード, ノード) の組までの最小コストを求めたあと、
+     * それをヒューリスティックとして EOS から後ろ向きに A* 探索する。
+     * 前向きのコストは正確な値なので、取り出された順がそのままコスト順になる。
+     */
+    pub fn resolve_nbest(&self, lattice: &LatticeGraph, n: usize) -> anyhow::Result<Vec<Sentence>> {
+        let yomi = &lattice.yomi;
+        let pair_costmap = if lattice.uses_trigram() {
+            Some(self.forward_trigram(lattice)?.1)
+        } else {
+            None
+        };
+        let costmap = if pair_costmap.is_none() {
+            self.forward(lattice)?.1
+        } else {
+            HashMap::new()
+        };
+        // 文頭から prev を経て node に至るまでの最小コスト(node 自身のコストを含む)。
+        let forward_cost = |prev: &WordNode, node: &WordNode| -> f32 {
+            if let Some(pair_costmap) = &pair_costmap {
+                *pair_costmap.get(&(prev, node)).unwrap_or(&f32::MAX)
+            } else {
+                costmap.get(prev).unwrap_or(&0_f32) // __BOS__ はコスト0
+                    + lattice.get_edge_cost(prev, node)
+                    + lattice.get_node_cost(node)
+            }
+        };
+
         let eos = lattice
             .get((yomi.len() + 1) as i32)
             .unwrap()
-            .get(0)
+            .first()
             .unwrap();
-        let bos = lattice.get(0).unwrap().get(0).unwrap();
-        let mut node = eos;
-        let mut result: Vec<VecDeque<Candidate>> = Vec::new();
-        while node != bos {
+        let bos = lattice.get(0).unwrap().first().unwrap();
+
+        let mut result: Vec<Sentence> = Vec::new();
+        let mut seen: HashSet<Vec<String>> = HashSet::new();
+        let mut queue: BinaryHeap<PartialPath> = BinaryHeap::new();
+        queue.push(PartialPath {
+            nodes: vec![eos],
+            backward_cost: 0_f32,
+            priority: 0_f32,
+        });
+
+        while let Some(path) = queue.pop() {
+            if result.len() >= n {
+                break;
+            }
+
+            let head = *path.nodes.last().unwrap();
+            if head == bos {
+                // 同じ表層・読みの並びは一度だけ返す。
+                let keys: Vec<String> = path.nodes.iter().map(|node| node.key()).collect();
+                if seen.insert(keys) {
+                    result.push(Self::build_sentence(lattice, &path));
+                }
+                continue;
+            }
+
+            // head の直後のノード。head が EOS のときはない。
+            let next = path.nodes.len().checked_sub(2).map(|i| path.nodes[i]);
+            let node_cost = lattice.get_node_cost(head);
+            let prev_nodes = lattice.get_prev_nodes(head).with_context(|| {
+                format!(
+                    "Cannot get prev nodes for '{}' start={} lattice={:?}",
+                    head.surface, head.start_pos, lattice
+                )
+            })?;
+            for prev in prev_nodes {
+                // prev が決まったので、head から next へのエッジのコストが確定する。
+                let backward_cost = path.backward_cost
+                    + node_cost
+                    + next.map_or(0_f32, |next| {
+                        lattice.get_trigram_edge_cost(prev, head, next)
+                    });
+                let priority = forward_cost(prev, head) - node_cost + backward_cost;
+                let mut nodes = path.nodes.clone();
+                nodes.push(prev);
+                queue.push(PartialPath {
+                    nodes,
+                    backward_cost,
+                    priority,
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn build_sentence(lattice: &LatticeGraph, path: &PartialPath) -> Sentence {
+        // path.nodes は EOS -> BOS の順に並んでいるので、BOS -> EOS の順に直す。
+        let nodes: Vec<&WordNode> = path.nodes.iter().rev().copied().collect();
+        let mut clauses: Vec<Candidate> = Vec::new();
+        let mut cost = 0_f32;
+        for i in 1..nodes.len() {
+            let (prev, node) = (nodes[i - 1], nodes[i]);
+            let edge_cost = if i >= 2 {
+                lattice.get_trigram_edge_cost(nodes[i - 2], prev, node)
+            } else {
+                lattice.get_edge_cost(prev, node)
+            };
+            cost += edge_cost + lattice.get_node_cost(node);
             if node.surface != "__EOS__" {
-                // 同一の開始位置、終了位置を持つものを集める。
-                let end_pos = node.start_pos + (node.yomi.len() as i32);
-                let mut candidates: VecDeque<Candidate> = lattice
-                    .node_list(end_pos)
-                    .unwrap()
-                    .iter()
-                    .filter(|alt_node| {
-                        alt_node.start_pos == node.start_pos
-                            && alt_node.yomi.len() == node.yomi.len()
-                            && alt_node != &node
-                    })
-                    .map(|f| Candidate {
-                        surface: f.surface.clone(),
-                        yomi: f.yomi.clone(),
-                        cost: *costmap.get(f).unwrap(),
-                    })
-                    .collect();
-                candidates
-                    .make_contiguous()
-                    .sort_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap());
-                candidates.push_front(Candidate {
+                clauses.push(Candidate {
                     surface: node.surface.clone(),
                     yomi: node.yomi.clone(),
-                    cost: *costmap.get(node).unwrap(),
+                    cost,
+                    annotation: node.annotation.clone(),
+                    dict_label: node
+                        .dict_source
+                        .as_ref()
+                        .and_then(|it| it.label())
+                        .map(|it| it.to_string()),
+                    single_term: node.single_term,
                 });
-                result.push(candidates);
             }
-            node = prevmap
-                .get(node)
-                .unwrap_or_else(|| panic!("Cannot get previous node: {}", node.surface));
         }
-        result.reverse();
-        Ok(result)
+        Sentence { clauses, cost }
+    }
+}
+
+/**
+ * A* 探索中の、EOS から後ろ向きに辿った部分経路。
+ */
+struct PartialPath<'a> {
+    /// EOS から文頭方向に向かって並べたノード
+    nodes: Vec<&'a WordNode>,
+    /// 先頭ノードより後ろのノードのコストと、先頭ノードの直後のノードより後ろのエッジのコストの和。
+    /// 先頭ノードの直後のエッジは、trigram では先頭ノードの前のノードが決まるまでコストが決まらない。
+    backward_cost: f32,
+    /// 文頭から先頭ノードを経て EOS に至る経路の最小コスト
+    priority: f32,
+}
+
+impl PartialEq for PartialPath<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for PartialPath<'_> {}
+
+impl PartialOrd for PartialPath<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PartialPath<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap は最大値から取り出すので、コストの低いものが大きくなるように逆順にする。
+        other.priority.total_cmp(&self.priority)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::btree_map::BTreeMap;
-    use std::rc::Rc;
     use std::sync::{Arc, Mutex};
 
     use anyhow::Result;
 
+    use crate::cost_model::base::CostModel;
     use crate::graph::graph_builder::GraphBuilder;
     use crate::graph::segmenter::{SegmentationResult, Segmenter};
     use crate::kana_trie::cedarwood_kana_trie::CedarwoodKanaTrie;
@@ -168,8 +481,8 @@ mod tests {
             HashMap::new(),
             Default::default(),
             Arc::new(Mutex::new(user_data)),
-            Rc::new(system_unigram_lm),
-            Rc::new(system_bigram_lm),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
         );
         let lattice = graph_builder.construct("abc", graph);
         let resolver = GraphResolver::default();
@@ -222,8 +535,8 @@ mod tests {
             dict,
             HashMap::new(),
             Arc::new(Mutex::new(user_data)),
-            Rc::new(system_unigram_lm),
-            Rc::new(system_bigram_lm),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
         );
         let lattice = graph_builder.construct(&yomi, graph);
         // dot -Tpng -o /tmp/lattice.png /tmp/lattice.dot && open /tmp/lattice.png
@@ -238,4 +551,192 @@ mod tests {
         assert_eq!(result, "私");
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_nbest() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let kana_trie = CedarwoodKanaTrie::build(Vec::from([
+            "わたし".to_string(),
+            "わた".to_string(),
+            "し".to_string(),
+        ]));
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("わたし", None);
+
+        let dict = HashMap::from([(
+            "わたし".to_string(),
+            vec!["私".to_string(), "渡し".to_string()],
+        )]);
+
+        let system_unigram_lm = MarisaSystemUnigramLMBuilder::default()
+            .set_default_cost(19_f32)
+            .set_default_cost_for_short(20_f32)
+            .build();
+        let system_bigram_lm = MarisaSystemBigramLMBuilder::default()
+            .set_default_edge_cost(20_f32)
+            .build()?;
+        let mut user_data = UserData::default();
+        user_data.record_entries(&[Candidate::new("わたし", "私", 0_f32)]);
+        let graph_builder = GraphBuilder::new_with_default_score(
+            dict,
+            HashMap::new(),
+            Arc::new(Mutex::new(user_data)),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
+        );
+        let lattice = graph_builder.construct("わたし", graph);
+        let resolver = GraphResolver::default();
+
+        // 最良の経路は resolve と一致する。
+        let got = resolver.resolve_nbest(&lattice, 3)?;
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[0].surface(), "私");
+        for i in 1..got.len() {
+            assert!(got[i - 1].cost <= got[i].cost);
+            assert_ne!(got[i - 1].surface(), got[i].surface());
+        }
+
+        // わたし(私, 渡し, わたし, ワタシ) と わた(わた, ワタ)+し(し, シ) の 8 通り。
+        let got = resolver.resolve_nbest(&lattice, 100)?;
+        assert_eq!(got.len(), 8);
+        assert!(got
+            .iter()
+            .any(|sentence| sentence.clauses.len() == 2 && sentence.surface() == "ワタし"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailing_consonant() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let kana_trie = CedarwoodKanaTrie::build(Vec::from([
+            "わたし".to_string(),
+            "わた".to_string(),
+            "し".to_string(),
+        ]));
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("わたしk", None);
+
+        let dict = HashMap::from([(
+            "わたし".to_string(),
+            vec!["私".to_string(), "渡し".to_string()],
+        )]);
+
+        let system_unigram_lm = MarisaSystemUnigramLMBuilder::default()
+            .set_default_cost(19_f32)
+            .set_default_cost_for_short(20_f32)
+            .build();
+        let system_bigram_lm = MarisaSystemBigramLMBuilder::default()
+            .set_default_edge_cost(20_f32)
+            .build()?;
+        let mut user_data = UserData::default();
+        user_data.record_entries(&[Candidate::new("わたし", "私", 0_f32)]);
+        let graph_builder = GraphBuilder::new_with_default_score(
+            dict,
+            HashMap::new(),
+            Arc::new(Mutex::new(user_data)),
+            Arc::new(system_unigram_lm),
+            Arc::new(system_bigram_lm),
+        );
+        let lattice = graph_builder.construct("わたしk", graph);
+        let resolver = GraphResolver::default();
+        let got = resolver.resolve(&lattice)?;
+        let terms: Vec<String> = got.iter().map(|f| f[0].surface.clone()).collect();
+        assert_eq!(terms, vec!["私", "k"]);
+        // 子音の文節には、カタカナなどの候補を作らない。
+        assert_eq!(got[1].len(), 1);
+        Ok(())
+    }
+
+    /// 「あ い」の後ろにだけ「鵜」が続きやすい、という trigram を持つコストモデル。
+    struct UkaiCostModel {
+        uses_trigram: bool,
+    }
+
+    impl CostModel for UkaiCostModel {
+        fn get_node_cost(&self, node: &WordNode) -> f32 {
+            match node.surface.as_str() {
+                "鵜" => 2_f32,
+                "う" => 1.5_f32,
+                "ア" | "イ" | "ウ" => 3_f32,
+                _ => 1_f32,
+            }
+        }
+
+        fn get_edge_cost(&self, _prev: &WordNode, _node: &WordNode) -> f32 {
+            0_f32
+        }
+
+        fn get_trigram_edge_cost(&self, prev2: &WordNode, prev: &WordNode, node: &WordNode) -> f32 {
+            if prev2.surface == "あ" && prev.surface == "い" && node.surface == "鵜" {
+                -5_f32
+            } else {
+                self.get_edge_cost(prev, node)
+            }
+        }
+
+        fn uses_trigram(&self) -> bool {
+            self.uses_trigram
+        }
+    }
+
+    #[test]
+    fn test_trigram() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let kana_trie = CedarwoodKanaTrie::build(Vec::from([
+            "あ".to_string(),
+            "い".to_string(),
+            "う".to_string(),
+        ]));
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let dict = HashMap::from([("う".to_string(), vec!["卯".to_string(), "鵜".to_string()])]);
+        let system_unigram_lm = Arc::new(
+            MarisaSystemUnigramLMBuilder::default()
+                .set_default_cost(20_f32)
+                .set_default_cost_for_short(19_f32)
+                .build(),
+        );
+        let resolver = GraphResolver::default();
+
+        // 直前の1単語しか見なければ、コストの低い「卯」が選ばれる。
+        let graph_builder = GraphBuilder::new_with_cost_model(
+            dict.clone(),
+            HashMap::new(),
+            system_unigram_lm.clone(),
+            Arc::new(UkaiCostModel {
+                uses_trigram: false,
+            }),
+        );
+        let lattice = graph_builder.construct("あいう", segmenter.build("あいう", None));
+        let got = resolver.resolve(&lattice)?;
+        let terms: Vec<String> = got.iter().map(|f| f[0].surface.clone()).collect();
+        assert_eq!(terms.join("/"), "あ/い/卯");
+
+        // 2つ前の単語まで見ると「鵜」が選ばれる。
+        let graph_builder = GraphBuilder::new_with_cost_model(
+            dict,
+            HashMap::new(),
+            system_unigram_lm,
+            Arc::new(UkaiCostModel { uses_trigram: true }),
+        );
+        let lattice = graph_builder.construct("あいう", segmenter.build("あいう", None));
+        let got = resolver.resolve(&lattice)?;
+        let terms: Vec<String> = got.iter().map(|f| f[0].surface.clone()).collect();
+        assert_eq!(terms.join("/"), "あ/い/鵜");
+        // 「う」の候補は、文頭からのコストの低い順に並ぶ。
+        let candidates: Vec<String> = got[2].iter().map(|c| c.surface.clone()).collect();
+        assert_eq!(candidates, vec!["鵜", "卯", "う", "ウ"]);
+
+        // N-best の最良の経路も、同じく trigram を考慮したものになる。
+        let got = resolver.resolve_nbest(&lattice, 3)?;
+        assert_eq!(got[0].surface(), "あい鵜");
+        assert_eq!(got[0].cost, 0_f32);
+        assert_eq!(got[1].surface(), "あい卯");
+        for i in 1..got.len() {
+            assert!(got[i - 1].cost <= got[i].cost);
+        }
+        Ok(())
+    }
 }
@@ -1,24 +1,20 @@
 use std::collections::btree_map::BTreeMap;
 use std::fmt::{Debug, Formatter};
-use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use log::{error, trace};
 
+use crate::cost_model::base::CostModel;
 use crate::graph::word_node::WordNode;
-use crate::lm::base::{SystemBigramLM, SystemUnigramLM};
-use crate::user_side_data::user_data::UserData;
 
 // 考えられる単語の列全てを含むようなグラフ構造
-pub struct LatticeGraph<U: SystemUnigramLM, B: SystemBigramLM> {
+pub struct LatticeGraph {
     pub(crate) yomi: String,
     pub(crate) graph: BTreeMap<i32, Vec<WordNode>>,
-    pub(crate) user_data: Arc<Mutex<UserData>>,
-    pub(crate) system_unigram_lm: Rc<U>,
-    pub(crate) system_bigram_lm: Rc<B>,
+    pub(crate) cost_model: Arc<dyn CostModel>,
 }
 
-impl<U: SystemUnigramLM, B: SystemBigramLM> Debug for LatticeGraph<U, B> {
+impl Debug for LatticeGraph {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
@@ -28,7 +24,7 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> Debug for LatticeGraph<U, B> {
     }
 }
 
-impl<U: SystemUnigramLM, B: SystemBigramLM> LatticeGraph<U, B> {
+impl LatticeGraph {
     /// i文字目で終わるノードを探す
     pub fn node_list(&self, end_pos: i32) -> Option<&Vec<WordNode>> {
         self.graph.get(&end_pos)
@@ -121,39 +117,24 @@ impl<U: SystemUnigramLM, B: SystemBigramLM> LatticeGraph<U, B> {
     }
 
     pub(crate) fn get_node_cost(&self, node: &WordNode) -> f32 {
-        if let Some(user_cost) = self.user_data.lock().unwrap().get_unigram_cost(node) {
-            // use user's score. if it's exists.
-            return user_cost;
-        }
-
-        return if let Some((_, system_unigram_cost)) = node.word_id_and_score {
-            trace!("HIT!: {}, {}", node.key(), system_unigram_cost);
-            system_unigram_cost
-        } else if node.surface.len() < node.yomi.len() {
-            // 労働者災害補償保険法 のように、システム辞書には wikipedia から採録されているが,
-            // 言語モデルには採録されていない場合,漢字候補を先頭に持ってくる。
-            // つまり、変換後のほうが短くなるもののほうをコストを安くしておく。
-            self.system_unigram_lm.get_default_cost_for_short()
-        } else {
-            self.system_unigram_lm.get_default_cost()
-        };
+        // 辞書ごとの重みは、どのコストモデルでも同じように足す。
+        self.cost_model.get_node_cost(node) + node.dict_weight()
     }
 
     pub(crate) fn get_edge_cost(&self, prev: &WordNode, node: &WordNode) -> f32 {
-        if let Some(cost) = self.user_data.lock().unwrap().get_bigram_cost(prev, node) {
-            return cost;
-        }
+        self.cost_model.get_edge_cost(prev, node)
+    }
 
-        let Some((prev_id, _)) = prev.word_id_and_score else {
-            return self.system_bigram_lm.get_default_edge_cost();
-        };
-        let Some((node_id, _)) = node.word_id_and_score else {
-            return self.system_bigram_lm.get_default_edge_cost();
-        };
-        if let Some(cost) = self.system_bigram_lm.get_edge_cost(prev_id, node_id) {
-            cost
-        } else {
-            self.system_bigram_lm.get_default_edge_cost()
-        }
+    pub(crate) fn get_trigram_edge_cost(
+        &self,
+        prev2: &WordNode,
+        prev: &WordNode,
+        node: &WordNode,
+    ) -> f32 {
+        self.cost_model.get_trigram_edge_cost(prev2, prev, node)
+    }
+
+    pub(crate) fn uses_trigram(&self) -> bool {
+        self.cost_model.uses_trigram()
     }
 }
@@ -5,7 +5,12 @@ use std::sync::{Arc, Mutex};
 
 use log::{debug, info, trace};
 
+use crate::char_type::CharType;
+use crate::dict::skk::okuri::{is_okuri_ari_yomi, okuri_ari_keys, split_okuri_block_key};
 use crate::kana_trie::base::KanaTrie;
+use crate::numeric::{
+    counter_prefixes, leading_digits, numeric_template_key, restore_numeric_prefix,
+};
 
 #[derive(PartialEq, Debug)]
 pub struct SegmentationResult {
@@ -36,6 +41,62 @@ impl SegmentationResult {
     }
 }
 
+/**
+ * ローマ字入力の途中で、末尾にひらがなになっていない子音が残っている場合に、
+ * (子音より前の部分, 末尾の子音) に分割する。
+ *
+ * 例: "かんじk" → ("かんじ", "k")
+ *
+ * N は NN だと「ん」になるので、末尾に単独で残っている場合は子音としては扱わない。
+ * "かny" のように子音が続いている場合は、「にゃ」などの入力途中なので子音に含める。
+ * "abc" のように直前が ASCII の場合は、ローマ字の入力途中ではないので分割しない。
+ */
+pub(crate) fn split_trailing_consonant(yomi: &str) -> (&str, &str) {
+    let body = yomi.trim_end_matches(|c: char| "qwrtypsdfghjklzxcvbm".contains(c));
+    let body = match body.strip_suffix('n') {
+        Some(stem) if body.len() < yomi.len() => stem,
+        _ => body,
+    };
+    if body.chars().last().is_some_and(|c| c.is_ascii()) {
+        return (yomi, "");
+    }
+    yomi.split_at(body.len())
+}
+
+/**
+ * yomi の先頭から、カタカナ・英字・数字のいずれかが続いている部分を返す。
+ * 2文字以上続いていなければ None。
+ *
+ * ローマ字入力では外来語もひらがなで入ってくるので、長音記号をふくむひらがなの並びは、
+ * 最後の長音記号までを外来語とみなす。
+ *
+ * 例: "コンピューターを" → "コンピューター"
+ *     "こんぴゅーたーを" → "こんぴゅーたー"
+ */
+pub(crate) fn unknown_word_run(yomi: &str) -> Option<&str> {
+    let first = CharType::of(yomi.chars().next()?);
+    let len = match first {
+        CharType::Katakana | CharType::Alphabet | CharType::Number => yomi
+            .chars()
+            .take_while(|c| CharType::of(*c) == first)
+            .map(|c| c.len_utf8())
+            .sum(),
+        CharType::Hiragana => {
+            let run = yomi
+                .char_indices()
+                .take_while(|(_, c)| *c == 'ー' || CharType::of(*c) == CharType::Hiragana)
+                .filter(|(_, c)| *c == 'ー')
+                .last()?;
+            run.0 + 'ー'.len_utf8()
+        }
+        _ => return None,
+    };
+    if yomi[..len].chars().count() < 2 {
+        return None;
+    }
+    Some(&yomi[..len])
+}
+
 pub struct Segmenter {
     tries: Vec<Arc<Mutex<dyn KanaTrie>>>,
 }
@@ -66,6 +127,21 @@ impl Segmenter {
             }
         }
 
+        // 末尾に残っている子音は辞書引きに使わず、最後に1文節として追加する。
+        // ただし、ユーザーが子音を含む範囲を明示的に選択している場合はそちらを優先する。
+        let (body, consonant) = split_trailing_consonant(yomi);
+        let consonant = if force_ranges
+            .unwrap_or_default()
+            .iter()
+            .any(|force_range| force_range.end > body.len())
+        {
+            ""
+        } else {
+            consonant
+        };
+        let full_yomi = yomi;
+        let yomi = &yomi[..yomi.len() - consonant.len()];
+
         let mut queue: Vec<usize> = Vec::new(); // 検索対象となる開始位置
         queue.push(0);
         let mut seen: HashSet<usize> = HashSet::new();
@@ -102,28 +178,55 @@ impl Segmenter {
                 continue;
             }
 
-            let mut candidates: HashSet<String> = HashSet::new();
+            let mut words: Vec<String> = Vec::new();
             for trie in &self.tries {
                 let got = trie.lock().unwrap().common_prefix_search(yomi);
                 debug!("Common prefix search: {:?}", got);
-                'insert: for word in got {
-                    let ends_at = start_pos + word.len();
-
-                    // end_pos が force の範囲に入っていたら処理しない。
-                    if let Some(force_ranges) = force_ranges {
-                        for force_range in force_ranges {
-                            // force_range は exclusive で、厳しい。
-                            if force_range.contains(&ends_at) || force_range.end == ends_at {
-                                trace!("Blocked candidate range: {}, {:?}", word, force_range);
-                                continue 'insert;
-                            } else {
-                                trace!("Accepted candidate range: {}, {:?}", word, force_range);
-                            }
+                words.extend(got);
+            }
+            // 数字を含む場合は、"#ねん" のような数値変換のエントリーも探す。
+            if let Some((key, _)) = numeric_template_key(yomi) {
+                for trie in &self.tries {
+                    for got in trie.lock().unwrap().common_prefix_search(&key) {
+                        if got.contains('#') {
+                            words.push(restore_numeric_prefix(yomi, &got).to_string());
                         }
                     }
+                }
+            }
+            // 送りありエントリーは "かk" のようなキーで入っているので、語幹に続くかなを見て探す。
+            words.extend(self.okuri_ari_words(yomi));
+            // カタカナ・英字・数字が続いている場合は、辞書になくてもひとかたまりの未知語として扱う。
+            if let Some(run) = unknown_word_run(yomi) {
+                trace!("Unknown word run: {}", run);
+                words.push(run.to_string());
+            }
+            // 数字は1文字でもひとかたまりにする。助数詞が続いていれば、それも含めて1文節にする。
+            if let Some(digits) = leading_digits(yomi) {
+                words.push(digits.to_string());
+                for counter in counter_prefixes(&yomi[digits.len()..]) {
+                    words.push(format!("{}{}", digits, counter));
+                }
+            }
 
-                    candidates.insert(word);
+            let mut candidates: HashSet<String> = HashSet::new();
+            'insert: for word in words {
+                let ends_at = start_pos + word.len();
+
+                // end_pos が force の範囲に入っていたら処理しない。
+                if let Some(force_ranges) = force_ranges {
+                    for force_range in force_ranges {
+                        // force_range は exclusive で、厳しい。
+                        if force_range.contains(&ends_at) || force_range.end == ends_at {
+                            trace!("Blocked candidate range: {}, {:?}", word, force_range);
+                            continue 'insert;
+                        } else {
+                            trace!("Accepted candidate range: {}, {:?}", word, force_range);
+                        }
+                    }
                 }
+
+                candidates.insert(word);
             }
             if !candidates.is_empty() {
                 for candidate in &candidates {
@@ -152,10 +255,59 @@ impl Segmenter {
             }
         }
 
+        if !consonant.is_empty() {
+            words_ends_at
+                .entry(full_yomi.len())
+                .or_default()
+                .push(consonant.to_string());
+        }
+
         SegmentationResult {
             base: words_ends_at,
         }
     }
+
+    /**
+     * yomi の先頭から、送りありエントリーの語幹と送り仮名1文字になっている部分を列挙する。
+     *
+     * 例: "かいた" に "かi" のエントリーがあれば "かい"
+     */
+    fn okuri_ari_words(&self, yomi: &str) -> Vec<String> {
+        let mut words: Vec<String> = Vec::new();
+        for (i, c) in yomi.char_indices().skip(1) {
+            let word = &yomi[..i + c.len_utf8()];
+            let found = okuri_ari_keys(word).iter().any(|(key, _)| {
+                self.tries
+                    .iter()
+                    .any(|trie| trie.lock().unwrap().common_prefix_search(key).contains(key))
+            });
+            if found {
+                trace!("Okuri-ari word: {}", word);
+                words.push(word.to_string());
+            }
+        }
+        words
+    }
+
+    /**
+     * yomi で始まる読みを、登録されているトライから列挙する。
+     */
+    pub(crate) fn predictive_search(&self, yomi: &str) -> Vec<String> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut result: Vec<String> = Vec::new();
+        for trie in &self.tries {
+            for word in trie.lock().unwrap().predictive_search(yomi) {
+                // 送りありエントリーのキーは、読みとしては使えない。
+                if is_okuri_ari_yomi(&word) || split_okuri_block_key(&word).is_some() {
+                    continue;
+                }
+                if seen.insert(word.clone()) {
+                    result.push(word);
+                }
+            }
+        }
+        result
+    }
 }
 
 #[cfg(test)]
@@ -225,4 +377,142 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_split_trailing_consonant() {
+        assert_eq!(split_trailing_consonant("かんじk"), ("かんじ", "k"));
+        assert_eq!(split_trailing_consonant("かんじsh"), ("かんじ", "sh"));
+        assert_eq!(split_trailing_consonant("かんじ"), ("かんじ", ""));
+        // n は「ん」になりうるので子音として扱わない。
+        assert_eq!(split_trailing_consonant("かんじn"), ("かんじn", ""));
+        assert_eq!(split_trailing_consonant("k"), ("", "k"));
+        assert_eq!(split_trailing_consonant("かny"), ("か", "ny"));
+        assert_eq!(split_trailing_consonant("かんk"), ("かん", "k"));
+        assert_eq!(split_trailing_consonant("abc"), ("abc", ""));
+    }
+
+    #[test]
+    fn test_trailing_consonant() {
+        let kana_trie = CedarwoodKanaTrie::build(vec![
+            "わたし".to_string(),
+            "わた".to_string(),
+            "し".to_string(),
+            // 子音が辞書引きに使われてしまうと、こういうエントリーにマッチしてしまう。
+            "k".to_string(),
+        ]);
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("わたしk", None);
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (6, vec!["わた".to_string()]),
+                (9, vec!["わたし".to_string(), "し".to_string()]),
+                (10, vec!["k".to_string()]),
+            ]))
+        )
+    }
+
+    #[test]
+    fn test_unknown_word_run() {
+        assert_eq!(unknown_word_run("コンピューターを"), Some("コンピューター"));
+        assert_eq!(unknown_word_run("2023ねん"), Some("2023"));
+        assert_eq!(unknown_word_run("abcあ"), Some("abc"));
+        assert_eq!(unknown_word_run("aあ"), None);
+        assert_eq!(unknown_word_run("かな"), None);
+        assert_eq!(unknown_word_run("こんぴゅーたーを"), Some("こんぴゅーたー"));
+        assert_eq!(unknown_word_run("さーばーに"), Some("さーばー"));
+        assert_eq!(unknown_word_run("ーあ"), None);
+        assert_eq!(unknown_word_run(""), None);
+    }
+
+    #[test]
+    fn test_unknown_word_segment() {
+        let kana_trie = CedarwoodKanaTrie::build(vec!["ねん".to_string()]);
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("2023ねん", None);
+        // 1文字ずつに分かれずに、数字の並びが1つの文節の候補になる。
+        // 助数詞の「ねん」が続いているので、「2023ねん」も1つの文節の候補になる。
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (4, vec!["2023".to_string()]),
+                (10, vec!["2023ねん".to_string(), "ねん".to_string()]),
+            ]))
+        )
+    }
+
+    #[test]
+    fn test_numeric_template_segment() {
+        let kana_trie = CedarwoodKanaTrie::build(vec!["#がつ#にち".to_string()]);
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("12がつ3にち", None);
+        assert!(graph
+            .iter()
+            .any(|(end_pos, yomis)| *end_pos == 15 && yomis.contains(&"12がつ3にち".to_string())));
+    }
+
+    #[test]
+    fn test_literal_sharp_segment() {
+        // 読みの '#' を数値変換の '#' と取り違えて、空の文節を作らないこと。
+        let kana_trie = CedarwoodKanaTrie::build(vec!["#".to_string(), "##".to_string()]);
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("#1", None);
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (1, vec!["#".to_string()]),
+                (2, vec!["1".to_string()]),
+            ]))
+        )
+    }
+
+    #[test]
+    fn test_counter_segment() {
+        let kana_trie = CedarwoodKanaTrie::build(vec![]);
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("3こ", None);
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (1, vec!["3".to_string()]),
+                (4, vec!["3こ".to_string(), "こ".to_string()]),
+            ]))
+        )
+    }
+
+    #[test]
+    fn test_trailing_consonant_without_kanatrie() {
+        let kana_trie = CedarwoodKanaTrie::build(vec![]);
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("かsh", None);
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (3, vec!["か".to_string()]),
+                (5, vec!["sh".to_string()]),
+            ]))
+        )
+    }
+
+    #[test]
+    fn test_okuri_ari_segment() {
+        let kana_trie = CedarwoodKanaTrie::build(vec!["かi".to_string(), "た".to_string()]);
+
+        let segmenter = Segmenter::new(vec![Arc::new(Mutex::new(kana_trie))]);
+        let graph = segmenter.build("かいた", None);
+        assert_eq!(
+            graph,
+            SegmentationResult::new(BTreeMap::from([
+                (6, vec!["かい".to_string()]),
+                (9, vec!["た".to_string()]),
+            ]))
+        );
+        assert!(segmenter.predictive_search("か").is_empty());
+    }
 }
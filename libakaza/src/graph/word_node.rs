@@ -1,5 +1,8 @@
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::dict::source::DictSource;
 
 #[derive(Debug, Clone)]
 pub struct WordNode {
@@ -10,6 +13,12 @@ pub struct WordNode {
     pub yomi: String,
     pub cost: f32,
     pub word_id_and_score: Option<(i32, f32)>,
+    /// single term 辞書から引いた候補かどうか。
+    pub single_term: bool,
+    /// 辞書に書かれていたアノテーション。
+    pub annotation: Option<String>,
+    /// 候補を引いた辞書。辞書以外から作られたノードでは None。
+    pub dict_source: Option<Arc<DictSource>>,
 }
 
 impl Hash for WordNode {
@@ -33,6 +42,14 @@ impl PartialEq<Self> for WordNode {
 impl Eq for WordNode {}
 
 impl WordNode {
+    /// 辞書に指定された、コストに足す値。
+    pub fn dict_weight(&self) -> f32 {
+        self.dict_source
+            .as_ref()
+            .map(|it| it.weight)
+            .unwrap_or(0_f32)
+    }
+
     pub fn key(&self) -> String {
         let mut buf = String::new();
         buf += self.surface.as_str();
@@ -48,6 +65,9 @@ impl WordNode {
             yomi: "__BOS__".to_string(),
             cost: 0_f32,
             word_id_and_score: None,
+            single_term: false,
+            annotation: None,
+            dict_source: None,
         }
     }
     pub(crate) fn create_eos(start_pos: i32) -> WordNode {
@@ -57,6 +77,9 @@ impl WordNode {
             yomi: "__EOS__".to_string(),
             cost: 0_f32,
             word_id_and_score: None,
+            single_term: false,
+            annotation: None,
+            dict_source: None,
         }
     }
     pub fn new(
@@ -78,6 +101,9 @@ impl WordNode {
             yomi: yomi.to_string(),
             cost: 0_f32,
             word_id_and_score,
+            single_term: false,
+            annotation: None,
+            dict_source: None,
         }
     }
 }
@@ -1,3 +1,6 @@
-pub trait KanaTrie {
+pub trait KanaTrie: Send {
     fn common_prefix_search(&self, query: &str) -> Vec<String>;
+
+    /// query で始まる読みを列挙する。予測変換で利用する。
+    fn predictive_search(&self, query: &str) -> Vec<String>;
 }
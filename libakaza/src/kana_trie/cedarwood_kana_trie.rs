@@ -38,6 +38,10 @@ impl CedarwoodKanaTrie {
         self.cedar.update(key, self.words.len() as i32);
         self.words.push(key.to_string());
     }
+
+    pub fn erase(&mut self, key: &str) {
+        self.cedar.erase(key);
+    }
 }
 
 impl KanaTrie for CedarwoodKanaTrie {
@@ -48,6 +52,14 @@ impl KanaTrie for CedarwoodKanaTrie {
             .map(|(n, _)| self.words[n as usize].clone())
             .collect::<Vec<String>>()
     }
+
+    fn predictive_search(&self, query: &str) -> Vec<String> {
+        debug!("Predictive search with CedarwoodKanaTrie: {}", query);
+        self.cedar
+            .common_prefix_predict_iter(query)
+            .map(|(n, _)| self.words[n as usize].clone())
+            .collect::<Vec<String>>()
+    }
 }
 
 #[cfg(test)]
@@ -68,4 +80,19 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_predictive_search() -> anyhow::Result<()> {
+        let trie = CedarwoodKanaTrie::build(vec![
+            "わたし".to_string(),
+            "わた".to_string(),
+            "わし".to_string(),
+            "ほげほげ".to_string(),
+        ]);
+        let mut got = trie.predictive_search("わた");
+        got.sort();
+        assert_eq!(got, vec!("わた", "わたし"));
+        assert!(trie.predictive_search("ふが").is_empty());
+        Ok(())
+    }
 }
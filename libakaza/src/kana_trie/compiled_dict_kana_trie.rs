@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use log::debug;
+
+use crate::dict::compiled_dict::CompiledDict;
+use crate::kana_trie::base::KanaTrie;
+
+/// コンパイル済みの辞書の読みを、そのままトライとして引く。
+pub struct CompiledDictKanaTrie {
+    dict: Arc<CompiledDict>,
+}
+
+impl CompiledDictKanaTrie {
+    pub fn new(dict: Arc<CompiledDict>) -> CompiledDictKanaTrie {
+        CompiledDictKanaTrie { dict }
+    }
+}
+
+impl KanaTrie for CompiledDictKanaTrie {
+    fn common_prefix_search(&self, query: &str) -> Vec<String> {
+        debug!("Search with CompiledDictKanaTrie: {}", query);
+        self.dict.common_prefix_yomis(query)
+    }
+
+    fn predictive_search(&self, query: &str) -> Vec<String> {
+        debug!("Predictive search with CompiledDictKanaTrie: {}", query);
+        self.dict.predictive_yomis(query)
+    }
+}
@@ -4,3 +4,4 @@
  */
 pub mod base;
 pub mod cedarwood_kana_trie;
+pub mod compiled_dict_kana_trie;
@@ -2,16 +2,22 @@
 
 extern crate core;
 
+pub mod char_type;
 pub mod config;
+pub mod conjugation;
 pub mod corpus;
 pub mod cost;
+pub mod cost_model;
 pub mod dict;
+pub mod dynamic_candidate;
 pub mod engine;
 pub mod extend_clause;
 pub mod graph;
 pub mod kana;
 pub mod kana_trie;
 pub mod lm;
+pub mod numeric;
 pub mod romkan;
 pub mod trie;
 pub mod user_side_data;
+pub mod wareki;
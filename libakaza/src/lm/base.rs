@@ -1,15 +1,24 @@
 use std::collections::HashMap;
 
-pub trait SystemBigramLM {
+/// 変換エンジンを複数のスレッドから共有できるように、言語モデルは Send + Sync であること。
+pub trait SystemBigramLM: Send + Sync {
     fn get_default_edge_cost(&self) -> f32;
     fn get_edge_cost(&self, word_id1: i32, word_id2: i32) -> Option<f32>;
     fn as_hash_map(&self) -> HashMap<(i32, i32), f32>;
 }
 
-pub trait SystemUnigramLM {
+/// 直前の2単語を見たときに、バイグラムのエッジコストに加える補正値を返す。
+pub trait SystemTrigramLM: Send + Sync {
+    fn get_trigram_cost(&self, word_id1: i32, word_id2: i32, word_id3: i32) -> Option<f32>;
+    fn as_hash_map(&self) -> HashMap<(i32, i32, i32), f32>;
+}
+
+pub trait SystemUnigramLM: Send + Sync {
     fn get_default_cost(&self) -> f32;
     fn get_default_cost_for_short(&self) -> f32;
 
     fn find(&self, word: &str) -> Option<(i32, f32)>;
+    /// word に続くバイグラムが採録されていないときに、後ろの単語のユニグラムのコストに足すコスト。
+    fn get_backoff_cost(&self, word: &str) -> Option<f32>;
     fn as_hash_map(&self) -> HashMap<String, (i32, f32)>;
 }
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::iter::once;
+
+use crate::lm::base::SystemUnigramLM;
+
+/*
+   未知語のコストを見積もるための、読みの文字バイグラム。
+   単語IDがずれないように、ユニグラム言語モデルを作るときに同じ trie に格納する。
+
+   __CHAR_BIGRAM__{prev}{c}  # -log10 P(c|prev)
+*/
+
+const CHAR_BIGRAM_PREFIX: &str = "__CHAR_BIGRAM__";
+// 語頭と語末をあらわす文字。読みには出てこない制御文字を使う。
+const BOW: char = '\u{2}';
+const EOW: char = '\u{3}';
+
+pub fn char_bigram_key(prev: char, c: char) -> String {
+    format!("{}{}{}", CHAR_BIGRAM_PREFIX, prev, c)
+}
+
+/// "表層/読み" ごとの出現回数から、読みの文字バイグラムのコストを求める。
+pub fn make_char_bigram_costs(wordcnt: &HashMap<String, u32>) -> HashMap<String, f32> {
+    let mut bigram_counts: HashMap<(char, char), u32> = HashMap::new();
+    let mut context_counts: HashMap<char, u32> = HashMap::new();
+    for (word, cnt) in wordcnt {
+        let Some((_, yomi)) = word.rsplit_once('/') else {
+            continue;
+        };
+        let chars = once(BOW)
+            .chain(yomi.chars())
+            .chain(once(EOW))
+            .collect::<Vec<_>>();
+        for w in chars.windows(2) {
+            *bigram_counts.entry((w[0], w[1])).or_insert(0) += cnt;
+            *context_counts.entry(w[0]).or_insert(0) += cnt;
+        }
+    }
+    bigram_counts
+        .iter()
+        .map(|((prev, c), cnt)| {
+            let p = *cnt as f32 / context_counts[prev] as f32;
+            (char_bigram_key(*prev, *c), -f32::log10(p))
+        })
+        .collect::<HashMap<_, _>>()
+}
+
+/// 文字バイグラムで見積もった、読みのコスト。登録されていない文字の並びがあれば None。
+pub fn char_ngram_cost<U: SystemUnigramLM + ?Sized>(
+    system_unigram_lm: &U,
+    yomi: &str,
+) -> Option<f32> {
+    let chars = once(BOW)
+        .chain(yomi.chars())
+        .chain(once(EOW))
+        .collect::<Vec<_>>();
+    let mut cost = 0_f32;
+    for w in chars.windows(2) {
+        let (_, c) = system_unigram_lm.find(&char_bigram_key(w[0], w[1]))?;
+        cost += c;
+    }
+    Some(cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lm::system_unigram_lm::MarisaSystemUnigramLMBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_char_ngram_cost() {
+        let costs = make_char_bigram_costs(&HashMap::from([
+            ("蟻/あり".to_string(), 3),
+            ("亜/あ".to_string(), 1),
+        ]));
+        // あ の後には、り が 3 回、語末が 1 回。
+        assert_eq!(costs[&char_bigram_key('あ', 'り')], -f32::log10(0.75));
+
+        let mut builder = MarisaSystemUnigramLMBuilder::default();
+        for (key, cost) in &costs {
+            builder.add(key, *cost);
+        }
+        let lm = builder
+            .set_default_cost(20_f32)
+            .set_default_cost_for_short(19_f32)
+            .build();
+
+        let got = char_ngram_cost(&lm, "あり").unwrap();
+        // P(あ|語頭) = 1, P(り|あ) = 0.75, P(語末|り) = 1
+        assert!((got - -f32::log10(0.75)).abs() < 0.0001);
+        assert_eq!(char_ngram_cost(&lm, "りあ"), None);
+    }
+}
@@ -1,4 +1,7 @@
 pub mod base;
+pub mod char_bigram;
 pub mod on_memory;
+pub mod smoothing;
 pub mod system_bigram;
+pub mod system_trigram;
 pub mod system_unigram_lm;
@@ -1,24 +1,27 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 
 use crate::lm::base::SystemUnigramLM;
 
 pub struct OnMemorySystemUnigramLM {
     // word -> (word_id, cost)
-    map: Rc<RefCell<HashMap<String, (i32, f32)>>>,
+    map: Arc<RwLock<HashMap<String, (i32, f32)>>>,
+    // word -> backoff cost
+    backoff: HashMap<String, f32>,
     pub default_cost: f32,
     pub default_cost_for_short: f32,
 }
 
 impl OnMemorySystemUnigramLM {
     pub fn new(
-        map: Rc<RefCell<HashMap<String, (i32, f32)>>>,
+        map: Arc<RwLock<HashMap<String, (i32, f32)>>>,
+        backoff: HashMap<String, f32>,
         default_cost: f32,
         default_cost_for_short: f32,
     ) -> Self {
         OnMemorySystemUnigramLM {
             map,
+            backoff,
             default_cost,
             default_cost_for_short,
         }
@@ -31,13 +34,19 @@ impl OnMemorySystemUnigramLM {
         };
 
         self.map
-            .borrow_mut()
+            .write()
+            .unwrap()
             .insert(word.to_string(), (word_id, cost));
     }
 
+    pub fn backoff_hash_map(&self) -> &HashMap<String, f32> {
+        &self.backoff
+    }
+
     pub fn reverse_lookup(&self, word_id: i32) -> Option<String> {
         self.map
-            .borrow()
+            .read()
+            .unwrap()
             .iter()
             .filter(|(_, (id, _))| *id == word_id)
             .map(|(key, (_, _))| key.clone())
@@ -55,10 +64,14 @@ impl SystemUnigramLM for OnMemorySystemUnigramLM {
     }
 
     fn find(&self, word: &str) -> Option<(i32, f32)> {
-        self.map.borrow().get(word).copied()
+        self.map.read().unwrap().get(word).copied()
+    }
+
+    fn get_backoff_cost(&self, word: &str) -> Option<f32> {
+        self.backoff.get(word).copied()
     }
 
     fn as_hash_map(&self) -> HashMap<String, (i32, f32)> {
-        self.map.borrow().clone()
+        self.map.read().unwrap().clone()
     }
 }
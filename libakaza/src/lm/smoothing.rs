@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+/// 修正 Kneser-Ney 法のディスカウント値。
+/// 出現回数が 1, 2, 3 以上のものに対して、それぞれ別の値を引く。
+#[derive(Debug, PartialEq)]
+pub struct KneserNeyDiscounts {
+    pub d1: f32,
+    pub d2: f32,
+    pub d3: f32,
+}
+
+impl KneserNeyDiscounts {
+    /// 出現回数の分布から Chen & Goodman の推定式でディスカウント値を求める。
+    /// 分布が偏っていて推定できない場合は、すべて 0.5 にする。
+    pub fn estimate<'a>(counts: impl Iterator<Item = &'a u32>) -> KneserNeyDiscounts {
+        let mut n = [0_u32; 5];
+        for cnt in counts {
+            if (1..=4).contains(cnt) {
+                n[*cnt as usize] += 1;
+            }
+        }
+        let (n1, n2, n3, n4) = (n[1] as f32, n[2] as f32, n[3] as f32, n[4] as f32);
+        if n1 == 0_f32 || n2 == 0_f32 || n3 == 0_f32 {
+            return KneserNeyDiscounts {
+                d1: 0.5,
+                d2: 0.5,
+                d3: 0.5,
+            };
+        }
+        let y = n1 / (n1 + 2_f32 * n2);
+        KneserNeyDiscounts {
+            d1: (1_f32 - 2_f32 * y * n2 / n1).max(0_f32),
+            d2: (2_f32 - 3_f32 * y * n3 / n2).max(0_f32),
+            d3: (3_f32 - 4_f32 * y * n4 / n3).max(0_f32),
+        }
+    }
+
+    fn discount(&self, cnt: u32) -> f32 {
+        match cnt {
+            0 => 0_f32,
+            1 => self.d1,
+            2 => self.d2,
+            _ => self.d3,
+        }
+    }
+}
+
+/// バイグラムの推定結果。コストはいずれも -log10 をとったもの。
+#[derive(Default)]
+pub struct BackoffBigram {
+    /// (word_id1, word_id2) -> -log10 P(word2|word1)
+    pub bigram_costs: HashMap<(i32, i32), f32>,
+    /// word_id1 -> -log10 α(word1)
+    /// 採録されていないバイグラムは α(word1) * P_unigram(word2) として扱う。
+    pub backoff_costs: HashMap<i32, f32>,
+}
+
+/**
+ * 修正 Kneser-Ney 法のディスカウントで、バイグラムの条件付き確率を推定する。
+ *
+ *   P(w2|w1) = max(c(w1 w2) - D, 0) / c(w1) + γ(w1) * P_unigram(w2)
+ *
+ * 変換時に未知のバイグラムがバックオフする先はユニグラム言語モデルなので、
+ * 補間する下位の分布にも Kneser-Ney の継続確率ではなく、ユニグラムの確率 (unigram_costs) を使う。
+ * 下位の分布がそろっていないと、採録したバイグラムとバックオフしたバイグラムの確率の和が 1 にならない。
+ *
+ * threshold 回以下しか出現しなかったバイグラムは捨てる。捨てたぶんの確率は
+ * バックオフ重み α(w1) に含まれるように正規化する。
+ */
+pub fn estimate_kneser_ney(
+    threshold: u32,
+    bigram_counts: &HashMap<(i32, i32), u32>,
+    unigram_costs: &HashMap<i32, f32>,
+) -> BackoffBigram {
+    let discounts = KneserNeyDiscounts::estimate(bigram_counts.values());
+
+    // word1 ごとの出現回数と、後続する単語の種類数(出現回数 1, 2, 3以上)。
+    let mut contexts: HashMap<i32, (u32, [u32; 3])> = HashMap::new();
+    for ((word_id1, _), cnt) in bigram_counts {
+        let (total, n) = contexts.entry(*word_id1).or_insert((0, [0; 3]));
+        *total += cnt;
+        n[(*cnt).clamp(1, 3) as usize - 1] += 1;
+    }
+    let unigram_prob = |word_id: &i32| {
+        unigram_costs
+            .get(word_id)
+            .map(|cost| 10_f32.powf(-cost))
+            .unwrap_or(0_f32)
+    };
+
+    let mut bigram_costs: HashMap<(i32, i32), f32> = HashMap::new();
+    // word1 -> (採録したバイグラムの確率の和, 採録したバイグラムの word2 のユニグラム確率の和)
+    let mut kept_mass: HashMap<i32, (f32, f32)> = HashMap::new();
+    for ((word_id1, word_id2), cnt) in bigram_counts {
+        if *cnt <= threshold {
+            continue;
+        }
+        let (total, n) = contexts[word_id1];
+        let total = total as f32;
+        let gamma =
+            (discounts.d1 * n[0] as f32 + discounts.d2 * n[1] as f32 + discounts.d3 * n[2] as f32)
+                / total;
+        let p_unigram = unigram_prob(word_id2);
+        let p = (*cnt as f32 - discounts.discount(*cnt)).max(0_f32) / total + gamma * p_unigram;
+        bigram_costs.insert((*word_id1, *word_id2), -f32::log10(p));
+
+        let mass = kept_mass.entry(*word_id1).or_insert((0_f32, 0_f32));
+        mass.0 += p;
+        mass.1 += p_unigram;
+    }
+
+    let backoff_costs = contexts
+        .keys()
+        .map(|word_id1| {
+            let (p_bigram, p_unigram) = kept_mass.get(word_id1).unwrap_or(&(0_f32, 0_f32));
+            let numerator = (1_f32 - p_bigram).max(f32::EPSILON);
+            let denominator = (1_f32 - p_unigram).max(f32::EPSILON);
+            (*word_id1, -f32::log10(numerator / denominator))
+        })
+        .collect::<HashMap<_, _>>();
+
+    BackoffBigram {
+        bigram_costs,
+        backoff_costs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discounts() {
+        let counts = [1, 1, 1, 1, 2, 2, 3, 4, 10];
+        let discounts = KneserNeyDiscounts::estimate(counts.iter());
+        // y = 4 / (4 + 2 * 2) = 0.5
+        assert_eq!(
+            discounts,
+            KneserNeyDiscounts {
+                d1: 1_f32 - 2_f32 * 0.5 * 2_f32 / 4_f32,
+                d2: 2_f32 - 3_f32 * 0.5 * 1_f32 / 2_f32,
+                d3: 3_f32 - 4_f32 * 0.5 * 1_f32 / 1_f32,
+            }
+        );
+
+        assert_eq!(
+            KneserNeyDiscounts::estimate([5, 6].iter()),
+            KneserNeyDiscounts {
+                d1: 0.5,
+                d2: 0.5,
+                d3: 0.5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_estimate_kneser_ney() {
+        let bigram_counts = HashMap::from([
+            ((1, 2), 8),
+            ((1, 3), 1),
+            ((4, 3), 2),
+            ((4, 2), 1),
+            ((5, 6), 3),
+        ]);
+        let unigram_costs = HashMap::from([(2, 1_f32), (3, 1.5_f32), (6, 2_f32)]);
+        let got = estimate_kneser_ney(1, &bigram_counts, &unigram_costs);
+
+        // threshold 以下のものは捨てられる。
+        assert_eq!(got.bigram_costs.len(), 3);
+        assert!(!got.bigram_costs.contains_key(&(1, 3)));
+        // よく出る組み合わせほどコストが低い。
+        assert!(got.bigram_costs[&(1, 2)] < got.bigram_costs[&(4, 3)]);
+        for cost in got.bigram_costs.values() {
+            assert!(*cost > 0_f32);
+        }
+
+        // 前に来たことのある単語には、すべてバックオフ重みがある。
+        let mut keys = got.backoff_costs.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec![1, 4, 5]);
+        // 採録したバイグラムに確率を取られたぶん、未知のバイグラムは不利になる。
+        assert!(got.backoff_costs[&1] > 0_f32);
+    }
+
+    #[test]
+    fn test_normalized() {
+        let bigram_counts = HashMap::from([
+            ((1, 2), 8),
+            ((1, 3), 3),
+            ((1, 4), 1),
+            ((2, 3), 2),
+            ((2, 5), 1),
+        ]);
+        // ユニグラムの確率の和が 1 になるようにしておく。
+        let unigram_costs = [(1, 0.1_f32), (2, 0.3), (3, 0.2), (4, 0.25), (5, 0.15)]
+            .iter()
+            .map(|(word_id, p)| (*word_id, -f32::log10(*p)))
+            .collect::<HashMap<_, _>>();
+        let got = estimate_kneser_ney(1, &bigram_counts, &unigram_costs);
+
+        // 採録したバイグラムと、バックオフしたバイグラムの確率をあわせると 1 になる。
+        for word_id1 in [1, 2] {
+            let total: f32 = unigram_costs
+                .iter()
+                .map(|(word_id2, unigram_cost)| {
+                    let cost = got
+                        .bigram_costs
+                        .get(&(word_id1, *word_id2))
+                        .cloned()
+                        .unwrap_or(got.backoff_costs[&word_id1] + unigram_cost);
+                    10_f32.powf(-cost)
+                })
+                .sum();
+            assert!((total - 1_f32).abs() < 1e-4, "{}: {}", word_id1, total);
+        }
+    }
+}
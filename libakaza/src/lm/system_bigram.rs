@@ -7,16 +7,30 @@ use log::info;
 use marisa_sys::{Keyset, Marisa};
 
 use crate::lm::base::SystemBigramLM;
+use crate::lm::system_unigram_lm::MAX_WORD_ID;
 use crate::trie::SearchResult;
 
 /*
    {word1 ID}    # 3 bytes
    {word2 ID}    # 3 bytes
-   packed float  # score: 4 bytes
+   packed float  # score: 2 bytes
+
+   採録されていないバイグラムのためのバックオフ重みは、ユニグラム言語モデルに格納している。
 */
 
 const DEFAULT_COST_KEY: &str = "__DEFAULT_EDGE_COST__";
 
+/// 単語 ID を 3 バイトに詰める。
+pub(crate) fn pack_word_id(word_id: i32) -> [u8; 3] {
+    assert!(
+        (0..MAX_WORD_ID).contains(&word_id),
+        "word_id is out of range: {}",
+        word_id
+    );
+    let bytes = word_id.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
+
 /**
  * bigram 言語モデル。
  * unigram の生成のときに得られた単語IDを利用することで、圧縮している。
@@ -44,15 +58,9 @@ impl MarisaSystemBigramLMBuilder {
         // さらに、スコアを f16 にしてみたが、あまりかわらない。
         // -rw-r--r-- 1 tokuhirom tokuhirom  27M Jan  1 02:14 bigram.model
 
-        let id1_bytes = word_id1.to_le_bytes();
-        let id2_bytes = word_id2.to_le_bytes();
-
-        assert_eq!(id1_bytes[3], 0);
-        assert_eq!(id2_bytes[3], 0);
-
         let mut key: Vec<u8> = Vec::new();
-        key.extend(id1_bytes[0..3].iter());
-        key.extend(id2_bytes[0..3].iter());
+        key.extend(pack_word_id(word_id1));
+        key.extend(pack_word_id(word_id2));
         key.extend(f16::from_f32(score).to_le_bytes());
         self.keyset.push_back(key.as_slice());
     }
@@ -121,23 +129,10 @@ impl MarisaSystemBigramLM {
             bail!("Cannot parse default edge cost from trie");
         }
     }
-}
-
-impl SystemBigramLM for MarisaSystemBigramLM {
-    fn get_default_edge_cost(&self) -> f32 {
-        self.default_edge_cost
-    }
 
-    /**
-     * edge cost を得る。
-     * この ID は、unigram の trie でふられたもの。
-     */
-    fn get_edge_cost(&self, word_id1: i32, word_id2: i32) -> Option<f32> {
-        let mut key: Vec<u8> = Vec::new();
-        key.extend(word_id1.to_le_bytes()[0..3].iter());
-        key.extend(word_id2.to_le_bytes()[0..3].iter());
+    fn find_score(&self, key: &[u8]) -> Option<f32> {
         let mut got: Vec<SearchResult> = Vec::new();
-        self.marisa.predictive_search(key.as_slice(), |key, id| {
+        self.marisa.predictive_search(key, |key, id| {
             got.push(SearchResult {
                 keyword: key.to_vec(),
                 id,
@@ -153,6 +148,27 @@ impl SystemBigramLM for MarisaSystemBigramLM {
         let score: f16 = f16::from_le_bytes(last2);
         Some(score.to_f32())
     }
+}
+
+impl SystemBigramLM for MarisaSystemBigramLM {
+    fn get_default_edge_cost(&self) -> f32 {
+        self.default_edge_cost
+    }
+
+    /**
+     * edge cost を得る。
+     * この ID は、unigram の trie でふられたもの。
+     */
+    fn get_edge_cost(&self, word_id1: i32, word_id2: i32) -> Option<f32> {
+        // 範囲外の ID を 3 バイトに切り詰めると、別の単語のバイグラムを引いてしまう。
+        if !(0..MAX_WORD_ID).contains(&word_id1) || !(0..MAX_WORD_ID).contains(&word_id2) {
+            return None;
+        }
+        let mut key: Vec<u8> = Vec::new();
+        key.extend(pack_word_id(word_id1));
+        key.extend(pack_word_id(word_id2));
+        self.find_score(key.as_slice())
+    }
 
     fn as_hash_map(&self) -> HashMap<(i32, i32), f32> {
         let mut map: HashMap<(i32, i32), f32> = HashMap::new();
@@ -189,4 +205,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn word_id_range() -> anyhow::Result<()> {
+        let mut builder = MarisaSystemBigramLMBuilder::default();
+        builder.set_default_edge_cost(20_f32);
+        builder.add(MAX_WORD_ID - 1, 1, 5.11_f32);
+        let system_bigram_lm = builder.build()?;
+        assert!(system_bigram_lm.get_edge_cost(MAX_WORD_ID - 1, 1).is_some());
+        // 3 バイトに切り詰めると MAX_WORD_ID - 1 と区別できなくなる ID は引けない。
+        assert_eq!(system_bigram_lm.get_edge_cost(MAX_WORD_ID * 2 - 1, 1), None);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "word_id is out of range")]
+    fn word_id_out_of_range() {
+        MarisaSystemBigramLMBuilder::default().add(MAX_WORD_ID, 1, 5.11_f32);
+    }
 }
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use half::f16;
+use log::info;
+
+use marisa_sys::{Keyset, Marisa};
+
+use crate::lm::base::SystemTrigramLM;
+use crate::lm::system_bigram::pack_word_id;
+use crate::lm::system_unigram_lm::MAX_WORD_ID;
+use crate::trie::SearchResult;
+
+/*
+   {word1 ID}    # 3 bytes
+   {word2 ID}    # 3 bytes
+   {word3 ID}    # 3 bytes
+   packed float  # score: 2 bytes
+*/
+
+/**
+ * trigram 言語モデル。
+ * bigram と同じく、unigram の生成のときに得られた単語IDを 3 byte に詰めて利用する。
+ *
+ * スコアは word1 word2 の後ろに word3 が続くときに、bigram のエッジコストに足し込む補正値。
+ * 文脈によって word3 が出やすくなる場合は負の値になる。
+ */
+#[derive(Default)]
+pub struct MarisaSystemTrigramLMBuilder {
+    keyset: Keyset,
+}
+
+impl MarisaSystemTrigramLMBuilder {
+    pub fn add(&mut self, word_id1: i32, word_id2: i32, word_id3: i32, score: f32) {
+        let mut key: Vec<u8> = Vec::new();
+        key.extend(pack_word_id(word_id1));
+        key.extend(pack_word_id(word_id2));
+        key.extend(pack_word_id(word_id3));
+        key.extend(f16::from_f32(score).to_le_bytes());
+        self.keyset.push_back(key.as_slice());
+    }
+
+    pub fn build(&self) -> MarisaSystemTrigramLM {
+        let mut marisa = Marisa::default();
+        marisa.build(&self.keyset);
+        MarisaSystemTrigramLM { marisa }
+    }
+
+    pub fn save(&self, ofname: &str) -> Result<()> {
+        let mut marisa = Marisa::default();
+        marisa.build(&self.keyset);
+        marisa.save(ofname)?;
+        Ok(())
+    }
+}
+
+pub struct MarisaSystemTrigramLM {
+    marisa: Marisa,
+}
+
+impl MarisaSystemTrigramLM {
+    pub fn load(filename: &str) -> Result<MarisaSystemTrigramLM> {
+        info!("Loading system-trigram: {}", filename);
+        let mut marisa = Marisa::default();
+        marisa.load(filename)?;
+        Ok(MarisaSystemTrigramLM { marisa })
+    }
+
+    pub fn num_keys(&self) -> usize {
+        self.marisa.num_keys()
+    }
+}
+
+impl SystemTrigramLM for MarisaSystemTrigramLM {
+    /**
+     * エッジコストの補正値を得る。
+     * この ID は、unigram の trie でふられたもの。
+     */
+    fn get_trigram_cost(&self, word_id1: i32, word_id2: i32, word_id3: i32) -> Option<f32> {
+        if [word_id1, word_id2, word_id3]
+            .iter()
+            .any(|word_id| !(0..MAX_WORD_ID).contains(word_id))
+        {
+            return None;
+        }
+        let mut key: Vec<u8> = Vec::new();
+        key.extend(pack_word_id(word_id1));
+        key.extend(pack_word_id(word_id2));
+        key.extend(pack_word_id(word_id3));
+        let mut got: Vec<SearchResult> = Vec::new();
+        self.marisa.predictive_search(key.as_slice(), |key, id| {
+            got.push(SearchResult {
+                keyword: key.to_vec(),
+                id,
+            });
+            true
+        });
+        let result = got.first()?;
+        let last2: [u8; 2] = result.keyword[result.keyword.len() - 2..result.keyword.len()]
+            .try_into()
+            .unwrap();
+        let score: f16 = f16::from_le_bytes(last2);
+        Some(score.to_f32())
+    }
+
+    fn as_hash_map(&self) -> HashMap<(i32, i32, i32), f32> {
+        let mut map: HashMap<(i32, i32, i32), f32> = HashMap::new();
+        self.marisa.predictive_search("".as_bytes(), |word, _id| {
+            if word.len() == 11 {
+                let word_id1 = i32::from_le_bytes([word[0], word[1], word[2], 0]);
+                let word_id2 = i32::from_le_bytes([word[3], word[4], word[5], 0]);
+                let word_id3 = i32::from_le_bytes([word[6], word[7], word[8], 0]);
+                let cost = f16::from_le_bytes([word[9], word[10]]).to_f32();
+                map.insert((word_id1, word_id2, word_id3), cost);
+            }
+            true
+        });
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_and_load() {
+        let mut builder = MarisaSystemTrigramLMBuilder::default();
+        builder.add(4649, 5963, 3, -1.5_f32);
+        let system_trigram_lm = builder.build();
+        let got_score = system_trigram_lm.get_trigram_cost(4649, 5963, 3).unwrap();
+        assert!(-1.51 < got_score && got_score < -1.49);
+        assert_eq!(system_trigram_lm.get_trigram_cost(4649, 5963, 4), None);
+
+        let map = system_trigram_lm.as_hash_map();
+        assert!(map.contains_key(&(4649, 5963, 3)));
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use log::info;
 
 use marisa_sys::{Keyset, Marisa};
@@ -12,8 +12,12 @@ use crate::lm::base::SystemUnigramLM;
    0xff   # marker
    packed ID     # 3 bytes(24bit). 最大語彙: 8,388,608(2**24/2)
    packed float  # score: 4 bytes
+   packed float  # backoff: 4 bytes。バイグラムのバックオフ重みがある単語のみ。
 */
 
+/// 単語 ID の上限。バイグラム・トライグラム言語モデルでは ID を 3 バイトに詰めて格納している。
+pub const MAX_WORD_ID: i32 = 8_388_608;
+
 const DEFAULT_COST_FOR_SHORT_KEY: &str = "__DEFAULT_COST_FOR_SHORT__";
 const DEFAULT_COST_KEY: &str = "__DEFAULT_COST__";
 
@@ -23,26 +27,34 @@ const DEFAULT_COST_KEY: &str = "__DEFAULT_COST__";
  */
 #[derive(Default)]
 pub struct MarisaSystemUnigramLMBuilder {
-    data: Vec<(String, f32)>,
+    data: Vec<(String, f32, Option<f32>)>,
 }
 
 impl MarisaSystemUnigramLMBuilder {
     pub fn add(&mut self, word: &str, score: f32) {
-        self.data.push((word.to_string(), score));
+        self.data.push((word.to_string(), score, None));
+    }
+
+    /// word に続くバイグラムが採録されていないときのコスト(-log10 α)とあわせて登録する。
+    pub fn add_with_backoff(&mut self, word: &str, score: f32, backoff: f32) {
+        self.data.push((word.to_string(), score, Some(backoff)));
     }
 
     pub fn keyset(&self) -> Keyset {
         let mut keyset = Keyset::default();
-        for (kanji, score) in &self.data {
+        for (kanji, score, backoff) in &self.data {
             // 区切り文字をいれなくても、末尾の4バイトを取り出せば十分な気がしないでもない。。
             // 先頭一致にして、+4バイトになるものを探せばいいはず。
             // 最適化の余地だけど、現実的には空間効率よりも速度のほうが重要かもしれない。
-            let key = [
+            let mut key = [
                 kanji.as_bytes(),
                 b"\xff",
                 score.to_le_bytes().as_slice(), // バイナリにしてデータ容量を節約する
             ]
             .concat();
+            if let Some(backoff) = backoff {
+                key.extend(backoff.to_le_bytes());
+            }
             keyset.push_back(key.as_slice());
         }
         keyset
@@ -61,6 +73,12 @@ impl MarisaSystemUnigramLMBuilder {
     pub fn save(&self, fname: &str) -> Result<()> {
         let mut marisa = Marisa::default();
         marisa.build(&self.keyset());
+        ensure!(
+            marisa.num_keys() <= MAX_WORD_ID as usize,
+            "Too many words for the unigram LM: {} > {}",
+            marisa.num_keys(),
+            MAX_WORD_ID
+        );
         marisa.save(fname)?;
         Ok(())
     }
@@ -68,6 +86,7 @@ impl MarisaSystemUnigramLMBuilder {
     pub fn build(&self) -> MarisaSystemUnigramLM {
         let mut marisa = Marisa::default();
         marisa.build(&self.keyset());
+        assert!(marisa.num_keys() <= MAX_WORD_ID as usize);
         let (_, default_cost_for_short) =
             MarisaSystemUnigramLM::find_from_trie(&marisa, DEFAULT_COST_FOR_SHORT_KEY).unwrap();
         let (_, default_cost) =
@@ -109,24 +128,43 @@ impl MarisaSystemUnigramLM {
     }
 
     fn find_from_trie(marisa: &Marisa, word: &str) -> Option<(i32, f32)> {
+        Self::find_entry(marisa, word).map(|(word_id, score, _)| (word_id, score))
+    }
+
+    /// @return (word_id, score, backoff)
+    fn find_entry(marisa: &Marisa, word: &str) -> Option<(i32, f32, Option<f32>)> {
         assert_ne!(word.len(), 0);
 
         let key = [word.as_bytes(), b"\xff"].concat();
-        let mut kanji_id: usize = usize::MAX;
-        let mut score = f32::MAX;
+        let mut got: Option<(i32, f32, Option<f32>)> = None;
         marisa.predictive_search(key.as_slice(), |word, id| {
-            kanji_id = id;
-
-            let idx = word.iter().position(|f| *f == b'\xff').unwrap();
-            let bytes: [u8; 4] = word[idx + 1..idx + 1 + 4].try_into().unwrap();
-            score = f32::from_le_bytes(bytes);
+            let (_, score, backoff) = Self::parse_key(word);
+            got = Some((id as i32, score, backoff));
             false
         });
-        if kanji_id != usize::MAX {
-            Some((kanji_id as i32, score))
-        } else {
-            None
-        }
+        got
+    }
+
+    /// @return (word, score, backoff)
+    fn parse_key(key: &[u8]) -> (&[u8], f32, Option<f32>) {
+        let idx = key.iter().position(|f| *f == b'\xff').unwrap();
+        let bytes: [u8; 4] = key[idx + 1..idx + 1 + 4].try_into().unwrap();
+        let backoff = key
+            .get(idx + 1 + 4..idx + 1 + 8)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()));
+        (&key[0..idx], f32::from_le_bytes(bytes), backoff)
+    }
+
+    /// word -> バックオフ重み
+    pub fn backoff_hash_map(&self) -> HashMap<String, f32> {
+        let mut map = HashMap::new();
+        self.marisa.predictive_search("".as_bytes(), |key, _id| {
+            if let (word, _, Some(backoff)) = Self::parse_key(key) {
+                map.insert(String::from_utf8_lossy(word).to_string(), backoff);
+            }
+            true
+        });
+        map
     }
 }
 
@@ -144,14 +182,15 @@ impl SystemUnigramLM for MarisaSystemUnigramLM {
         Self::find_from_trie(&self.marisa, word)
     }
 
+    fn get_backoff_cost(&self, word: &str) -> Option<f32> {
+        Self::find_entry(&self.marisa, word).and_then(|(_, _, backoff)| backoff)
+    }
+
     fn as_hash_map(&self) -> HashMap<String, (i32, f32)> {
         let mut map = HashMap::new();
-        self.marisa.predictive_search("".as_bytes(), |word, id| {
-            let idx = word.iter().position(|f| *f == b'\xff').unwrap();
-            let bytes: [u8; 4] = word[idx + 1..idx + 1 + 4].try_into().unwrap();
-            let word = String::from_utf8_lossy(&word[0..idx]);
-            let cost = f32::from_le_bytes(bytes);
-            map.insert(word.to_string(), (id as i32, cost));
+        self.marisa.predictive_search("".as_bytes(), |key, id| {
+            let (word, cost, _) = Self::parse_key(key);
+            map.insert(String::from_utf8_lossy(word).to_string(), (id as i32, cost));
             true
         });
         map
@@ -189,4 +228,24 @@ mod tests {
             assert_eq!(p, None);
         }
     }
+
+    #[test]
+    fn test_backoff() {
+        let mut builder = MarisaSystemUnigramLMBuilder::default();
+        builder.add_with_backoff("私/わたし", 2_f32, 0.5_f32);
+        builder.add("は/は", 1_f32);
+        let lm = builder
+            .set_default_cost(20_f32)
+            .set_default_cost_for_short(19_f32)
+            .build();
+
+        assert_eq!(lm.find("私/わたし").map(|(_, score)| score), Some(2_f32));
+        assert_eq!(lm.get_backoff_cost("私/わたし"), Some(0.5_f32));
+        assert_eq!(lm.get_backoff_cost("は/は"), None);
+        assert_eq!(lm.get_backoff_cost("unknown"), None);
+        assert_eq!(
+            lm.backoff_hash_map(),
+            HashMap::from([("私/わたし".to_string(), 0.5_f32)])
+        );
+    }
 }
@@ -0,0 +1,464 @@
+/*
+   数字の変換。
+
+   読みに含まれる数字を、"123", "１２３", "百二十三", "一二三", "1,234" のような表記に変換する。
+   SKK の数値変換 (#0, #1, #2, #3, #5, #8, #9) と同じ表記を扱う。
+*/
+
+const KANJI_DIGITS: [char; 10] = ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+// 4桁ごとの位
+const KANJI_LARGE_UNITS: [&str; 5] = ["", "万", "億", "兆", "京"];
+// 4桁のなかの位。千の位から順にならべる。
+const KANJI_SMALL_UNITS: [&str; 4] = ["千", "百", "十", ""];
+// 大字
+const DAIJI_DIGITS: [char; 10] = ['零', '壱', '弐', '参', '四', '伍', '六', '七', '八', '九'];
+const DAIJI_LARGE_UNITS: [&str; 5] = ["", "萬", "億", "兆", "京"];
+const DAIJI_SMALL_UNITS: [&str; 4] = ["阡", "百", "拾", ""];
+
+/// 数字のうしろにつく助数詞の読みと表記。
+const COUNTERS: &[(&str, &[&str])] = &[
+    ("こ", &["個"]),
+    ("ほん", &["本"]),
+    ("ぼん", &["本"]),
+    ("ぽん", &["本"]),
+    ("まい", &["枚"]),
+    ("にん", &["人"]),
+    ("ひき", &["匹"]),
+    ("びき", &["匹"]),
+    ("ぴき", &["匹"]),
+    ("かい", &["回", "階"]),
+    ("ねん", &["年"]),
+    ("がつ", &["月"]),
+    ("にち", &["日"]),
+    ("じ", &["時"]),
+    ("じかん", &["時間"]),
+    ("ふん", &["分"]),
+    ("ぷん", &["分"]),
+    ("びょう", &["秒"]),
+    ("えん", &["円"]),
+    ("さい", &["歳"]),
+    ("ど", &["度"]),
+    ("ばん", &["番"]),
+    ("だい", &["台"]),
+    ("さつ", &["冊"]),
+    ("けん", &["件", "軒"]),
+    ("ばい", &["倍"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericType {
+    /// #0: そのまま。"123"
+    AsIs,
+    /// #1: 全角。"１２３"
+    FullWidth,
+    /// #2: 位取りなしの漢数字。"一二三"
+    KanjiDigits,
+    /// #3: 位取りありの漢数字。"百二十三"
+    Kansuji,
+    /// #5: 大字。"壱百弐拾参"
+    Daiji,
+    /// #8: 3桁ごとの桁区切り。"1,234"
+    Grouped,
+    /// #9: 将棋の棋譜。2桁の数字を "３四" のように書く。
+    Shogi,
+}
+
+impl NumericType {
+    /// SKK 辞書の "#1" などの、# のあとの数字から表記を求める。
+    /// #4 (数字を読みとして辞書を引き直す) には対応していない。
+    pub fn from_skk(c: char) -> Option<NumericType> {
+        match c {
+            '0' => Some(NumericType::AsIs),
+            '1' => Some(NumericType::FullWidth),
+            '2' => Some(NumericType::KanjiDigits),
+            '3' => Some(NumericType::Kansuji),
+            '5' => Some(NumericType::Daiji),
+            '8' => Some(NumericType::Grouped),
+            '9' => Some(NumericType::Shogi),
+            _ => None,
+        }
+    }
+}
+
+/// 数字だけからなる文字列かどうか。全角数字も数字として扱う。
+pub fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| to_ascii_digit(c).is_some())
+}
+
+fn to_ascii_digit(c: char) -> Option<char> {
+    match c {
+        '0'..='9' => Some(c),
+        '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32),
+        _ => None,
+    }
+}
+
+/// 先頭から数字が続いている部分を返す。
+pub fn leading_digits(yomi: &str) -> Option<&str> {
+    let len = yomi
+        .chars()
+        .take_while(|c| to_ascii_digit(*c).is_some())
+        .map(|c| c.len_utf8())
+        .sum();
+    if len == 0 {
+        None
+    } else {
+        Some(&yomi[..len])
+    }
+}
+
+/// 数字を numeric_type の表記に変換する。数字以外が含まれていたり、桁が多すぎる場合は None。
+pub fn format_number(digits: &str, numeric_type: NumericType) -> Option<String> {
+    let digits = digits
+        .chars()
+        .map(to_ascii_digit)
+        .collect::<Option<String>>()?;
+    if digits.is_empty() {
+        return None;
+    }
+
+    match numeric_type {
+        NumericType::AsIs => Some(digits),
+        NumericType::FullWidth => Some(
+            digits
+                .chars()
+                .filter_map(|c| char::from_u32(c as u32 - '0' as u32 + '０' as u32))
+                .collect(),
+        ),
+        NumericType::KanjiDigits => Some(
+            digits
+                .bytes()
+                .map(|b| KANJI_DIGITS[(b - b'0') as usize])
+                .collect(),
+        ),
+        NumericType::Kansuji => to_kansuji(
+            &digits,
+            &KANJI_DIGITS,
+            &KANJI_SMALL_UNITS,
+            &KANJI_LARGE_UNITS,
+            true,
+        ),
+        NumericType::Daiji => to_kansuji(
+            &digits,
+            &DAIJI_DIGITS,
+            &DAIJI_SMALL_UNITS,
+            &DAIJI_LARGE_UNITS,
+            false,
+        ),
+        NumericType::Grouped => {
+            let mut buf = String::new();
+            for (i, c) in digits.chars().enumerate() {
+                if i > 0 && (digits.len() - i) % 3 == 0 {
+                    buf.push(',');
+                }
+                buf.push(c);
+            }
+            Some(buf)
+        }
+        NumericType::Shogi => {
+            let b = digits.as_bytes();
+            if b.len() != 2 {
+                return None;
+            }
+            let mut buf = format_number(&digits[..1], NumericType::FullWidth)?;
+            buf.push(KANJI_DIGITS[(b[1] - b'0') as usize]);
+            Some(buf)
+        }
+    }
+}
+
+/// 位取りありの漢数字にする。omit_one が true なら「一千」ではなく「千」とする。
+fn to_kansuji(
+    digits: &str,
+    digit_chars: &[char; 10],
+    small_units: &[&str; 4],
+    large_units: &[&str; 5],
+    omit_one: bool,
+) -> Option<String> {
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some(digit_chars[0].to_string());
+    }
+    if digits.len() > large_units.len() * 4 {
+        return None;
+    }
+
+    // 4桁ずつに区切る。先頭のグループは 4桁に満たないことがあるので、0 で埋めておく。
+    let padded = format!("{}{}", "0".repeat((4 - digits.len() % 4) % 4), digits);
+    let groups = padded.as_bytes().chunks(4).collect::<Vec<_>>();
+
+    let mut buf = String::new();
+    for (i, group) in groups.iter().enumerate() {
+        if group.iter().all(|b| *b == b'0') {
+            continue;
+        }
+        for (b, unit) in group.iter().zip(small_units) {
+            let n = (b - b'0') as usize;
+            if n == 0 {
+                continue;
+            }
+            if n != 1 || unit.is_empty() || !omit_one {
+                buf.push(digit_chars[n]);
+            }
+            buf += unit;
+        }
+        buf += large_units[groups.len() - i - 1];
+    }
+    Some(buf)
+}
+
+/// 数字の変換候補。
+pub fn numeric_surfaces(digits: &str) -> Vec<String> {
+    let mut types = vec![
+        NumericType::AsIs,
+        NumericType::FullWidth,
+        NumericType::Kansuji,
+        NumericType::KanjiDigits,
+    ];
+    if digits.chars().count() >= 4 {
+        types.push(NumericType::Grouped);
+    }
+
+    let mut surfaces: Vec<String> = Vec::new();
+    for numeric_type in types {
+        if let Some(surface) = format_number(digits, numeric_type) {
+            if !surfaces.contains(&surface) {
+                surfaces.push(surface);
+            }
+        }
+    }
+    surfaces
+}
+
+/// yomi の先頭にある助数詞の読みをすべて返す。"こ" → ["こ"], "じかん" → ["じ", "じかん"]
+pub fn counter_prefixes(yomi: &str) -> Vec<&'static str> {
+    COUNTERS
+        .iter()
+        .map(|(counter, _)| *counter)
+        .filter(|counter| yomi.starts_with(counter))
+        .collect()
+}
+
+/// "3こ" のような、数字と助数詞の読みを (数字, 助数詞の表記) に分ける。
+pub fn split_counter(yomi: &str) -> Option<(&str, &'static [&'static str])> {
+    let digits = leading_digits(yomi)?;
+    let counter = &yomi[digits.len()..];
+    COUNTERS
+        .iter()
+        .find(|(c, _)| *c == counter)
+        .map(|(_, surfaces)| (digits, *surfaces))
+}
+
+/// "3こ" → ["3個", "３個", "三個"]
+pub fn counter_surfaces(yomi: &str) -> Vec<String> {
+    let Some((digits, counters)) = split_counter(yomi) else {
+        return Vec::new();
+    };
+    let mut surfaces: Vec<String> = Vec::new();
+    for counter in counters {
+        for number in numeric_surfaces(digits) {
+            surfaces.push(number + counter);
+        }
+    }
+    surfaces
+}
+
+/**
+ * 読みに含まれる数字の並びを、それぞれ '#' に置き換える。
+ * SKK 辞書の数値変換のエントリーを引くためのキーと、置き換えた数字のリストを返す。
+ * 数字が含まれていなければ None。
+ * 読みにもともと '#' が含まれている場合は、置き換えた数字と区別できないので None。
+ *
+ * 例: "12がつ3にち" → ("#がつ#にち", ["12", "3"])
+ */
+pub fn numeric_template_key(yomi: &str) -> Option<(String, Vec<&str>)> {
+    if yomi.contains('#') {
+        return None;
+    }
+    let mut key = String::new();
+    let mut numbers: Vec<&str> = Vec::new();
+    let mut rest = yomi;
+    while let Some(c) = rest.chars().next() {
+        if let Some(digits) = leading_digits(rest) {
+            key.push('#');
+            numbers.push(digits);
+            rest = &rest[digits.len()..];
+        } else {
+            key.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if numbers.is_empty() {
+        None
+    } else {
+        Some((key, numbers))
+    }
+}
+
+/// numeric_template_key で作ったキーの先頭部分 key_prefix に対応する、yomi の先頭部分を返す。
+pub fn restore_numeric_prefix<'a>(yomi: &'a str, key_prefix: &str) -> &'a str {
+    let mut len = 0;
+    for c in key_prefix.chars() {
+        if c == '#' {
+            len += leading_digits(&yomi[len..]).map(|d| d.len()).unwrap_or(0);
+        } else {
+            len += c.len_utf8();
+        }
+    }
+    &yomi[..len]
+}
+
+/// SKK 辞書の "#1年" のような表記の "#1" を、numbers の数字で順に置き換える。
+/// 対応していない表記や、数字の個数が合わない場合は None。
+pub fn expand_numeric_template(template: &str, numbers: &[&str]) -> Option<String> {
+    let mut buf = String::new();
+    let mut numbers = numbers.iter();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c == '#' {
+            let numeric_type = NumericType::from_skk(chars.next()?)?;
+            buf += &format_number(numbers.next()?, numeric_type)?;
+        } else {
+            buf.push(c);
+        }
+    }
+    if numbers.next().is_some() {
+        return None;
+    }
+    Some(buf)
+}
+
+/// "#1年" の #1 のように、表記に含まれる数値変換の指定がすべて対応しているものか。
+pub fn is_supported_numeric_template(template: &str) -> bool {
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c == '#' && chars.next().and_then(NumericType::from_skk).is_none() {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(
+            format_number("123", NumericType::AsIs),
+            Some("123".to_string())
+        );
+        assert_eq!(
+            format_number("123", NumericType::FullWidth),
+            Some("１２３".to_string())
+        );
+        assert_eq!(
+            format_number("１２３", NumericType::AsIs),
+            Some("123".to_string())
+        );
+        assert_eq!(
+            format_number("2023", NumericType::KanjiDigits),
+            Some("二〇二三".to_string())
+        );
+        assert_eq!(
+            format_number("1234567", NumericType::Grouped),
+            Some("1,234,567".to_string())
+        );
+        assert_eq!(
+            format_number("123", NumericType::Grouped),
+            Some("123".to_string())
+        );
+        assert_eq!(format_number("12a", NumericType::AsIs), None);
+    }
+
+    #[test]
+    fn test_kansuji() {
+        for (digits, expected) in [
+            ("0", "〇"),
+            ("10", "十"),
+            ("123", "百二十三"),
+            ("1000", "千"),
+            ("2023", "二千二十三"),
+            ("10000", "一万"),
+            ("100010001", "一億一万一"),
+            ("31415926", "三千百四十一万五千九百二十六"),
+        ] {
+            assert_eq!(
+                format_number(digits, NumericType::Kansuji),
+                Some(expected.to_string()),
+                "{}",
+                digits
+            );
+        }
+        assert_eq!(format_number(&"9".repeat(21), NumericType::Kansuji), None);
+    }
+
+    #[test]
+    fn test_daiji_and_shogi() {
+        assert_eq!(
+            format_number("123", NumericType::Daiji),
+            Some("壱百弐拾参".to_string())
+        );
+        assert_eq!(
+            format_number("10000", NumericType::Daiji),
+            Some("壱萬".to_string())
+        );
+        assert_eq!(
+            format_number("34", NumericType::Shogi),
+            Some("３四".to_string())
+        );
+        assert_eq!(format_number("345", NumericType::Shogi), None);
+    }
+
+    #[test]
+    fn test_numeric_template() {
+        assert_eq!(
+            numeric_template_key("12がつ3にち"),
+            Some(("#がつ#にち".to_string(), vec!["12", "3"]))
+        );
+        assert_eq!(numeric_template_key("がつ"), None);
+        assert_eq!(numeric_template_key("#1"), None);
+        assert_eq!(restore_numeric_prefix("12がつ3にち", "#がつ"), "12がつ");
+        assert_eq!(restore_numeric_prefix("12がつ3にち", "#がつ#"), "12がつ3");
+
+        assert_eq!(
+            expand_numeric_template("#1年", &["2023"]),
+            Some("２０２３年".to_string())
+        );
+        assert_eq!(
+            expand_numeric_template("#0月#3日", &["12", "3"]),
+            Some("12月三日".to_string())
+        );
+        assert_eq!(expand_numeric_template("#4", &["1"]), None);
+        assert_eq!(expand_numeric_template("#0月#0日", &["12"]), None);
+        assert!(is_supported_numeric_template("#3年"));
+        assert!(!is_supported_numeric_template("#4"));
+    }
+
+    #[test]
+    fn test_numeric_surfaces() {
+        assert_eq!(
+            numeric_surfaces("123"),
+            vec!["123", "１２３", "百二十三", "一二三"]
+        );
+        assert_eq!(
+            numeric_surfaces("1234"),
+            vec!["1234", "１２３４", "千二百三十四", "一二三四", "1,234"]
+        );
+        assert_eq!(numeric_surfaces("0"), vec!["0", "０", "〇"]);
+    }
+
+    #[test]
+    fn test_counter() {
+        assert_eq!(leading_digits("3こ"), Some("3"));
+        assert_eq!(leading_digits("こ"), None);
+        assert_eq!(counter_prefixes("じかんです"), vec!["じ", "じかん"]);
+        assert_eq!(counter_surfaces("3こ"), vec!["3個", "３個", "三個"]);
+        assert_eq!(
+            counter_surfaces("2かい"),
+            vec!["2回", "２回", "二回", "2階", "２階", "二階"]
+        );
+        assert_eq!(counter_surfaces("3ほ"), Vec::<String>::new());
+    }
+}
@@ -305,6 +305,7 @@ pub struct RomKanConverter {
     romkan_pattern: Regex,
     romkan_map: HashMap<&'static str, &'static str>,
     last_char_pattern: Regex,
+    nn_pattern: Regex,
 }
 
 impl Default for RomKanConverter {
@@ -326,6 +327,8 @@ impl Default for RomKanConverter {
             romkan_pattern,
             romkan_map,
             last_char_pattern,
+            // "nn'" の "'" は、"nn" で「ん」が確定したあとの区切りなので、まとめて1つの「ん」にする。
+            nn_pattern: Regex::new("nn'?").unwrap(),
         }
     }
 }
@@ -337,8 +340,14 @@ impl RomKanConverter {
 
     pub fn to_hiragana(&self, src: &str) -> String {
         let src = src.to_ascii_lowercase();
-        let src = src.replace("nn", "n'"); // replace nn as n'.
-        let retval = self.romkan_pattern.replace_all(&src, |caps: &Captures| {
+        let src = self.nn_pattern.replace_all(&src, "n'"); // replace nn as n'.
+
+        // "kany" の "ny" は「にゃ」などの入力途中なので、「んy」にせずにそのまま残す。
+        let (src, pending) = match src.strip_suffix("ny") {
+            Some(body) => (body, "ny"),
+            None => (src.as_ref(), ""),
+        };
+        let retval = self.romkan_pattern.replace_all(src, |caps: &Captures| {
             let rom = caps.get(1).unwrap().as_str();
             if let Some(e) = self.romkan_map.get(rom) {
                 e.to_string()
@@ -346,7 +355,7 @@ impl RomKanConverter {
                 rom.to_string()
             }
         });
-        retval.into_owned()
+        retval.into_owned() + pending
     }
 
     pub fn remove_last_char(&self, src: &str) -> String {
@@ -401,6 +410,15 @@ mod tests {
             ("ddha", "っでゃ"),
             ("zzye", "っじぇ"),
             ("tanni", "たんい"),
+            ("kank", "かんk"),
+            ("kannk", "かんk"),
+            ("kan'a", "かんあ"),
+            ("kann'a", "かんあ"),
+            ("kann'", "かん"),
+            ("nnn", "んん"),
+            ("kany", "かny"),
+            ("kanny", "かんy"),
+            ("kanya", "かにゃ"),
         ];
         let converter = RomKanConverter::new();
         for (rom, kana) in data {
@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::cost::calc_cost;
+use crate::cost::calc_cost_f32;
 use crate::graph::candidate::Candidate;
+use crate::user_side_data::user_stats_utils::{decayed_count, evict_least_recently_used, now};
 
 #[derive(Default)]
 pub(crate) struct BiGramUserStats {
@@ -13,6 +14,14 @@ pub(crate) struct BiGramUserStats {
     // V
     /// その単語の出現頻度。「漢字/漢字」がキー。
     pub(crate) word_count: HashMap<String, u32>,
+    /// そのバイグラムを最後に使った時刻。UNIX 時間の秒。
+    pub(crate) last_used: HashMap<String, u64>,
+    /// 指定されていれば、最後に使ってからこの日数ごとに、回数を半分として扱う。
+    half_life_days: Option<f32>,
+    /// 覚えておくバイグラム数の上限。
+    max_entries: Option<usize>,
+    /// 前の単語のキーから、後ろに続いたことのある単語のキーを引くための索引。
+    next_words: HashMap<String, HashSet<String>>,
 }
 
 impl BiGramUserStats {
@@ -20,14 +29,40 @@ impl BiGramUserStats {
         unique_words: u32,
         total_words: u32,
         word_count: HashMap<String, u32>,
+        last_used: HashMap<String, u64>,
     ) -> BiGramUserStats {
-        BiGramUserStats {
+        let mut stats = BiGramUserStats {
             unique_words,
             total_words,
             word_count,
+            last_used,
+            half_life_days: None,
+            max_entries: None,
+            next_words: HashMap::new(),
+        };
+        stats.rebuild_next_words();
+        stats
+    }
+
+    fn rebuild_next_words(&mut self) {
+        self.next_words.clear();
+        for key in self.word_count.keys() {
+            if let Some((key1, key2)) = key.split_once('\t') {
+                self.next_words
+                    .entry(key1.to_string())
+                    .or_default()
+                    .insert(key2.to_string());
+            }
         }
     }
 
+    /// 回数の減らし方と、覚えておくバイグラム数の上限を設定する。上限を超えていれば、古いものから捨てる。
+    pub(crate) fn set_decay(&mut self, half_life_days: Option<f32>, max_entries: Option<usize>) {
+        self.half_life_days = half_life_days;
+        self.max_entries = max_entries;
+        self.evict();
+    }
+
     /**
      * エッジコストを計算する。
      * システム言語モデルのコストよりも安くなるように調整してある。
@@ -37,7 +72,79 @@ impl BiGramUserStats {
         let Some(count) = self.word_count.get(key.as_str()) else {
             return None;
         };
-        Some(calc_cost(*count, self.unique_words, self.total_words))
+        let last_used = *self.last_used.get(key.as_str()).unwrap_or(&0);
+        let count = decayed_count(*count, last_used, self.half_life_days, now);
+        Some(calc_cost_f32(count, self.unique_words, self.total_words))
+    }
+
+    /**
+     * key1 の後ろに入力されたことのある単語のキーと、その回数を返す。
+     * 回数の多いものから順に並ぶ。
+     */
+    pub(crate) fn get_next_words(&self, key1: &str) -> Vec<(String, u32)> {
+        let Some(key2s) = self.next_words.get(key1) else {
+            return Vec::new();
+        };
+        let mut result: Vec<(String, u32)> = key2s
+            .iter()
+            .filter_map(|key2| {
+                self.word_count
+                    .get(&(key1.to_owned() + "\t" + key2))
+                    .map(|count| (key2.to_string(), *count))
+            })
+            .collect();
+        result.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        result
+    }
+
+    /// key1 の後ろに key2 が続くバイグラムを忘れる。
+    pub(crate) fn remove(&mut self, key1: &str, key2: &str) {
+        self.remove_if(|k1, k2| k1 == key1 && k2 == key2);
+    }
+
+    /// key を含むバイグラムをすべて忘れる。
+    pub(crate) fn remove_word(&mut self, key: &str) {
+        self.remove_if(|k1, k2| k1 == key || k2 == key);
+    }
+
+    fn remove_if(&mut self, f: impl Fn(&str, &str) -> bool) {
+        let mut removed = Vec::new();
+        self.word_count.retain(|key, count| {
+            let matched = key.split_once('\t').is_some_and(|(k1, k2)| f(k1, k2));
+            if matched {
+                removed.push((key.to_string(), *count));
+            }
+            !matched
+        });
+        self.last_used
+            .retain(|key, _| self.word_count.contains_key(key));
+        self.forget_entries(removed);
+    }
+
+    fn evict(&mut self) {
+        let Some(max_entries) = self.max_entries else {
+            return;
+        };
+        let removed =
+            evict_least_recently_used(&mut self.word_count, &mut self.last_used, max_entries);
+        self.forget_entries(removed);
+    }
+
+    /// 捨てたバイグラムの分だけ、単語数と索引を更新する。
+    fn forget_entries(&mut self, removed: Vec<(String, u32)>) {
+        for (key, count) in removed {
+            self.unique_words -= 1;
+            self.total_words -= count;
+            let Some((key1, key2)) = key.split_once('\t') else {
+                continue;
+            };
+            if let Some(key2s) = self.next_words.get_mut(key1) {
+                key2s.remove(key2);
+                if key2s.is_empty() {
+                    self.next_words.remove(key1);
+                }
+            }
+        }
     }
 
     pub(crate) fn record_entries(&mut self, candidates: &[Candidate]) {
@@ -46,6 +153,7 @@ impl BiGramUserStats {
         }
 
         // bigram
+        let now = now();
         for i in 1..candidates.len() {
             let Some(candidate1) = candidates.get(i - 1) else {
                 continue;
@@ -55,6 +163,11 @@ impl BiGramUserStats {
             };
 
             let key = candidate1.key() + "\t" + candidate2.key().as_str();
+            self.last_used.insert(key.clone(), now);
+            self.next_words
+                .entry(candidate1.key())
+                .or_default()
+                .insert(candidate2.key());
             if let Some(cnt) = self.word_count.get(&key) {
                 self.word_count.insert(key, cnt + 1);
             } else {
@@ -63,5 +176,81 @@ impl BiGramUserStats {
             }
             self.total_words += 1;
         }
+        self.evict();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_next_words() {
+        let mut stats = BiGramUserStats::default();
+        stats.record_entries(&[
+            Candidate::new("きょう", "今日", 0_f32),
+            Candidate::new("は", "は", 0_f32),
+            Candidate::new("よい", "良い", 0_f32),
+        ]);
+        stats.record_entries(&[
+            Candidate::new("は", "は", 0_f32),
+            Candidate::new("よい", "良い", 0_f32),
+        ]);
+        stats.record_entries(&[
+            Candidate::new("は", "は", 0_f32),
+            Candidate::new("いい", "いい", 0_f32),
+        ]);
+
+        assert_eq!(
+            stats.get_next_words("は/は"),
+            vec![("良い/よい".to_string(), 2), ("いい/いい".to_string(), 1)]
+        );
+        assert_eq!(
+            stats.get_next_words("今日/きょう"),
+            vec![("は/は".to_string(), 1)]
+        );
+        assert!(stats.get_next_words("良い/よい").is_empty());
+
+        stats.remove("は/は", "良い/よい");
+        assert_eq!(
+            stats.get_next_words("は/は"),
+            vec![("いい/いい".to_string(), 1)]
+        );
+        stats.remove_word("は/は");
+        assert!(stats.get_next_words("今日/きょう").is_empty());
+        assert!(stats.get_next_words("は/は").is_empty());
+
+        // 上限を超えて捨てたバイグラムは、索引からも消える。
+        let mut stats = BiGramUserStats::new(
+            2,
+            3,
+            HashMap::from([
+                ("今日/きょう\tは/は".to_string(), 1),
+                ("は/は\t良い/よい".to_string(), 2),
+            ]),
+            HashMap::from([
+                ("今日/きょう\tは/は".to_string(), 20),
+                ("は/は\t良い/よい".to_string(), 10),
+            ]),
+        );
+        stats.set_decay(None, Some(1));
+        assert!(stats.get_next_words("は/は").is_empty());
+        assert_eq!(
+            stats.get_next_words("今日/きょう"),
+            vec![("は/は".to_string(), 1)]
+        );
+        assert_eq!((stats.unique_words, stats.total_words), (1, 1));
+
+        // ファイルから読み込んだ統計データでも引ける。
+        let stats = BiGramUserStats::new(
+            1,
+            1,
+            HashMap::from([("今日/きょう\tは/は".to_string(), 1)]),
+            HashMap::new(),
+        );
+        assert_eq!(
+            stats.get_next_words("今日/きょう"),
+            vec![("は/は".to_string(), 1)]
+        );
     }
 }
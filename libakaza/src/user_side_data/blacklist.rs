@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+
+use anyhow::Result;
+
+/*
+   候補に出さない単語とバイグラムのリスト。
+
+   ユーザー統計データと同じく、単語は "漢字/かな"、バイグラムは "漢字/かな<TAB>漢字/かな" をキーにする。
+   ファイルには1行に1つずつキーを書く。
+*/
+
+#[derive(Default)]
+pub(crate) struct Blacklist {
+    words: HashSet<String>,
+    bigrams: HashSet<String>,
+}
+
+impl Blacklist {
+    pub(crate) fn load(path: &str) -> Result<Blacklist> {
+        let mut blacklist = Blacklist::default();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match line.split_once('\t') {
+                Some((key1, key2)) => blacklist.add_bigram(key1, key2),
+                None => blacklist.add_word(line),
+            }
+        }
+        Ok(blacklist)
+    }
+
+    pub(crate) fn save(&self, path: &str) -> Result<()> {
+        let mut tmpfile = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path.to_string() + ".tmp")?;
+        let mut keys: Vec<&String> = self.words.iter().chain(self.bigrams.iter()).collect();
+        keys.sort();
+        for key in keys {
+            tmpfile.write_fmt(format_args!("{}\n", key))?;
+        }
+        fs::rename(path.to_owned() + ".tmp", path)?;
+        Ok(())
+    }
+
+    pub(crate) fn add_word(&mut self, key: &str) {
+        self.words.insert(key.to_string());
+    }
+
+    /// 削除した場合は true。
+    pub(crate) fn remove_word(&mut self, key: &str) -> bool {
+        self.words.remove(key)
+    }
+
+    pub(crate) fn add_bigram(&mut self, key1: &str, key2: &str) {
+        self.bigrams.insert(key1.to_owned() + "\t" + key2);
+    }
+
+    pub(crate) fn contains_word(&self, key: &str) -> bool {
+        self.words.contains(key)
+    }
+
+    /// 削除したバイグラムがひとつでもあるか。
+    pub(crate) fn has_bigrams(&self) -> bool {
+        !self.bigrams.is_empty()
+    }
+
+    pub(crate) fn contains_bigram(&self, key1: &str, key2: &str) -> bool {
+        self.bigrams.contains(&(key1.to_owned() + "\t" + key2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_save_load() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let path = tmpfile.path().to_str().unwrap();
+
+        let mut blacklist = Blacklist::default();
+        blacklist.add_word("渡し/わたし");
+        blacklist.add_bigram("私/わたし", "派/は");
+        blacklist.save(path)?;
+        assert_eq!(fs::read_to_string(path)?, "渡し/わたし\n私/わたし\t派/は\n");
+
+        let loaded = Blacklist::load(path)?;
+        assert!(loaded.contains_word("渡し/わたし"));
+        assert!(!loaded.contains_word("私/わたし"));
+        assert!(loaded.contains_bigram("私/わたし", "派/は"));
+        assert!(!loaded.contains_bigram("派/は", "私/わたし"));
+        Ok(())
+    }
+}
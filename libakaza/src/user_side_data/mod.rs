@@ -1,5 +1,7 @@
 mod bigram_user_stats;
+mod blacklist;
 mod unigram_user_stats;
 // 調整めんどくさいのでいったんオフ。
 pub mod user_data;
+pub mod user_dict;
 mod user_stats_utils;
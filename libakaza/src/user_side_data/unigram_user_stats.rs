@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
-use crate::cost::calc_cost;
+use crate::cost::calc_cost_f32;
 use crate::graph::candidate::Candidate;
+use crate::user_side_data::user_stats_utils::{decayed_count, evict_least_recently_used, now};
 
 #[derive(Default)]
 pub(crate) struct UniGramUserStats {
@@ -13,6 +14,12 @@ pub(crate) struct UniGramUserStats {
     // V
     /// その単語の出現頻度。「漢字/かな」がキー。
     pub(crate) word_count: HashMap<String, u32>,
+    /// その単語を最後に使った時刻。UNIX 時間の秒。
+    pub(crate) last_used: HashMap<String, u64>,
+    /// 指定されていれば、最後に使ってからこの日数ごとに、回数を半分として扱う。
+    half_life_days: Option<f32>,
+    /// 覚えておく単語数の上限。
+    max_entries: Option<usize>,
 }
 
 impl UniGramUserStats {
@@ -20,14 +27,25 @@ impl UniGramUserStats {
         unique_words: u32,
         total_words: u32,
         word_count: HashMap<String, u32>,
+        last_used: HashMap<String, u64>,
     ) -> UniGramUserStats {
         UniGramUserStats {
             unique_words,
             total_words,
             word_count,
+            last_used,
+            half_life_days: None,
+            max_entries: None,
         }
     }
 
+    /// 回数の減らし方と、覚えておく単語数の上限を設定する。上限を超えていれば、古いものから捨てる。
+    pub(crate) fn set_decay(&mut self, half_life_days: Option<f32>, max_entries: Option<usize>) {
+        self.half_life_days = half_life_days;
+        self.max_entries = max_entries;
+        self.evict();
+    }
+
     /**
      * ノードコストを計算する。
      * システム言語モデルと似ているがちょっと違う式を使ってる模様。
@@ -36,13 +54,38 @@ impl UniGramUserStats {
         let Some(count) = self.word_count.get(key.as_str()) else {
             return None;
         };
+        let last_used = *self.last_used.get(key.as_str()).unwrap_or(&0);
+        let count = decayed_count(*count, last_used, self.half_life_days, now);
 
-        Some(calc_cost(*count, self.unique_words, self.total_words))
+        Some(calc_cost_f32(count, self.unique_words, self.total_words))
+    }
+
+    /// 学習した単語を忘れる。
+    pub(crate) fn remove(&mut self, key: &str) {
+        if let Some(count) = self.word_count.remove(key) {
+            self.unique_words -= 1;
+            self.total_words -= count;
+        }
+        self.last_used.remove(key);
+    }
+
+    fn evict(&mut self) {
+        let Some(max_entries) = self.max_entries else {
+            return;
+        };
+        for (_, count) in
+            evict_least_recently_used(&mut self.word_count, &mut self.last_used, max_entries)
+        {
+            self.unique_words -= 1;
+            self.total_words -= count;
+        }
     }
 
     pub(crate) fn record_entries(&mut self, candidates: &[Candidate]) {
+        let now = now();
         for candidate in candidates {
             let key = candidate.key();
+            self.last_used.insert(key.clone(), now);
             if let Some(i) = self.word_count.get(&key) {
                 self.word_count.insert(key, i + 1);
             } else {
@@ -51,5 +94,49 @@ impl UniGramUserStats {
             }
             self.total_words += 1;
         }
+        self.evict();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decay() {
+        let mut stats = UniGramUserStats::default();
+        stats.record_entries(&[
+            Candidate::new("わたし", "私", 0_f32),
+            Candidate::new("わたし", "渡し", 0_f32),
+        ]);
+        let cost = stats.get_cost("私/わたし".to_string()).unwrap();
+        assert_eq!(stats.get_cost("渡し/わたし".to_string()), Some(cost));
+
+        // 減らさない設定なら、どれだけ前に使ったものでもコストは変わらない。
+        stats.last_used.insert("渡し/わたし".to_string(), 0);
+        assert_eq!(stats.get_cost("渡し/わたし".to_string()), Some(cost));
+
+        // 前に使ったものほど、コストが高くなる。
+        stats.set_decay(Some(30_f32), None);
+        assert_eq!(stats.get_cost("私/わたし".to_string()), Some(cost));
+        assert!(stats.get_cost("渡し/わたし".to_string()).unwrap() > cost);
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut stats = UniGramUserStats::default();
+        stats.set_decay(None, Some(2));
+        stats.record_entries(&[
+            Candidate::new("わたし", "私", 0_f32),
+            Candidate::new("わたし", "渡し", 0_f32),
+        ]);
+        stats.last_used.insert("渡し/わたし".to_string(), 0);
+        stats.record_entries(&[Candidate::new("は", "は", 0_f32)]);
+        // いちばん前に使ったものから捨てる。
+        assert_eq!(stats.word_count.len(), 2);
+        assert_eq!(stats.get_cost("渡し/わたし".to_string()), None);
+        assert!(stats.get_cost("私/わたし".to_string()).is_some());
+        assert_eq!(stats.unique_words, 2);
+        assert_eq!(stats.total_words, 2);
     }
 }
@@ -6,12 +6,15 @@ use std::time::SystemTime;
 use anyhow::Result;
 use log::{debug, info, warn};
 
+use crate::config::UserStatsConfig;
 use crate::graph::candidate::Candidate;
 use crate::graph::word_node::WordNode;
 use crate::kana_trie::base::KanaTrie;
 use crate::kana_trie::cedarwood_kana_trie::CedarwoodKanaTrie;
 use crate::user_side_data::bigram_user_stats::BiGramUserStats;
+use crate::user_side_data::blacklist::Blacklist;
 use crate::user_side_data::unigram_user_stats::UniGramUserStats;
+use crate::user_side_data::user_dict::{UserDict, UserDictEntry};
 use crate::user_side_data::user_stats_utils::{read_user_stats_file, write_user_stats_file};
 
 /**
@@ -27,9 +30,15 @@ pub struct UserData {
 
     unigram_user_stats: UniGramUserStats,
     bigram_user_stats: BiGramUserStats,
+    /// IME から登録した単語。
+    user_dict: UserDict,
+    /// 候補に出さない単語とバイグラム。
+    blacklist: Blacklist,
 
     unigram_path: Option<String>,
     bigram_path: Option<String>,
+    user_dict_path: Option<String>,
+    blacklist_path: Option<String>,
 
     pub(crate) need_save: bool,
 }
@@ -47,11 +56,51 @@ impl UserData {
             .to_str()
             .unwrap()
             .to_string();
+        let user_dict_path = basedir
+            .place_data_file(Path::new("user_dict.v1.txt"))?
+            .to_str()
+            .unwrap()
+            .to_string();
+        let blacklist_path = basedir
+            .place_data_file(Path::new("blacklist.v1.txt"))?
+            .to_str()
+            .unwrap()
+            .to_string();
         info!(
-            "Load user data from default path: unigram={}, bigram={}",
-            unigram_path, bigram_path
+            "Load user data from default path: unigram={}, bigram={}, user_dict={}, blacklist={}",
+            unigram_path, bigram_path, user_dict_path, blacklist_path
         );
-        Ok(UserData::load(&unigram_path, &bigram_path))
+        let mut user_data = UserData::load(&unigram_path, &bigram_path);
+        user_data.load_user_dict(&user_dict_path);
+        user_data.load_blacklist(&blacklist_path);
+        Ok(user_data)
+    }
+
+    /// 候補に出さない単語のリストを読み込む。保存先も path になる。
+    pub fn load_blacklist(&mut self, path: &str) {
+        // 候補を削除したことがなければ、ファイルはない。
+        self.blacklist = match Blacklist::load(path) {
+            Ok(blacklist) => blacklist,
+            Err(err) => {
+                warn!("Cannot load blacklist from {}: {}", path, err);
+                Blacklist::default()
+            }
+        };
+        self.blacklist_path = Some(path.to_string());
+    }
+
+    /// ユーザー辞書を読み込む。保存先も path になる。
+    pub fn load_user_dict(&mut self, path: &str) {
+        // 単語を登録したことがなければ、ファイルはない。
+        self.user_dict = match UserDict::load(path) {
+            Ok(user_dict) => user_dict,
+            Err(err) => {
+                warn!("Cannot load user dictionary from {}: {}", path, err);
+                UserDict::default()
+            }
+        };
+        self.user_dict_path = Some(path.to_string());
+        self.update_kana_trie(self.user_dict.yomis());
     }
 
     pub fn load(unigram_path: &String, bigram_path: &String) -> Self {
@@ -63,10 +112,12 @@ impl UserData {
                 let unique_count = dat.len() as u32;
                 let total_count: u32 = dat.iter().map(|f| f.1).sum();
                 let mut word_count: HashMap<String, u32> = HashMap::new();
-                for (word, count) in dat {
+                let mut last_used: HashMap<String, u64> = HashMap::new();
+                for (word, count, time) in dat {
+                    last_used.insert(word.clone(), time);
                     word_count.insert(word, count);
                 }
-                UniGramUserStats::new(unique_count, total_count, word_count)
+                UniGramUserStats::new(unique_count, total_count, word_count, last_used)
             }
             Err(err) => {
                 warn!(
@@ -74,7 +125,7 @@ impl UserData {
                     unigram_path, err
                 );
 
-                UniGramUserStats::new(0, 0, HashMap::new())
+                UniGramUserStats::new(0, 0, HashMap::new(), HashMap::new())
             }
         };
 
@@ -84,15 +135,17 @@ impl UserData {
                 let unique_count = dat.len() as u32;
                 let total_count: u32 = dat.iter().map(|f| f.1).sum();
                 let mut words_count: HashMap<String, u32> = HashMap::new();
-                for (words, count) in dat {
+                let mut last_used: HashMap<String, u64> = HashMap::new();
+                for (words, count, time) in dat {
+                    last_used.insert(words.clone(), time);
                     words_count.insert(words, count);
                 }
-                BiGramUserStats::new(unique_count, total_count, words_count)
+                BiGramUserStats::new(unique_count, total_count, words_count, last_used)
             }
             Err(err) => {
                 warn!("Cannot load user bigram data from {}: {}", bigram_path, err);
                 // ユーザーデータは初回起動時などにはないので、データがないものとして処理を続行する
-                BiGramUserStats::new(0, 0, HashMap::new())
+                BiGramUserStats::new(0, 0, HashMap::new(), HashMap::new())
             }
         };
 
@@ -128,12 +181,32 @@ impl UserData {
             unigram_user_stats,
             bigram_user_stats,
             kana_trie: Arc::new(Mutex::new(kana_trie)),
+            user_dict: UserDict::default(),
+            blacklist: Blacklist::default(),
             unigram_path: Some(unigram_path.clone()),
             bigram_path: Some(bigram_path.clone()),
+            user_dict_path: None,
+            blacklist_path: None,
             need_save: false,
         }
     }
 
+    /// 学習した回数を時間とともに減らす設定と、覚えておくエントリー数の上限を設定する。
+    pub fn set_stats_config(&mut self, config: &UserStatsConfig) {
+        let unigram_len = self.unigram_user_stats.word_count.len();
+        let bigram_len = self.bigram_user_stats.word_count.len();
+        self.unigram_user_stats
+            .set_decay(config.half_life_days, Some(config.max_entries));
+        self.bigram_user_stats
+            .set_decay(config.half_life_days, Some(config.max_entries));
+        // 上限を超えていて捨てたものがあれば、ファイルにも反映する。
+        if self.unigram_user_stats.word_count.len() != unigram_len
+            || self.bigram_user_stats.word_count.len() != bigram_len
+        {
+            self.need_save = true;
+        }
+    }
+
     /// 入力確定した漢字のリストをユーザー統計データとして記録する。
     /// "Surface/Kana" のフォーマットで渡すこと。
     pub fn record_entries(&mut self, candidates: &[Candidate]) {
@@ -141,19 +214,108 @@ impl UserData {
         self.bigram_user_stats.record_entries(candidates);
 
         // かなトライを更新する
+        self.update_kana_trie(candidates.iter().map(|it| it.yomi.to_string()).collect());
+
+        self.need_save = true;
+    }
+
+    fn update_kana_trie(&self, yomis: Vec<String>) {
         let mut kana_trie = self.kana_trie.lock().unwrap();
-        candidates
-            .iter()
-            .map(|it| it.yomi.to_string())
-            .for_each(|it| {
-                if !kana_trie.contains(it.as_str()) {
-                    kana_trie.update(it.as_str())
-                }
-            });
+        for yomi in yomis {
+            if !yomi.is_empty() && !kana_trie.contains(yomi.as_str()) {
+                kana_trie.update(yomi.as_str())
+            }
+        }
+    }
 
+    /// ユーザー辞書に単語を登録する。同じ読みと表記の単語があれば置き換える。
+    pub fn add_word(&mut self, entry: UserDictEntry) {
+        info!("Add a word to user dictionary: {:?}", entry);
+        // 候補から削除していた単語でも、登録しなおしたら候補に出す。
+        self.blacklist
+            .remove_word(&(entry.surface.to_string() + "/" + entry.yomi.as_str()));
+        self.user_dict.add(entry);
+        self.update_kana_trie(self.user_dict.yomis());
         self.need_save = true;
     }
 
+    /// ユーザー辞書から単語を削除する。削除した場合は true。
+    pub fn remove_word(&mut self, yomi: &str, surface: &str) -> bool {
+        let yomis = self.user_dict.yomis();
+        let removed = self.user_dict.remove(yomi, surface);
+        if removed {
+            info!("Remove a word from user dictionary: {}/{}", surface, yomi);
+            self.prune_kana_trie(yomis);
+            self.need_save = true;
+        }
+        removed
+    }
+
+    /// yomis のうち、ユーザー辞書にも学習したデータにも残っていない読みを、かなトライから削除する。
+    fn prune_kana_trie(&self, yomis: Vec<String>) {
+        let mut kana_trie = self.kana_trie.lock().unwrap();
+        for yomi in yomis {
+            let in_user_stats = self
+                .unigram_user_stats
+                .word_count
+                .keys()
+                .any(|key| key.split_once('/').is_some_and(|(_, it)| it == yomi));
+            if self.user_dict.get(&yomi).is_none() && !in_user_stats {
+                kana_trie.erase(&yomi);
+            }
+        }
+    }
+
+    /// ユーザー辞書に登録されている単語を、登録した順に返す。
+    pub fn list_words(&self) -> &[UserDictEntry] {
+        self.user_dict.entries()
+    }
+
+    /// ユーザー辞書から、読みに対応する候補を引く。"表層;コメント" の形で返す。
+    pub fn get_user_dict_words(&self, yomi: &str) -> Vec<String> {
+        self.user_dict.get(yomi).cloned().unwrap_or_default()
+    }
+
+    /**
+     * 単語を忘れる。学習した統計データとユーザー辞書から削除して、以後は候補に出さない。
+     * ひらがなそのものは、変換できなくならないように候補に残す。
+     */
+    pub fn forget_word(&mut self, yomi: &str, surface: &str) {
+        info!("Forget a word: {}/{}", surface, yomi);
+        let key = surface.to_string() + "/" + yomi;
+        self.unigram_user_stats.remove(&key);
+        self.bigram_user_stats.remove_word(&key);
+        let yomis = self.user_dict.yomis();
+        if self.user_dict.remove(yomi, surface) {
+            self.prune_kana_trie(yomis);
+        }
+        self.blacklist.add_word(&key);
+        self.need_save = true;
+    }
+
+    /// key1("漢字/かな") の後ろに key2 が続くバイグラムを忘れて、以後は使わない。
+    pub fn forget_bigram(&mut self, key1: &str, key2: &str) {
+        info!("Forget a bigram: {} {}", key1, key2);
+        self.bigram_user_stats.remove(key1, key2);
+        self.blacklist.add_bigram(key1, key2);
+        self.need_save = true;
+    }
+
+    /// 候補に出さない単語かどうか。
+    pub fn is_blacklisted_word(&self, yomi: &str, surface: &str) -> bool {
+        self.blacklist
+            .contains_word(&(surface.to_string() + "/" + yomi))
+    }
+
+    /// node1 の後ろに node2 が続くのを避けるべきかどうか。
+    pub fn is_blacklisted_bigram(&self, node1: &WordNode, node2: &WordNode) -> bool {
+        // エッジごとに呼ばれるので、削除したバイグラムがなければキーを作らずに返す。
+        self.blacklist.has_bigrams()
+            && self
+                .blacklist
+                .contains_bigram(node1.key().as_str(), node2.key().as_str())
+    }
+
     pub fn write_user_stats_file(&mut self) -> Result<()> {
         if self.need_save {
             info!(
@@ -164,10 +326,24 @@ impl UserData {
                 self.bigram_user_stats.word_count.len(),
             );
             if let Some(unigram_path) = &self.unigram_path {
-                write_user_stats_file(unigram_path, &self.unigram_user_stats.word_count)?;
+                write_user_stats_file(
+                    unigram_path,
+                    &self.unigram_user_stats.word_count,
+                    &self.unigram_user_stats.last_used,
+                )?;
             }
             if let Some(bigram_path) = &self.bigram_path {
-                write_user_stats_file(bigram_path, &self.bigram_user_stats.word_count)?;
+                write_user_stats_file(
+                    bigram_path,
+                    &self.bigram_user_stats.word_count,
+                    &self.bigram_user_stats.last_used,
+                )?;
+            }
+            if let Some(user_dict_path) = &self.user_dict_path {
+                self.user_dict.save(user_dict_path)?;
+            }
+            if let Some(blacklist_path) = &self.blacklist_path {
+                self.blacklist.save(blacklist_path)?;
             }
 
             self.need_save = false;
@@ -180,6 +356,11 @@ impl UserData {
         self.unigram_user_stats.get_cost(node.key())
     }
 
+    /// key("漢字/かな") の後ろに入力されたことのある単語のキーを、回数の多い順に返す。
+    pub fn get_next_words(&self, key: &str) -> Vec<(String, u32)> {
+        self.bigram_user_stats.get_next_words(key)
+    }
+
     pub fn get_bigram_cost(&self, node1: &WordNode, node2: &WordNode) -> Option<f32> {
         self.bigram_user_stats
             .get_cost(node1.key().as_str(), node2.key().as_str())
@@ -235,4 +416,61 @@ mod tests {
         info!("{}, {}", cost2, cost3);
         assert!(cost2 > cost3);
     }
+
+    #[test]
+    fn test_user_dict() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("user_dict.v1.txt");
+        let path = path.to_str().unwrap();
+
+        let mut user_data = UserData::default();
+        user_data.load_user_dict(path);
+        assert!(user_data.list_words().is_empty());
+
+        user_data.add_word(UserDictEntry::new("あかざ", "赤座"));
+        assert_eq!(user_data.get_user_dict_words("あかざ"), vec!["赤座"]);
+        assert!(user_data.kana_trie.lock().unwrap().contains("あかざ"));
+        user_data.write_user_stats_file()?;
+
+        let mut reloaded = UserData::default();
+        reloaded.load_user_dict(path);
+        assert_eq!(reloaded.list_words(), user_data.list_words());
+
+        assert!(reloaded.remove_word("あかざ", "赤座"));
+        assert!(reloaded.get_user_dict_words("あかざ").is_empty());
+        // ほかに使われていない読みは、かなトライからも削除する。
+        assert!(!reloaded.kana_trie.lock().unwrap().contains("あかざ"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_forget_word() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("blacklist.v1.txt");
+        let path = path.to_str().unwrap();
+
+        let mut user_data = UserData::default();
+        user_data.load_blacklist(path);
+        user_data.record_entries(&[
+            Candidate::new("わたし", "渡し", 0_f32),
+            Candidate::new("は", "は", 0_f32),
+        ]);
+        let watashi = WordNode::new(0, "渡し", "わたし", None);
+        assert!(user_data.get_unigram_cost(&watashi).is_some());
+
+        user_data.forget_word("わたし", "渡し");
+        assert_eq!(user_data.get_unigram_cost(&watashi), None);
+        assert!(user_data.get_next_words("渡し/わたし").is_empty());
+        assert!(user_data.is_blacklisted_word("わたし", "渡し"));
+        user_data.write_user_stats_file()?;
+
+        let mut reloaded = UserData::default();
+        reloaded.load_blacklist(path);
+        assert!(reloaded.is_blacklisted_word("わたし", "渡し"));
+
+        // 登録しなおしたら、候補に出す。
+        reloaded.add_word(UserDictEntry::new("わたし", "渡し"));
+        assert!(!reloaded.is_blacklisted_word("わたし", "渡し"));
+        Ok(())
+    }
 }
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+
+use anyhow::Result;
+use log::info;
+
+use crate::dict::ime_dict::insert_word;
+
+/*
+   ユーザー辞書。IME から登録した単語を保存しておく。
+
+   ファイルは Mozc のユーザー辞書と同じ形式にするので、dict_type: mozc の辞書としても読める。
+
+     よみ<TAB>単語<TAB>品詞<TAB>コメント
+
+   品詞が動詞や形容詞なら、活用した形も変換できるようにする。
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserDictEntry {
+    pub yomi: String,
+    pub surface: String,
+    /// 品詞。"動詞ラ行五段" のような Mozc の品詞名。
+    pub pos: Option<String>,
+    /// コメント。候補のアノテーションとして表示する。
+    pub comment: Option<String>,
+}
+
+impl UserDictEntry {
+    pub fn new(yomi: &str, surface: &str) -> UserDictEntry {
+        UserDictEntry {
+            yomi: yomi.to_string(),
+            surface: surface.to_string(),
+            pos: None,
+            comment: None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UserDict {
+    /// 登録された順の単語。
+    entries: Vec<UserDictEntry>,
+    /// 活用した形も含めた、読みから候補を引くための辞書。
+    dict: HashMap<String, Vec<String>>,
+}
+
+impl UserDict {
+    pub fn load(path: &str) -> Result<UserDict> {
+        let src = fs::read_to_string(path)?;
+        let mut user_dict = UserDict::default();
+        for line in src.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns: Vec<&str> = line.split('\t').collect();
+            if columns.len() < 2 || columns[0].is_empty() || columns[1].is_empty() {
+                info!("Invalid line in user dictionary: {}", line);
+                continue;
+            }
+            let optional = |i: usize| {
+                columns
+                    .get(i)
+                    .filter(|it| !it.is_empty())
+                    .map(|it| it.to_string())
+            };
+            user_dict.add(UserDictEntry {
+                yomi: columns[0].to_string(),
+                surface: columns[1].to_string(),
+                pos: optional(2),
+                comment: optional(3),
+            });
+        }
+        Ok(user_dict)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut tmpfile = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path.to_string() + ".tmp")?;
+        for entry in &self.entries {
+            tmpfile.write_fmt(format_args!(
+                "{}\t{}\t{}\t{}\n",
+                entry.yomi,
+                entry.surface,
+                entry.pos.as_deref().unwrap_or_default(),
+                entry.comment.as_deref().unwrap_or_default()
+            ))?;
+        }
+        fs::rename(path.to_owned() + ".tmp", path)?;
+        Ok(())
+    }
+
+    /// 単語を登録する。同じ読みと表記の単語がすでにあれば、品詞とコメントを置き換える。
+    pub fn add(&mut self, entry: UserDictEntry) {
+        // タブや改行はファイルの書式と衝突するので、空白にしておく。
+        let sanitize = |s: &str| s.replace(['\t', '\r', '\n'], " ");
+        let entry = UserDictEntry {
+            yomi: sanitize(&entry.yomi),
+            surface: sanitize(&entry.surface),
+            pos: entry.pos.as_deref().map(sanitize),
+            comment: entry.comment.as_deref().map(sanitize),
+        };
+        match self
+            .entries
+            .iter_mut()
+            .find(|it| it.yomi == entry.yomi && it.surface == entry.surface)
+        {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+        self.rebuild();
+    }
+
+    /// 単語を削除する。削除した場合は true。
+    pub fn remove(&mut self, yomi: &str, surface: &str) -> bool {
+        let len = self.entries.len();
+        self.entries
+            .retain(|it| !(it.yomi == yomi && it.surface == surface));
+        if self.entries.len() == len {
+            return false;
+        }
+        self.rebuild();
+        true
+    }
+
+    pub fn entries(&self) -> &[UserDictEntry] {
+        &self.entries
+    }
+
+    /// 読みに対応する候補。活用した形も含む。"表層;コメント" の形で返す。
+    pub fn get(&self, yomi: &str) -> Option<&Vec<String>> {
+        self.dict.get(yomi)
+    }
+
+    /// 活用した形も含めた、すべての読み。
+    pub fn yomis(&self) -> Vec<String> {
+        self.dict.keys().cloned().collect()
+    }
+
+    fn rebuild(&mut self) {
+        let mut dict: HashMap<String, Vec<String>> = HashMap::new();
+        for entry in &self.entries {
+            insert_word(
+                &mut dict,
+                &entry.yomi,
+                &entry.surface,
+                entry.pos.as_deref().unwrap_or_default(),
+                entry.comment.as_deref(),
+            );
+        }
+        self.dict = dict;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use crate::dict::ime_dict::parse_mozc_dict;
+
+    use super::*;
+
+    #[test]
+    fn test_add_remove() {
+        let mut user_dict = UserDict::default();
+        user_dict.add(UserDictEntry::new("あかざ", "赤座"));
+        user_dict.add(UserDictEntry {
+            pos: Some("動詞ラ行五段".to_string()),
+            ..UserDictEntry::new("ぐぐる", "ググる")
+        });
+        user_dict.add(UserDictEntry {
+            comment: Some("植物".to_string()),
+            ..UserDictEntry::new("あかざ", "赤座")
+        });
+        assert_eq!(user_dict.entries().len(), 2);
+        assert_eq!(
+            user_dict.get("あかざ"),
+            Some(&vec!["赤座;植物".to_string()])
+        );
+        assert_eq!(
+            user_dict.get("ぐぐった"),
+            Some(&vec!["ググった".to_string()])
+        );
+
+        assert!(user_dict.remove("ぐぐる", "ググる"));
+        assert!(!user_dict.remove("ぐぐる", "ググる"));
+        assert_eq!(user_dict.get("ぐぐった"), None);
+        assert_eq!(user_dict.yomis(), vec!["あかざ".to_string()]);
+    }
+
+    #[test]
+    fn test_save_load() -> Result<()> {
+        let tmpfile = NamedTempFile::new()?;
+        let path = tmpfile.path().to_str().unwrap();
+
+        let mut user_dict = UserDict::default();
+        user_dict.add(UserDictEntry::new("あかざ", "赤座"));
+        user_dict.add(UserDictEntry {
+            pos: Some("動詞ラ行五段".to_string()),
+            comment: Some("検索する".to_string()),
+            ..UserDictEntry::new("ぐぐる", "ググる")
+        });
+        user_dict.save(path)?;
+
+        let src = fs::read_to_string(path)?;
+        assert_eq!(
+            src,
+            "あかざ\t赤座\t\t\nぐぐる\tググる\t動詞ラ行五段\t検索する\n"
+        );
+        // Mozc のユーザー辞書としても読める。
+        assert_eq!(
+            parse_mozc_dict(&src)?.get("ぐぐった"),
+            Some(&vec!["ググった;検索する".to_string()])
+        );
+
+        let loaded = UserDict::load(path)?;
+        assert_eq!(loaded.entries(), user_dict.entries());
+        assert_eq!(loaded.get("ぐぐった"), user_dict.get("ぐぐった"));
+        Ok(())
+    }
+}
@@ -4,30 +4,62 @@ use std::fs;
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::fs::OpenOptionsExt;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub(crate) fn read_user_stats_file(path: &String) -> Result<Vec<(String, u32)>> {
+/**
+ * ユーザー統計データを読む。1行に "キー 回数 最後に使った時刻" を書く。時刻は UNIX 時間の秒。
+ * 時刻のない古い形式の行は、いま使ったものとして扱う。ただし、上限を超えて古いものから捨てるときに
+ * 回数の少ないものから捨てるように、回数の多いものほど新しくなるよう1秒ずつずらしておく。
+ */
+pub(crate) fn read_user_stats_file(path: &String) -> Result<Vec<(String, u32, u64)>> {
     let file = File::open(path)?;
 
-    let mut result: Vec<(String, u32)> = Vec::new();
+    let mut result: Vec<(String, u32, u64)> = Vec::new();
+    // 時刻のない行の、result でのインデックス。
+    let mut legacy: Vec<usize> = Vec::new();
 
     for line in BufReader::new(file).lines() {
         let line = line.context("Cannot read user language model file")?;
         let Some((key, count)) = line.trim().split_once(' ') else {
             continue;
         };
+        let (count, last_used) = match count.split_once(' ') {
+            Some((count, last_used)) => (
+                count,
+                last_used
+                    .parse::<u64>()
+                    .with_context(|| format!("Invalid line in user language model: {}", line))?,
+            ),
+            None => {
+                legacy.push(result.len());
+                (count, 0)
+            }
+        };
 
         let count = count
             .to_string()
             .parse::<u32>()
             .with_context(|| format!("Invalid line in user language model: {}", count))?;
 
-        result.push((key.to_string(), count));
+        result.push((key.to_string(), count, last_used));
+    }
+
+    if !legacy.is_empty() {
+        let now = now();
+        legacy.sort_by_key(|i| std::cmp::Reverse(result[*i].1));
+        for (rank, i) in legacy.into_iter().enumerate() {
+            result[i].2 = now.saturating_sub(rank as u64);
+        }
     }
 
     Ok(result)
 }
 
-pub(crate) fn write_user_stats_file(path: &str, word_count: &HashMap<String, u32>) -> Result<()> {
+pub(crate) fn write_user_stats_file(
+    path: &str,
+    word_count: &HashMap<String, u32>,
+    last_used: &HashMap<String, u64>,
+) -> Result<()> {
     let mut tmpfile = OpenOptions::new()
         .write(true)
         .create(true)
@@ -36,16 +68,72 @@ pub(crate) fn write_user_stats_file(path: &str, word_count: &HashMap<String, u32
         .open(path.to_string() + ".tmp")?;
 
     for (key, cnt) in word_count {
-        tmpfile.write_all(key.as_bytes())?;
-        tmpfile.write_all(" ".as_bytes())?;
-        tmpfile.write_all(cnt.to_string().as_bytes())?;
-        tmpfile.write_all("\n".as_bytes())?;
+        tmpfile.write_fmt(format_args!(
+            "{} {} {}\n",
+            key,
+            cnt,
+            last_used.get(key).unwrap_or(&0)
+        ))?;
     }
     fs::rename(path.to_owned() + ".tmp", path)?;
 
     Ok(())
 }
 
+/// 現在時刻。UNIX 時間の秒。
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs())
+}
+
+/**
+ * 最後に使ってからの時間に応じて減らした回数。half_life_days 日ごとに半分になる。
+ * half_life_days が None なら、回数をそのまま返す。
+ * コストを引くたびに呼ばれるので、現在時刻は減らすときだけ now で求める。
+ */
+pub(crate) fn decayed_count(
+    count: u32,
+    last_used: u64,
+    half_life_days: Option<f32>,
+    now: impl FnOnce() -> u64,
+) -> f32 {
+    let Some(half_life_days) = half_life_days else {
+        return count as f32;
+    };
+    let elapsed_days = now().saturating_sub(last_used) as f32 / (24 * 60 * 60) as f32;
+    count as f32 * 0.5_f32.powf(elapsed_days / half_life_days)
+}
+
+/**
+ * エントリー数が max_entries を超えていたら、最後に使った時刻が古いものから捨てる。
+ * 確定のたびに呼ばれるので、毎回並べかえなくて済むように、上限の 9 割まで一度に減らす。
+ * 捨てたエントリーのキーと回数を返す。
+ */
+pub(crate) fn evict_least_recently_used(
+    word_count: &mut HashMap<String, u32>,
+    last_used: &mut HashMap<String, u64>,
+    max_entries: usize,
+) -> Vec<(String, u32)> {
+    if word_count.len() <= max_entries {
+        return Vec::new();
+    }
+    let remove_len = word_count.len() - (max_entries - max_entries / 10);
+    let mut keys: Vec<(u64, String)> = word_count
+        .keys()
+        .map(|key| (*last_used.get(key).unwrap_or(&0), key.to_string()))
+        .collect();
+    keys.select_nth_unstable(remove_len - 1);
+    keys.truncate(remove_len);
+
+    keys.into_iter()
+        .filter_map(|(_, key)| {
+            last_used.remove(&key);
+            word_count.remove(&key).map(|count| (key, count))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -56,9 +144,84 @@ mod tests {
     fn test_write() {
         let tmpfile = NamedTempFile::new().unwrap();
         let path = tmpfile.path().to_str().unwrap().to_string();
-        write_user_stats_file(&path, &HashMap::from([("渡し".to_string(), 3_u32)])).unwrap();
+        write_user_stats_file(
+            &path,
+            &HashMap::from([("渡し".to_string(), 3_u32)]),
+            &HashMap::from([("渡し".to_string(), 1700000000_u64)]),
+        )
+        .unwrap();
         let mut buf = String::new();
-        File::open(path).unwrap().read_to_string(&mut buf).unwrap();
-        assert_eq!(buf, "渡し 3\n");
+        File::open(&path).unwrap().read_to_string(&mut buf).unwrap();
+        assert_eq!(buf, "渡し 3 1700000000\n");
+        assert_eq!(
+            read_user_stats_file(&path).unwrap(),
+            vec![("渡し".to_string(), 3, 1700000000)]
+        );
+    }
+
+    #[test]
+    fn test_read_without_last_used() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile
+            .write_all("渡し 3\n私 5\n橋 1 1700000000\n".as_bytes())
+            .unwrap();
+        let path = tmpfile.path().to_str().unwrap().to_string();
+        let got = read_user_stats_file(&path).unwrap();
+        assert_eq!(got[0].1, 3);
+        assert!(got[0].2 > 0);
+        // 回数の多いものほど、最近使ったものとして扱う。
+        assert_eq!(got[1].2, got[0].2 + 1);
+        assert_eq!(got[2].2, 1700000000);
+    }
+
+    #[test]
+    fn test_decayed_count() {
+        let day = 24 * 60 * 60;
+        assert_eq!(
+            decayed_count(4, 0, None, || panic!("Decay is disabled")),
+            4_f32
+        );
+        assert_eq!(
+            decayed_count(4, 100 * day, Some(30_f32), || 100 * day),
+            4_f32
+        );
+        assert_eq!(
+            decayed_count(4, 40 * day, Some(30_f32), || 100 * day),
+            1_f32
+        );
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut word_count = HashMap::from([
+            ("a".to_string(), 1_u32),
+            ("b".to_string(), 2_u32),
+            ("c".to_string(), 3_u32),
+        ]);
+        let mut last_used = HashMap::from([
+            ("a".to_string(), 30_u64),
+            ("b".to_string(), 10_u64),
+            ("c".to_string(), 20_u64),
+        ]);
+        let mut removed = evict_least_recently_used(&mut word_count, &mut last_used, 1);
+        removed.sort();
+        assert_eq!(removed, vec![("b".to_string(), 2), ("c".to_string(), 3)]);
+        assert_eq!(word_count, HashMap::from([("a".to_string(), 1_u32)]));
+        assert_eq!(last_used, HashMap::from([("a".to_string(), 30_u64)]));
+        assert!(evict_least_recently_used(&mut word_count, &mut last_used, 1).is_empty());
+    }
+
+    #[test]
+    fn test_evict_least_recently_used_in_batch() {
+        let mut word_count: HashMap<String, u32> =
+            (0..11).map(|i| (i.to_string(), 1_u32)).collect();
+        let mut last_used: HashMap<String, u64> =
+            (0..11).map(|i| (i.to_string(), i as u64)).collect();
+        // 上限の 10 を超えたら、9 まで減らす。
+        let mut removed = evict_least_recently_used(&mut word_count, &mut last_used, 10);
+        removed.sort();
+        assert_eq!(removed, vec![("0".to_string(), 1), ("1".to_string(), 1)]);
+        assert_eq!(word_count.len(), 9);
+        assert_eq!(last_used.len(), 9);
     }
 }
@@ -0,0 +1,151 @@
+use chrono::{Datelike, NaiveDate};
+
+/// 元号。
+#[derive(Debug, PartialEq)]
+pub struct Era {
+    pub name: &'static str,
+    pub yomi: &'static str,
+    /// 改元された日 (年, 月, 日)
+    pub start: (i32, u32, u32),
+}
+
+/// 明治以降の元号。新しいものほどうしろにならべる。
+pub const ERAS: [Era; 5] = [
+    Era {
+        name: "明治",
+        yomi: "めいじ",
+        start: (1868, 10, 23),
+    },
+    Era {
+        name: "大正",
+        yomi: "たいしょう",
+        start: (1912, 7, 30),
+    },
+    Era {
+        name: "昭和",
+        yomi: "しょうわ",
+        start: (1926, 12, 25),
+    },
+    Era {
+        name: "平成",
+        yomi: "へいせい",
+        start: (1989, 1, 8),
+    },
+    Era {
+        name: "令和",
+        yomi: "れいわ",
+        start: (2019, 5, 1),
+    },
+];
+
+impl Era {
+    fn start_date(&self) -> NaiveDate {
+        let (y, m, d) = self.start;
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+}
+
+/// 日付を (元号, 和暦の年) にする。明治より前なら None。
+pub fn to_wareki<D: Datelike>(date: &D) -> Option<(&'static Era, i32)> {
+    let date = NaiveDate::from_ymd_opt(date.year(), date.month(), date.day())?;
+    ERAS.iter()
+        .rev()
+        .find(|era| era.start_date() <= date)
+        .map(|era| (era, date.year() - era.start.0 + 1))
+}
+
+/// 西暦の年にかかる元号と和暦の年を、新しい元号から順に返す。
+/// 改元のあった年は、2019年 → [令和元, 平成31] のように2つになる。
+pub fn eras_of_year(year: i32) -> Vec<(&'static Era, i32)> {
+    let mut result: Vec<(&'static Era, i32)> = Vec::new();
+    for (i, era) in ERAS.iter().enumerate().rev() {
+        if era.start.0 > year {
+            continue;
+        }
+        result.push((era, year - era.start.0 + 1));
+        // 1月1日に改元されていなければ、その年の前半は1つ前の元号になる。
+        if era.start.0 < year || (era.start.1, era.start.2) == (1, 1) || i == 0 {
+            break;
+        }
+    }
+    result
+}
+
+/// 和暦の年を西暦の年にする。西暦の年が i32 に収まらなければ None。
+pub fn from_wareki(era: &Era, year: i32) -> Option<i32> {
+    if year < 1 {
+        return None;
+    }
+    era.start.0.checked_add(year - 1)
+}
+
+/// "れいわ6ねん" のような読みを (元号, 和暦の年) にする。「がんねん」は1年。
+pub fn parse_wareki_yomi(yomi: &str) -> Option<(&'static Era, i32)> {
+    let (era, rest) = ERAS
+        .iter()
+        .find_map(|era| yomi.strip_prefix(era.yomi).map(|rest| (era, rest)))?;
+    let year = rest.strip_suffix("ねん")?;
+    if year == "がん" {
+        return Some((era, 1));
+    }
+    if year.is_empty() || !year.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((era, year.parse::<i32>().ok()?))
+}
+
+/// "令和5" のような、元号つきの年の表記。1年目は「元」とする。
+pub fn format_wareki_year(era: &Era, year: i32) -> String {
+    if year == 1 {
+        format!("{}元", era.name)
+    } else {
+        format!("{}{}", era.name, year)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_wareki() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let wareki =
+            |y, m, d| to_wareki(&date(y, m, d)).map(|(era, year)| format_wareki_year(era, year));
+        assert_eq!(wareki(2023, 1, 16), Some("令和5".to_string()));
+        assert_eq!(wareki(2019, 4, 30), Some("平成31".to_string()));
+        assert_eq!(wareki(2019, 5, 1), Some("令和元".to_string()));
+        assert_eq!(wareki(1989, 1, 7), Some("昭和64".to_string()));
+        assert_eq!(wareki(1868, 1, 1), None);
+    }
+
+    #[test]
+    fn test_eras_of_year() {
+        let names = |year| {
+            eras_of_year(year)
+                .iter()
+                .map(|(era, year)| format_wareki_year(era, *year))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(2024), vec!["令和6"]);
+        assert_eq!(names(2019), vec!["令和元", "平成31"]);
+        assert_eq!(names(1868), vec!["明治元"]);
+        assert_eq!(names(1800), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_wareki_yomi() {
+        let (era, year) = parse_wareki_yomi("れいわ6ねん").unwrap();
+        assert_eq!((era.name, year), ("令和", 6));
+        assert_eq!(from_wareki(era, year), Some(2024));
+
+        let (era, year) = parse_wareki_yomi("へいせいがんねん").unwrap();
+        assert_eq!(from_wareki(era, year), Some(1989));
+
+        let (era, year) = parse_wareki_yomi("れいわ2147483647ねん").unwrap();
+        assert_eq!(from_wareki(era, year), None);
+
+        assert_eq!(parse_wareki_yomi("れいわ"), None);
+        assert_eq!(parse_wareki_yomi("れいわねん"), None);
+    }
+}
@@ -14,11 +14,9 @@ mod tests {
         BigramWordViterbiEngine, BigramWordViterbiEngineBuilder,
     };
     use libakaza::graph::candidate::Candidate;
-    use libakaza::lm::system_bigram::MarisaSystemBigramLM;
     use libakaza::lm::system_unigram_lm::MarisaSystemUnigramLM;
 
-    fn load_akaza() -> Result<BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM>>
-    {
+    fn load_akaza() -> Result<BigramWordViterbiEngine<MarisaSystemUnigramLM>> {
         let datadir = env!("CARGO_MANIFEST_DIR").to_string() + "/../akaza-data/data/";
         assert!(Path::new(datadir.as_str()).exists());
         env::set_var("AKAZA_DATA_DIR", datadir);
@@ -29,12 +27,14 @@ mod tests {
                     encoding: Some("euc-jp".to_string()),
                     path: (env!("CARGO_MANIFEST_DIR").to_string()
                         + "/../akaza-data/skk-dev-dict/SKK-JISYO.L"),
+                    ..Default::default()
                 },
                 DictConfig {
                     dict_type: "skk".to_string(),
                     encoding: Some("utf-8".to_string()),
                     path: (env!("CARGO_MANIFEST_DIR").to_string()
                         + "/../akaza-data/data/SKK-JISYO.akaza"),
+                    ..Default::default()
                 },
             ],
             single_term: Some(vec![DictConfig {
@@ -42,13 +42,15 @@ mod tests {
                 encoding: Some("utf-8".to_string()),
                 path: (env!("CARGO_MANIFEST_DIR").to_string()
                     + "/../akaza-data/skk-dev-dict/SKK-JISYO.emoji"),
+                ..Default::default()
             }]),
+            ..Default::default()
         })
         .build()
     }
 
     struct Tester {
-        akaza: BigramWordViterbiEngine<MarisaSystemUnigramLM, MarisaSystemBigramLM>,
+        akaza: BigramWordViterbiEngine<MarisaSystemUnigramLM>,
     }
 
     impl Tester {
@@ -146,6 +148,7 @@ mod tests {
             ("ぜんぶでてるやつ", "全部でてる奴"),
             ("えらべる", "選べる"),
             ("わたしだよ", "私だよ"),
+            ("watasik", "私k"),
             ("にほんごじょうほう", "日本語情報"),
             // ("れいわ", "令和"),
             ("ちいさい", "小さい"),
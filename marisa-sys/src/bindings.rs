@@ -2,7 +2,7 @@
 // low level C wrappers
 // ---------------------------------------------------
 
-use std::ffi::{c_char, CString};
+use std::ffi::{c_char, CStr, CString};
 use std::os::raw::c_void;
 
 use anyhow::{anyhow, Result};
@@ -13,9 +13,6 @@ struct marisa_obj {
     trie: *mut c_void,
 }
 
-// ↓↓ It's unsafe operation. I'll remove this in the future.
-unsafe impl Send for marisa_obj {}
-
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 struct marisa_keyset {
@@ -36,6 +33,7 @@ extern "C" {
     fn marisa_release(self_: *mut marisa_obj);
     fn marisa_build(self_: *mut marisa_obj, keyset: *mut marisa_keyset);
     fn marisa_load(self_: *mut marisa_obj, filename: *const c_char) -> *mut marisa_exception;
+    fn marisa_mmap(self_: *mut marisa_obj, filename: *const c_char) -> *mut marisa_exception;
     fn marisa_save(self_: *mut marisa_obj, filename: *const c_char) -> *mut marisa_exception;
     fn marisa_predictive_search(
         self_: *mut marisa_obj,
@@ -70,6 +68,20 @@ pub struct Marisa {
     marisa: *mut marisa_obj,
 }
 
+// marisa::Trie は、構築後の検索(const メンバ関数)であればスレッドセーフ。
+// 検索時の状態は呼び出しごとに作る marisa::Agent が持つので、&self からの同時アクセスは問題ない。
+// 内容を書き換える load/build は &mut self を要求しているので、Rust 側の借用規則で排他される。
+unsafe impl Send for Marisa {}
+unsafe impl Sync for Marisa {}
+
+impl Drop for Marisa {
+    fn drop(&mut self) {
+        unsafe {
+            marisa_release(self.marisa);
+        }
+    }
+}
+
 impl Default for Marisa {
     fn default() -> Marisa {
         let marisa = unsafe { marisa_new() };
@@ -77,6 +89,15 @@ impl Default for Marisa {
     }
 }
 
+/// C 側で確保したエラーメッセージを読み出して、例外オブジェクトを解放する。
+unsafe fn take_error_message(exc: *mut marisa_exception) -> String {
+    let message = CStr::from_ptr((*exc).error_message)
+        .to_string_lossy()
+        .into_owned();
+    marisa_exception_release(exc);
+    message
+}
+
 impl Marisa {
     pub fn load(&mut self, filename: &str) -> Result<()> {
         unsafe {
@@ -87,9 +108,25 @@ impl Marisa {
             } else {
                 Err(anyhow!(
                     "Cannot load file: {}, file={}",
-                    CString::from_raw((*exc).error_message)
-                        .into_string()
-                        .unwrap(),
+                    take_error_message(exc),
+                    filename
+                ))
+            }
+        }
+    }
+
+    /// ファイルをメモリマップして読み込む。ファイル全体を読み込むよりも起動が速い。
+    /// マップしたファイルは、この Marisa が drop されるまで変更してはいけない。
+    pub fn mmap(&mut self, filename: &str) -> Result<()> {
+        unsafe {
+            let cstring = CString::new(filename).unwrap();
+            let exc = marisa_mmap(self.marisa, cstring.as_ptr());
+            if exc.is_null() {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "Cannot mmap file: {}, file={}",
+                    take_error_message(exc),
                     filename
                 ))
             }
@@ -111,9 +148,7 @@ impl Marisa {
             } else {
                 Err(anyhow!(
                     "Cannot save marisa file: {}, filename={}",
-                    CString::from_raw((*exc).error_message)
-                        .into_string()
-                        .unwrap(),
+                    take_error_message(exc),
                     filename
                 ))
             }
@@ -184,6 +219,9 @@ pub struct Keyset {
     keyset: *mut marisa_keyset,
 }
 
+// Keyset は他と共有しないハンドルなので、スレッド間で移動しても問題ない。
+unsafe impl Send for Keyset {}
+
 impl Default for Keyset {
     fn default() -> Self {
         unsafe {
@@ -255,6 +293,13 @@ mod tests {
             assert_eq!(got[0].0, "age");
             assert_eq!(got[1].0, "apple");
         }
+
+        // mmap it
+        {
+            let mut marisa = Marisa::default();
+            marisa.mmap(&tmpfile).unwrap();
+            assert_eq!(marisa.num_keys(), 3);
+        }
     }
 
     #[test]
@@ -268,5 +313,16 @@ mod tests {
                 panic!() // unreachable
             }
         }
+        {
+            let mut marisa = Marisa::default();
+            let err = marisa.mmap("UNKNOWN_PATH").unwrap_err();
+            assert!(err.to_string().contains("MARISA_IO_"));
+        }
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Marisa>();
     }
 }
@@ -32,6 +32,15 @@ marisa_exception* marisa_load(marisa_obj* self, const char* filename) {
     }
 }
 
+marisa_exception* marisa_mmap(marisa_obj* self, const char* filename) {
+    try {
+        self->trie->mmap(filename);
+        return NULL;
+    } catch (const marisa::Exception &e) {
+        return marisa_exception_new(e);
+    }
+}
+
 void marisa_exception_release(marisa_exception* exc) {
     if (exc != NULL) {
         free(exc->error_message);
@@ -22,6 +22,7 @@ extern "C" {
     void marisa_release(marisa_obj* self);
     void marisa_build(marisa_obj* self, marisa_keyset* keyset);
     marisa_exception* marisa_load(marisa_obj* self, const char* filename);
+    marisa_exception* marisa_mmap(marisa_obj* self, const char* filename);
     marisa_exception* marisa_save(marisa_obj* self, const char* filename);
     void marisa_predictive_search(marisa_obj *self, const char* query, size_t query_len, void* user_data, marisa_callback cb);
     void marisa_common_prefix_search(marisa_obj *self, const char* query, size_t query_len, void* user_data, marisa_callback cb);